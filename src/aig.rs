@@ -0,0 +1,488 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::circuit::{Circuit, CircuitBuilder};
+use crate::expression_parser::{Gate, GateType};
+
+/// An edge into the graph: twice the index of the node it leads to, plus one when the edge is complemented.
+pub type Literal = u32;
+
+pub const FALSE: Literal = 0;
+pub const TRUE: Literal = 1;
+
+pub fn negate(literal: Literal) -> Literal {
+    literal ^ 1
+}
+
+pub fn is_complemented(literal: Literal) -> bool {
+    literal & 1 == 1
+}
+
+pub fn node_index(literal: Literal) -> usize {
+    (literal >> 1) as usize
+}
+
+/// Node 0 is the constant false, the inputs come next and the ANDs follow in the order they're made,
+/// so every AND comes after both of its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AigNode {
+    Constant,
+    Input(usize),
+    And(Literal, Literal),
+}
+
+/// An and-inverter graph: two-input ANDs joined by edges that may be complemented.
+/// Structural hashing means an AND of the same two operands is only ever made once,
+/// so expressions built in one graph share whatever they have in common.
+pub struct Aig {
+    inputs: Vec<String>,
+    nodes: Vec<AigNode>,
+    strash: HashMap<(Literal, Literal), Literal>,
+}
+
+impl Aig {
+    pub fn new(inputs: Vec<String>) -> Aig {
+        let mut nodes = vec![AigNode::Constant];
+        nodes.extend((0..inputs.len()).map(AigNode::Input));
+        Aig {
+            inputs,
+            nodes,
+            strash: HashMap::new(),
+        }
+    }
+
+    /// Builds `tree` over its inputs, sorted.
+    pub fn from_gate(tree: &Gate) -> (Aig, Literal) {
+        let mut aig = Aig::new(tree.input_names());
+        let root = aig.build(tree);
+        (aig, root)
+    }
+
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    pub fn node(&self, literal: Literal) -> AigNode {
+        self.nodes[node_index(literal)]
+    }
+
+    pub fn variable(&mut self, name: &str) -> Literal {
+        let position = self
+            .inputs
+            .iter()
+            .position(|v| v == name)
+            .unwrap_or_else(|| panic!("{} is not an input of the AIG", name));
+        2 * (position as Literal + 1)
+    }
+
+    /// The AND of `a` and `b`, simplified where one is constant or they're the same signal either way up,
+    /// and shared with any AND already made of the same operands.
+    pub fn and(&mut self, a: Literal, b: Literal) -> Literal {
+        if a == FALSE || b == FALSE || a == negate(b) {
+            return FALSE;
+        }
+        if a == TRUE || a == b {
+            return b;
+        }
+        if b == TRUE {
+            return a;
+        }
+        let key = (a.min(b), a.max(b));
+        if let Some(&literal) = self.strash.get(&key) {
+            return literal;
+        }
+        self.nodes.push(AigNode::And(a, b));
+        let literal = 2 * (self.nodes.len() - 1) as Literal;
+        self.strash.insert(key, literal);
+        literal
+    }
+
+    pub fn or(&mut self, a: Literal, b: Literal) -> Literal {
+        negate(self.and(negate(a), negate(b)))
+    }
+
+    pub fn xor(&mut self, a: Literal, b: Literal) -> Literal {
+        let only_a = self.and(a, negate(b));
+        let only_b = self.and(negate(a), b);
+        self.or(only_a, only_b)
+    }
+
+    pub fn build(&mut self, tree: &Gate) -> Literal {
+        self.build_reading(tree, &HashMap::new())
+    }
+
+    /// Builds every signal of `circuit` once, however many others read it, returning each one's literal by name.
+    pub fn build_circuit(&mut self, circuit: &Circuit) -> Result<HashMap<String, Literal>, String> {
+        let mut signals = HashMap::new();
+        for i in circuit.topological_order()? {
+            let (name, gate) = &circuit.signals[i];
+            let literal = self.build_reading(gate, &signals);
+            signals.insert(name.clone(), literal);
+        }
+        Ok(signals)
+    }
+
+    /// Builds `tree`, reading INPUT gates named in `signals` as those literals rather than as variables.
+    fn build_reading(&mut self, tree: &Gate, signals: &HashMap<String, Literal>) -> Literal {
+        // operands are stored last first
+        let inputs: Vec<Literal> = tree
+            .get_inputs()
+            .iter()
+            .rev()
+            .map(|g| self.build_reading(g, signals))
+            .collect();
+        match tree.get_type() {
+            GateType::INPUT => match &tree.get_name()[..] {
+                "0" => FALSE,
+                "1" => TRUE,
+                name => match signals.get(name) {
+                    Some(&literal) => literal,
+                    None => self.variable(name),
+                },
+            },
+            GateType::NOT => negate(inputs[0]),
+            GateType::AND => inputs.into_iter().reduce(|a, b| self.and(a, b)).unwrap(),
+            GateType::OR => inputs.into_iter().reduce(|a, b| self.or(a, b)).unwrap(),
+            GateType::XOR => inputs.into_iter().reduce(|a, b| self.xor(a, b)).unwrap(),
+            GateType::NAND => negate(inputs.into_iter().reduce(|a, b| self.and(a, b)).unwrap()),
+            GateType::NOR => negate(inputs.into_iter().reduce(|a, b| self.or(a, b)).unwrap()),
+            GateType::XNOR => negate(inputs.into_iter().reduce(|a, b| self.xor(a, b)).unwrap()),
+        }
+    }
+
+    /// Indices of the ANDs reachable from `roots`, operands before the ANDs using them.
+    pub fn reachable(&self, roots: &[Literal]) -> Vec<usize> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack: Vec<usize> = roots.iter().map(|&r| node_index(r)).collect();
+        while let Some(index) = stack.pop() {
+            if seen[index] {
+                continue;
+            }
+            seen[index] = true;
+            if let AigNode::And(a, b) = self.nodes[index] {
+                stack.push(node_index(a));
+                stack.push(node_index(b));
+            }
+        }
+        (0..self.nodes.len())
+            .filter(|&i| seen[i] && matches!(self.nodes[i], AigNode::And(..)))
+            .collect()
+    }
+
+    /// Simulates 64 input assignments at once: bit `k` of `patterns[i]` is input `i` in the `k`th assignment,
+    /// and bit `k` of each returned word is that node's value in it.
+    pub fn simulate(&self, patterns: &[u64]) -> Vec<u64> {
+        let mut words: Vec<u64> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let word = match *node {
+                AigNode::Constant => 0,
+                AigNode::Input(i) => patterns[i],
+                AigNode::And(a, b) => value(&words, a) & value(&words, b),
+            };
+            words.push(word);
+        }
+        words
+    }
+
+    /// The first row of the truth table, with the first input as the most significant bit,
+    /// on which `a` and `b` differ, simulating 64 rows at a time.
+    pub fn find_difference(&self, a: Literal, b: Literal) -> Option<u64> {
+        let n = self.inputs.len();
+        let rows = 1_u64 << n;
+        (0..rows).step_by(64).find_map(|start| {
+            let mut patterns = vec![0_u64; n];
+            for k in 0..(rows - start).min(64) {
+                for (i, pattern) in patterns.iter_mut().enumerate() {
+                    *pattern |= ((start + k) >> (n - 1 - i) & 1) << k;
+                }
+            }
+            let words = self.simulate(&patterns);
+            let used = if rows - start >= 64 {
+                u64::MAX
+            } else {
+                (1 << (rows - start)) - 1
+            };
+            let difference = (value(&words, a) ^ value(&words, b)) & used;
+            (difference != 0).then(|| start + difference.trailing_zeros() as u64)
+        })
+    }
+
+    /// Adds the ANDs below `root` to `builder`, each once, and returns the signal, input or constant it comes down to.
+    /// A complemented edge becomes a NOT, shared by every AND reading that node inverted.
+    pub fn add_to(&self, builder: &mut CircuitBuilder, root: Literal) -> String {
+        let mut names: Vec<String> = vec![String::new(); self.nodes.len()];
+        for (i, name) in self.inputs.iter().enumerate() {
+            names[i + 1] = name.clone();
+        }
+        let edge = |builder: &mut CircuitBuilder, names: &[String], literal: Literal| match (
+            node_index(literal),
+            is_complemented(literal),
+        ) {
+            (0, complemented) => String::from(if complemented { "1" } else { "0" }),
+            (index, false) => names[index].clone(),
+            (index, true) => builder.gate(GateType::NOT, vec![names[index].clone()]),
+        };
+        for index in self.reachable(&[root]) {
+            if let AigNode::And(a, b) = self.nodes[index] {
+                let operands = vec![edge(builder, &names, a), edge(builder, &names, b)];
+                names[index] = builder.gate(GateType::AND, operands);
+            }
+        }
+        edge(builder, &names, root)
+    }
+
+    /// The graph below `roots` rebuilt with two-level rewriting: each AND looks one level into its operands
+    /// before it's made, so `a(ab)` is `ab`, `a(a'b)` is 0, `a(a'b)'` is `a` and `a(ab)'` is `ab'`,
+    /// and ANDs of two ANDs with opposite operands are 0.
+    pub fn rewritten(&self, roots: &[Literal]) -> (Aig, Vec<Literal>) {
+        self.rebuild(roots, |aig, a, b| aig.and_rewriting(a, b))
+    }
+
+    fn and_rewriting(&mut self, a: Literal, b: Literal) -> Literal {
+        for (x, y) in [(a, b), (b, a)] {
+            if let AigNode::And(c, d) = self.node(y) {
+                if !is_complemented(y) {
+                    if x == c || x == d {
+                        return y;
+                    }
+                    if x == negate(c) || x == negate(d) {
+                        return FALSE;
+                    }
+                } else {
+                    if x == negate(c) || x == negate(d) {
+                        return x;
+                    }
+                    if x == c {
+                        return self.and(x, negate(d));
+                    }
+                    if x == d {
+                        return self.and(x, negate(c));
+                    }
+                }
+            }
+        }
+        if let (AigNode::And(c, d), AigNode::And(e, f)) = (self.node(a), self.node(b)) {
+            let opposite = [c, d].iter().any(|&l| l == negate(e) || l == negate(f));
+            if !is_complemented(a) && !is_complemented(b) && opposite {
+                return FALSE;
+            }
+        }
+        self.and(a, b)
+    }
+
+    /// The graph below `roots` rebuilt with each tree of ANDs balanced to the least depth.
+    /// An AND read once, uncomplemented, by another is part of that one's tree; the leaves of each tree
+    /// are joined shallowest first, so `((ab)c)d` becomes `(ab)(cd)`.
+    pub fn balanced(&self, roots: &[Literal]) -> (Aig, Vec<Literal>) {
+        let reachable = self.reachable(roots);
+        let mut reads = vec![0_usize; self.nodes.len()];
+        for &root in roots {
+            reads[node_index(root)] += 1;
+        }
+        for &index in &reachable {
+            if let AigNode::And(a, b) = self.nodes[index] {
+                reads[node_index(a)] += 1;
+                reads[node_index(b)] += 1;
+            }
+        }
+        let mut absorbed = vec![false; self.nodes.len()];
+        for &index in &reachable {
+            if let AigNode::And(a, b) = self.nodes[index] {
+                for operand in [a, b] {
+                    let is_and = matches!(self.node(operand), AigNode::And(..));
+                    if is_and && !is_complemented(operand) && reads[node_index(operand)] == 1 {
+                        absorbed[node_index(operand)] = true;
+                    }
+                }
+            }
+        }
+
+        let mut balanced = Aig::new(self.inputs.clone());
+        let mut copies = self.input_copies();
+        let mut depths = vec![0_usize; balanced.nodes.len()];
+        for &index in reachable.iter().filter(|&&index| !absorbed[index]) {
+            let mut leaves = Vec::new();
+            let mut stack = vec![2 * index as Literal];
+            while let Some(literal) = stack.pop() {
+                match self.node(literal) {
+                    AigNode::And(a, b)
+                        if node_index(literal) == index || absorbed[node_index(literal)] =>
+                    {
+                        stack.push(a);
+                        stack.push(b);
+                    }
+                    _ => leaves.push(copy_of(&copies, literal)),
+                }
+            }
+            let mut queue: BinaryHeap<Reverse<(usize, Literal)>> = leaves
+                .into_iter()
+                .map(|leaf| Reverse((depths[node_index(leaf)], leaf)))
+                .collect();
+            while queue.len() > 1 {
+                let Reverse((depth_a, a)) = queue.pop().unwrap();
+                let Reverse((depth_b, b)) = queue.pop().unwrap();
+                let and = balanced.and(a, b);
+                if node_index(and) >= depths.len() {
+                    depths.push(depth_a.max(depth_b) + 1);
+                }
+                queue.push(Reverse((depths[node_index(and)], and)));
+            }
+            copies[index] = queue.pop().unwrap().0 .1;
+        }
+        let roots = roots.iter().map(|&root| copy_of(&copies, root)).collect();
+        (balanced, roots)
+    }
+
+    /// A new graph over the same inputs with the ANDs below `roots` copied in order,
+    /// each made by `and` from its operands' copies, and the copies of `roots`.
+    fn rebuild<F>(&self, roots: &[Literal], mut and: F) -> (Aig, Vec<Literal>)
+    where
+        F: FnMut(&mut Aig, Literal, Literal) -> Literal,
+    {
+        let mut rebuilt = Aig::new(self.inputs.clone());
+        let mut copies = self.input_copies();
+        for index in self.reachable(roots) {
+            if let AigNode::And(a, b) = self.nodes[index] {
+                copies[index] = and(&mut rebuilt, copy_of(&copies, a), copy_of(&copies, b));
+            }
+        }
+        let roots = roots.iter().map(|&root| copy_of(&copies, root)).collect();
+        (rebuilt, roots)
+    }
+
+    /// What each node becomes in a new graph over the same inputs, with the constant and the inputs filled in.
+    fn input_copies(&self) -> Vec<Literal> {
+        let mut copies = vec![FALSE; self.nodes.len()];
+        for (i, copy) in copies.iter_mut().enumerate().take(self.inputs.len() + 1) {
+            *copy = 2 * i as Literal;
+        }
+        copies
+    }
+}
+
+/// The copy of `literal` in a rebuilt graph, complemented as `literal` is.
+fn copy_of(copies: &[Literal], literal: Literal) -> Literal {
+    copies[node_index(literal)] ^ (literal & 1)
+}
+
+/// The value of `literal` in each of the assignments `words` was simulated for.
+pub fn value(words: &[u64], literal: Literal) -> u64 {
+    let word = words[node_index(literal)];
+    if is_complemented(literal) {
+        !word
+    } else {
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::{
+        expression_variables, parse_boolean_expression, parse_into_aig,
+    };
+
+    fn variables(names: &str) -> Vec<String> {
+        names.chars().map(String::from).collect()
+    }
+
+    /// Bit `k` is the value of `literal` in row `k` of the truth table, for up to 6 inputs.
+    fn truth_table(aig: &Aig, literal: Literal) -> u64 {
+        let n = aig.inputs().len();
+        let patterns: Vec<u64> = (0..n)
+            .map(|i| {
+                (0..1 << n)
+                    .filter(|row| row >> i & 1 == 1)
+                    .fold(0, |word, row| word | 1 << row)
+            })
+            .collect();
+        value(&aig.simulate(&patterns), literal) & (u64::MAX >> (64 - (1 << n)))
+    }
+
+    /// How many ANDs deep `literal` is.
+    fn depth(aig: &Aig, literal: Literal) -> usize {
+        match aig.node(literal) {
+            AigNode::And(a, b) => 1 + depth(aig, a).max(depth(aig, b)),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn parsing_into_the_graph_matches_building_the_tree() {
+        for expression in ["ab + c'", "(a ^ b)'c", "a(b + 0) + 1c", "a'b' + (c + d)'"] {
+            let mut aig = Aig::new(expression_variables(expression));
+            let parsed = parse_into_aig(expression, &mut aig);
+            let built = aig.build(&parse_boolean_expression(expression));
+            assert_eq!(parsed, built, "{}", expression);
+        }
+    }
+
+    #[test]
+    fn rewriting_looks_one_level_into_operands() {
+        let mut aig = Aig::new(variables("abc"));
+        let (a, b, c) = (aig.variable("a"), aig.variable("b"), aig.variable("c"));
+        let ab = aig.and(a, b);
+        let not_a_b = aig.and(negate(a), b);
+        let ac = aig.and(a, c);
+        let roots = [
+            aig.and(a, ab),
+            aig.and(a, not_a_b),
+            aig.and(a, negate(not_a_b)),
+            aig.and(a, negate(ab)),
+            aig.and(not_a_b, ac),
+        ];
+
+        let (mut rewritten, roots) = aig.rewritten(&roots);
+        // structural hashing hands back the rewritten graph's own nodes for these
+        let (a, b) = (rewritten.variable("a"), rewritten.variable("b"));
+        let ab = rewritten.and(a, b);
+        let a_not_b = rewritten.and(a, negate(b));
+        assert_eq!(roots, vec![ab, FALSE, a, a_not_b, FALSE]);
+    }
+
+    #[test]
+    fn balancing_halves_a_chain() {
+        let names = variables("abcdefgh");
+        let mut aig = Aig::new(names.clone());
+        let chain = names
+            .iter()
+            .map(|name| aig.variable(name))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .reduce(|a, b| aig.and(a, b))
+            .unwrap();
+        assert_eq!(depth(&aig, chain), 7);
+
+        let (balanced, roots) = aig.balanced(&[chain]);
+        assert_eq!(depth(&balanced, roots[0]), 3);
+        assert_eq!(balanced.reachable(&roots).len(), 7);
+    }
+
+    #[test]
+    fn balancing_keeps_shared_ands_whole() {
+        let tree = parse_boolean_expression("(abc)d + (abc)'e");
+        let (aig, root) = Aig::from_gate(&tree);
+        let (balanced, roots) = aig.balanced(&[root]);
+        assert_eq!(truth_table(&balanced, roots[0]), truth_table(&aig, root));
+        assert_eq!(
+            balanced.reachable(&roots).len(),
+            aig.reachable(&[root]).len()
+        );
+    }
+
+    #[test]
+    fn shared_ands_become_one_signal_each() {
+        // every XOR of the chain reads the one before twice, which a tree would copy at each link
+        let expression: String = ('a'..='t').map(String::from).collect::<Vec<_>>().join("^");
+        let (aig, root) = Aig::from_gate(&parse_boolean_expression(&expression));
+        let mut builder = CircuitBuilder::new(aig.inputs());
+        let output = aig.add_to(&mut builder, root);
+        let circuit = builder.finish("f", output);
+        let ands = circuit
+            .signals
+            .iter()
+            .filter(|(_, gate)| gate.get_type() == GateType::AND)
+            .count();
+        assert_eq!(ands, aig.reachable(&[root]).len());
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use crate::aig::{is_complemented, node_index, Aig, AigNode, Literal};
+use crate::circuit::{self, Circuit, NetlistGate};
+use crate::expression_parser::{Gate, GateType};
+
+/// An ASCII AIGER (`aag`) file computing `tree` with the ANDs of its structurally hashed and-inverter graph,
+/// with a symbol per input and for the output, and the expression as its comment.
+/// AIGER numbers the inputs from 1 and the ANDs after them, so ANDs no longer used are left out.
+pub fn aag(tree: &Gate, output: &str) -> String {
+    let (aig, root) = Aig::from_gate(tree);
+    let inputs = aig.inputs().len();
+    let ands = aig.reachable(&[root]);
+    let mut variables: HashMap<usize, usize> = (0..=inputs).map(|i| (i, i)).collect();
+    for (i, &index) in ands.iter().enumerate() {
+        variables.insert(index, inputs + i + 1);
+    }
+    let literal =
+        |literal: Literal| 2 * variables[&node_index(literal)] + is_complemented(literal) as usize;
+
+    let mut text = format!(
+        "aag {} {} 0 1 {}\n",
+        inputs + ands.len(),
+        inputs,
+        ands.len()
+    );
+    for i in 0..inputs {
+        text.push_str(&format!("{}\n", 2 * (i + 1)));
+    }
+    text.push_str(&format!("{}\n", literal(root)));
+    for &index in &ands {
+        if let AigNode::And(a, b) = aig.node(2 * index as Literal) {
+            let (a, b) = (literal(a), literal(b));
+            text.push_str(&format!(
+                "{} {} {}\n",
+                2 * variables[&index],
+                a.max(b),
+                a.min(b)
+            ));
+        }
+    }
+    for (i, name) in aig.inputs().iter().enumerate() {
+        text.push_str(&format!("i{} {}\n", i, name));
+    }
+    text.push_str(&format!("o0 {}\nc\n{}\n", output, tree.get_name()));
+    text
+}
+
+/// Reads an ASCII AIGER file without latches into a circuit with a signal per AND.
+/// Inputs and outputs are named by the symbol table where it names them, and `i0`, `o0`, ... otherwise;
+/// the ANDs are named `n` and their variable number.
+pub fn parse_aag(text: &str) -> Result<Circuit, String> {
+    let mut lines = text.lines();
+    let header: Vec<&str> = lines.next().unwrap_or("").split_whitespace().collect();
+    if header.first() != Some(&"aag") {
+        return Err(String::from(
+            "expected an ASCII AIGER header, aag M I L O A",
+        ));
+    }
+    let counts = header[1..]
+        .iter()
+        .map(|n| n.parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| format!("{} isn't a valid header", header.join(" ")))?;
+    let (variables, input_count, latches, output_count, and_count) = match counts.as_slice() {
+        [m, i, l, o, a] => (*m, *i, *l, *o, *a),
+        _ => return Err(format!("{} isn't a valid header", header.join(" "))),
+    };
+    if latches > 0 {
+        return Err(String::from(
+            "only combinational circuits can be read, but this one has latches",
+        ));
+    }
+
+    let mut numbers = |count: usize, per_line: usize, what: &str| {
+        (0..count)
+            .map(|_| {
+                let line = lines
+                    .next()
+                    .ok_or_else(|| format!("the file ends before its {}", what))?;
+                let fields = line
+                    .split_whitespace()
+                    .map(|n| n.parse::<usize>())
+                    .collect::<Result<Vec<usize>, _>>()
+                    .map_err(|_| format!("{} isn't a valid {} line", line, what))?;
+                if fields.len() != per_line || fields.iter().any(|&l| l / 2 > variables) {
+                    return Err(format!("{} isn't a valid {} line", line, what));
+                }
+                Ok(fields)
+            })
+            .collect::<Result<Vec<Vec<usize>>, String>>()
+    };
+    let input_literals = numbers(input_count, 1, "inputs")?;
+    let output_literals = numbers(output_count, 1, "outputs")?;
+    let and_lines = numbers(and_count, 3, "ANDs")?;
+
+    let mut inputs: Vec<String> = (0..input_count).map(|i| format!("i{}", i)).collect();
+    let mut outputs: Vec<String> = (0..output_count).map(|i| format!("o{}", i)).collect();
+    for line in lines {
+        if line == "c" {
+            break;
+        }
+        let (symbol, name) = line.split_once(' ').unwrap_or((line, ""));
+        let list = match symbol.chars().next() {
+            Some('i') => &mut inputs,
+            Some('o') => &mut outputs,
+            _ => continue,
+        };
+        if let Some(slot) = symbol[1..]
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| list.get_mut(i))
+        {
+            if !name.is_empty() {
+                *slot = String::from(name);
+            }
+        }
+    }
+
+    let mut names: Vec<Option<String>> = vec![None; variables + 1];
+    for (literal, name) in input_literals.iter().zip(&inputs) {
+        names[literal[0] / 2] = Some(name.clone());
+    }
+    for line in &and_lines {
+        let mut name = format!("n{}", line[0] / 2);
+        while inputs.contains(&name) || outputs.contains(&name) {
+            name.push('_');
+        }
+        names[line[0] / 2] = Some(name);
+    }
+    let operand = |literal: usize| -> Result<Gate, String> {
+        let gate = if literal / 2 == 0 {
+            Gate::input("0")
+        } else {
+            let name = names[literal / 2]
+                .as_ref()
+                .ok_or_else(|| format!("variable {} is used but never defined", literal / 2))?;
+            Gate::input(name)
+        };
+        Ok(if literal % 2 == 1 {
+            match &gate.get_name()[..] {
+                "0" => Gate::input("1"),
+                _ => Gate::new(GateType::NOT, vec![gate]),
+            }
+        } else {
+            gate
+        })
+    };
+
+    let mut gates = Vec::new();
+    for line in &and_lines {
+        gates.push(NetlistGate {
+            output: names[line[0] / 2].clone().unwrap(),
+            gate_type: Some(GateType::AND),
+            operands: vec![operand(line[1])?, operand(line[2])?],
+        });
+    }
+    // outputs are buffers of their literal, which from_netlist gives a signal of their own
+    let mut output_names = Vec::new();
+    for (literal, mut name) in output_literals.iter().zip(outputs) {
+        while inputs.contains(&name) || output_names.contains(&name) {
+            name.push_str("_out");
+        }
+        gates.push(NetlistGate {
+            output: name.clone(),
+            gate_type: None,
+            operands: vec![operand(literal[0])?],
+        });
+        output_names.push(name);
+    }
+    circuit::from_netlist(&inputs, output_names, gates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equivalence::assert_round_trips;
+
+    #[test]
+    fn graphs_read_back_as_the_same_function() {
+        // symbols that look like the literals AIGER numbers its inputs and gates with
+        let (i0, l2) = (Gate::input("i0"), Gate::input("2"));
+        let collision = Gate::new(
+            GateType::XOR,
+            vec![
+                Gate::new(GateType::NOR, vec![i0.clone(), Gate::input("1")]),
+                Gate::new(GateType::AND, vec![i0, l2]),
+            ],
+        );
+        assert_round_trips(&|tree| aag(tree, "f"), parse_aag, &[collision]);
+    }
+}
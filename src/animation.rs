@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, ImageBuffer, Rgba, RgbaImage};
+
+use crate::expression_parser::minterm_assignment;
+
+/// Past this many inputs there are too many rows to step through one by one.
+const MAX_VARIABLES: usize = 10;
+const FRAME_DELAY_MS: u32 = 1200;
+
+/// One frame for each row of the truth table over `variables`, in counting order.
+/// `draw` gets the row's assignment and a caption spelling it out, prefixed by `title` if there is one.
+pub fn truth_table_frames<F>(
+    variables: &[String],
+    title: Option<&str>,
+    mut draw: F,
+) -> Result<Vec<RgbaImage>, String>
+where
+    F: FnMut(HashMap<String, bool>, String) -> RgbaImage,
+{
+    if variables.len() > MAX_VARIABLES {
+        return Err(format!(
+            "{} inputs make {} frames, animations stop at {} inputs",
+            variables.len(),
+            1_u64 << variables.len(),
+            MAX_VARIABLES
+        ));
+    }
+
+    Ok((0..(1_u64 << variables.len()))
+        .map(|row| {
+            let assignment = minterm_assignment(variables, row);
+            let values: Vec<String> = variables
+                .iter()
+                .map(|v| format!("{}={}", v, assignment[v] as u8))
+                .collect();
+            let caption = match title {
+                Some(title) => format!("{}: {}", title, values.join(" ")),
+                None => values.join(" "),
+            };
+            draw(assignment, caption)
+        })
+        .collect())
+}
+
+/// Writes the frames as an animated GIF if `path` ends in `.gif`,
+/// otherwise as numbered PNGs in the directory `path`.
+/// Frames are padded to the largest of them so they all line up.
+pub fn save_frames(frames: Vec<RgbaImage>, path: &str) -> Result<(), String> {
+    let width = frames.iter().map(|f| f.width()).max().unwrap_or(1);
+    let height = frames.iter().map(|f| f.height()).max().unwrap_or(1);
+    let padded = frames.into_iter().map(|frame| {
+        let mut canvas: RgbaImage =
+            ImageBuffer::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+        image::imageops::overlay(&mut canvas, &frame, 0, 0);
+        canvas
+    });
+
+    if path.ends_with(".gif") {
+        let file = File::create(path).map_err(|e| format!("couldn't create {}: {}", path, e))?;
+        let mut encoder = GifEncoder::new_with_speed(file, 10);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|e| e.to_string())?;
+        encoder
+            .encode_frames(padded.map(|buffer| {
+                Frame::from_parts(buffer, 0, 0, Delay::from_numer_denom_ms(FRAME_DELAY_MS, 1))
+            }))
+            .map_err(|e| e.to_string())
+    } else {
+        fs::create_dir_all(path).map_err(|e| format!("couldn't create {}: {}", path, e))?;
+        for (i, frame) in padded.enumerate() {
+            frame
+                .save(format!("{}/frame_{:03}.png", path, i))
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_step_through_the_rows_in_counting_order() {
+        let variables = vec![String::from("a"), String::from("b")];
+        let mut captions = Vec::new();
+        let frames = truth_table_frames(&variables, Some("f"), |assignment, caption| {
+            captions.push(caption);
+            // a frame as wide as the row number, to tell them apart
+            let row = 2 * assignment["a"] as u32 + assignment["b"] as u32;
+            RgbaImage::new(row + 1, 1)
+        })
+        .unwrap();
+        assert_eq!(
+            captions,
+            vec!["f: a=0 b=0", "f: a=0 b=1", "f: a=1 b=0", "f: a=1 b=1"]
+        );
+        let widths: Vec<u32> = frames.iter().map(|frame| frame.width()).collect();
+        assert_eq!(widths, vec![1, 2, 3, 4]);
+
+        let many: Vec<String> = (0..=MAX_VARIABLES).map(|i| format!("x{}", i)).collect();
+        assert!(truth_table_frames(&many, None, |_, _| RgbaImage::new(1, 1)).is_err());
+    }
+
+    #[test]
+    fn saved_frames_are_padded_to_the_largest() {
+        let directory = std::env::temp_dir().join(format!("frames_{}", std::process::id()));
+        let path = directory.to_str().unwrap();
+        let frames = vec![
+            RgbaImage::from_pixel(2, 3, Rgba([0, 0, 0, 255])),
+            RgbaImage::from_pixel(4, 1, Rgba([0, 0, 0, 255])),
+        ];
+        save_frames(frames, path).unwrap();
+
+        let second = image::open(directory.join("frame_001.png"))
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(second.dimensions(), (4, 3));
+        assert_eq!(*second.get_pixel(3, 0), Rgba([0, 0, 0, 255]));
+        assert_eq!(*second.get_pixel(0, 2), Rgba([255, 255, 255, 255]));
+        assert!(directory.join("frame_000.png").exists());
+        assert!(!directory.join("frame_002.png").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::expression_parser::{Gate, GateType};
+
+pub type NodeId = usize;
+
+pub const FALSE: NodeId = 0;
+pub const TRUE: NodeId = 1;
+
+/// A decision on the variable at `level` of the order: follow `low` when it's 0 and `high` when it's 1.
+/// The two terminals sit one level past the last variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BddNode {
+    pub level: usize,
+    pub low: NodeId,
+    pub high: NodeId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Operation {
+    And,
+    Or,
+    Xor,
+}
+
+impl Operation {
+    fn on_terminals(&self, a: bool, b: bool) -> bool {
+        match self {
+            Operation::And => a && b,
+            Operation::Or => a || b,
+            Operation::Xor => a != b,
+        }
+    }
+}
+
+/// A reduced ordered binary decision diagram manager.
+/// Every function built in one manager shares its nodes, so two roots are equal exactly when
+/// their functions are.
+pub struct Bdd {
+    order: Vec<String>,
+    nodes: Vec<BddNode>,
+    unique: HashMap<BddNode, NodeId>,
+    apply_cache: HashMap<(Operation, NodeId, NodeId), NodeId>,
+}
+
+impl Bdd {
+    pub fn new(order: Vec<String>) -> Bdd {
+        let terminal_level = order.len();
+        Bdd {
+            order,
+            nodes: vec![
+                BddNode {
+                    level: terminal_level,
+                    low: FALSE,
+                    high: FALSE,
+                },
+                BddNode {
+                    level: terminal_level,
+                    low: TRUE,
+                    high: TRUE,
+                },
+            ],
+            unique: HashMap::new(),
+            apply_cache: HashMap::new(),
+        }
+    }
+
+    /// Builds `tree` with the variables in `order`; inputs missing from `order` go after it, sorted.
+    pub fn from_gate(tree: &Gate, order: &[String]) -> (Bdd, NodeId) {
+        let mut full_order = order.to_vec();
+        for name in tree.input_names() {
+            if !full_order.contains(&name) {
+                full_order.push(name);
+            }
+        }
+
+        let mut bdd = Bdd::new(full_order);
+        let root = bdd.build(tree);
+        (bdd, root)
+    }
+
+    pub fn order(&self) -> &[String] {
+        &self.order
+    }
+
+    pub fn node(&self, id: NodeId) -> BddNode {
+        self.nodes[id]
+    }
+
+    pub fn is_terminal(&self, id: NodeId) -> bool {
+        id == FALSE || id == TRUE
+    }
+
+    fn make(&mut self, level: usize, low: NodeId, high: NodeId) -> NodeId {
+        if low == high {
+            return low;
+        }
+        let node = BddNode { level, low, high };
+        if let Some(&id) = self.unique.get(&node) {
+            return id;
+        }
+        self.nodes.push(node);
+        self.unique.insert(node, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    pub fn variable(&mut self, name: &str) -> NodeId {
+        let level = self
+            .order
+            .iter()
+            .position(|v| v == name)
+            .unwrap_or_else(|| panic!("{} is not in the BDD variable order", name));
+        self.make(level, FALSE, TRUE)
+    }
+
+    pub fn not(&mut self, a: NodeId) -> NodeId {
+        self.apply(Operation::Xor, a, TRUE)
+    }
+
+    pub fn and(&mut self, a: NodeId, b: NodeId) -> NodeId {
+        self.apply(Operation::And, a, b)
+    }
+
+    pub fn or(&mut self, a: NodeId, b: NodeId) -> NodeId {
+        self.apply(Operation::Or, a, b)
+    }
+
+    pub fn xor(&mut self, a: NodeId, b: NodeId) -> NodeId {
+        self.apply(Operation::Xor, a, b)
+    }
+
+    fn apply(&mut self, operation: Operation, a: NodeId, b: NodeId) -> NodeId {
+        if self.is_terminal(a) && self.is_terminal(b) {
+            return operation.on_terminals(a == TRUE, b == TRUE) as NodeId;
+        }
+        let key = if a <= b {
+            (operation, a, b)
+        } else {
+            (operation, b, a)
+        };
+        if let Some(&id) = self.apply_cache.get(&key) {
+            return id;
+        }
+
+        let (node_a, node_b) = (self.nodes[a], self.nodes[b]);
+        let level = node_a.level.min(node_b.level);
+        let (a_low, a_high) = if node_a.level == level {
+            (node_a.low, node_a.high)
+        } else {
+            (a, a)
+        };
+        let (b_low, b_high) = if node_b.level == level {
+            (node_b.low, node_b.high)
+        } else {
+            (b, b)
+        };
+
+        let low = self.apply(operation, a_low, b_low);
+        let high = self.apply(operation, a_high, b_high);
+        let result = self.make(level, low, high);
+        self.apply_cache.insert(key, result);
+        result
+    }
+
+    pub fn build(&mut self, tree: &Gate) -> NodeId {
+        let inputs: Vec<NodeId> = tree.get_inputs().iter().map(|g| self.build(g)).collect();
+        match tree.get_type() {
+            GateType::INPUT => match &tree.get_name()[..] {
+                "0" => FALSE,
+                "1" => TRUE,
+                name => self.variable(name),
+            },
+            GateType::NOT => self.not(inputs[0]),
+            GateType::AND => inputs.into_iter().reduce(|a, b| self.and(a, b)).unwrap(),
+            GateType::OR => inputs.into_iter().reduce(|a, b| self.or(a, b)).unwrap(),
+            GateType::NAND => {
+                let and = inputs.into_iter().reduce(|a, b| self.and(a, b)).unwrap();
+                self.not(and)
+            }
+            GateType::NOR => {
+                let or = inputs.into_iter().reduce(|a, b| self.or(a, b)).unwrap();
+                self.not(or)
+            }
+            GateType::XOR => inputs.into_iter().reduce(|a, b| self.xor(a, b)).unwrap(),
+            GateType::XNOR => {
+                let xor = inputs.into_iter().reduce(|a, b| self.xor(a, b)).unwrap();
+                self.not(xor)
+            }
+        }
+    }
+
+    /// Decision nodes reachable from `root`, parents before children.
+    pub fn reachable(&self, root: NodeId) -> Vec<NodeId> {
+        let mut seen: HashSet<NodeId> = HashSet::new();
+        let mut found: Vec<NodeId> = Vec::new();
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            if self.is_terminal(id) || !seen.insert(id) {
+                continue;
+            }
+            found.push(id);
+            stack.push(self.nodes[id].high);
+            stack.push(self.nodes[id].low);
+        }
+        found.sort_by_key(|&id| self.nodes[id].level);
+        found
+    }
+
+    pub fn node_count(&self, root: NodeId) -> usize {
+        self.reachable(root).len()
+    }
+
+    /// Some input assignment that makes `root` true, with variables the path skips set to 0.
+    pub fn satisfying_assignment(&self, root: NodeId) -> Option<Vec<(String, bool)>> {
+        if root == FALSE {
+            return None;
+        }
+
+        let mut values = vec![false; self.order.len()];
+        let mut id = root;
+        while !self.is_terminal(id) {
+            let node = self.nodes[id];
+            if node.low != FALSE {
+                id = node.low;
+            } else {
+                values[node.level] = true;
+                id = node.high;
+            }
+        }
+        Some(self.order.iter().cloned().zip(values).collect())
+    }
+
+    /// The number of assignments of all the variables in the order that make `root` true.
+    pub fn satisfying_count(&self, root: NodeId) -> u128 {
+        let mut memo: HashMap<NodeId, u128> = HashMap::new();
+        let top_level = self.nodes[root].level;
+        self.count_below(root, &mut memo) << top_level
+    }
+
+    fn count_below(&self, id: NodeId, memo: &mut HashMap<NodeId, u128>) -> u128 {
+        if self.is_terminal(id) {
+            return (id == TRUE) as u128;
+        }
+        if let Some(&count) = memo.get(&id) {
+            return count;
+        }
+        let node = self.nodes[id];
+        let skipped = |child: NodeId| self.nodes[child].level - node.level - 1;
+        let count = (self.count_below(node.low, memo) << skipped(node.low))
+            + (self.count_below(node.high, memo) << skipped(node.high));
+        memo.insert(id, count);
+        count
+    }
+
+    /// Copies the function at `root` into a fresh manager using `order`.
+    pub fn reordered(&self, root: NodeId, order: Vec<String>) -> (Bdd, NodeId) {
+        let mut target = Bdd::new(order);
+        let mut memo: HashMap<NodeId, NodeId> = HashMap::new();
+        let new_root = self.transfer(root, &mut target, &mut memo);
+        (target, new_root)
+    }
+
+    fn transfer(&self, id: NodeId, target: &mut Bdd, memo: &mut HashMap<NodeId, NodeId>) -> NodeId {
+        if self.is_terminal(id) {
+            return id;
+        }
+        if let Some(&copied) = memo.get(&id) {
+            return copied;
+        }
+
+        let node = self.nodes[id];
+        let low = self.transfer(node.low, target, memo);
+        let high = self.transfer(node.high, target, memo);
+        let variable = target.variable(&self.order[node.level]);
+
+        // (x AND high) OR (x' AND low)
+        let not_variable = target.not(variable);
+        let with_high = target.and(variable, high);
+        let with_low = target.and(not_variable, low);
+        let copied = target.or(with_high, with_low);
+
+        memo.insert(id, copied);
+        copied
+    }
+
+    /// Sifting in the manner of Rudell's: take each variable in turn, busiest first, try it at every
+    /// position in the order and leave it wherever the diagram came out smallest. Rather than moving the
+    /// variable by swapping adjacent levels in place as Rudell does, each position is tried by rebuilding
+    /// the diagram with `reordered`, which is simpler and fast enough for the diagrams drawn here.
+    pub fn sift(&self, root: NodeId) -> (Bdd, NodeId) {
+        let mut best_order = self.order.clone();
+        let (mut best, mut best_root) = self.reordered(root, best_order.clone());
+        let mut best_size = best.node_count(best_root);
+
+        let mut by_busyness: Vec<String> = self.order.clone();
+        let reachable = self.reachable(root);
+        by_busyness.sort_by_key(|name| {
+            let level = self.order.iter().position(|v| v == name).unwrap();
+            std::cmp::Reverse(
+                reachable
+                    .iter()
+                    .filter(|&&id| self.nodes[id].level == level)
+                    .count(),
+            )
+        });
+
+        for name in by_busyness {
+            let mut without: Vec<String> = best_order.clone();
+            without.retain(|v| *v != name);
+
+            for position in 0..=without.len() {
+                let mut order = without.clone();
+                order.insert(position, name.clone());
+                if order == best_order {
+                    continue;
+                }
+
+                let (candidate, candidate_root) = best.reordered(best_root, order.clone());
+                let size = candidate.node_count(candidate_root);
+                if size < best_size {
+                    best_size = size;
+                    best_order = order;
+                    best = candidate;
+                    best_root = candidate_root;
+                }
+            }
+        }
+        (best, best_root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::{minterm_assignment, parse_boolean_expression};
+
+    fn names(variables: &str) -> Vec<String> {
+        variables.chars().map(String::from).collect()
+    }
+
+    /// Follows `root` down to a terminal for `assignment`.
+    fn value(bdd: &Bdd, root: NodeId, assignment: &HashMap<String, bool>) -> bool {
+        let mut id = root;
+        while !bdd.is_terminal(id) {
+            let node = bdd.node(id);
+            id = if assignment[&bdd.order()[node.level]] {
+                node.high
+            } else {
+                node.low
+            };
+        }
+        id == TRUE
+    }
+
+    fn assert_computes(bdd: &Bdd, root: NodeId, tree: &Gate) {
+        let variables = tree.input_names();
+        for row in 0..1 << variables.len() {
+            let assignment = minterm_assignment(&variables, row);
+            assert_eq!(
+                value(bdd, root, &assignment),
+                tree.evaluate(&assignment),
+                "{} on row {}",
+                tree.get_name(),
+                row
+            );
+        }
+    }
+
+    #[test]
+    fn reordering_and_sifting_keep_the_function() {
+        // pairing each variable with one three places on is the textbook bad order
+        let tree = parse_boolean_expression("ad + be + cf");
+        let (bdd, root) = Bdd::from_gate(&tree, &names("abcdef"));
+        assert_computes(&bdd, root, &tree);
+
+        let (reordered, reordered_root) = bdd.reordered(root, names("adbecf"));
+        assert_computes(&reordered, reordered_root, &tree);
+        assert!(reordered.node_count(reordered_root) < bdd.node_count(root));
+
+        let (sifted, sifted_root) = bdd.sift(root);
+        assert_computes(&sifted, sifted_root, &tree);
+        assert!(sifted.node_count(sifted_root) <= reordered.node_count(reordered_root));
+    }
+
+    #[test]
+    fn sifting_never_grows_the_diagram() {
+        for expression in ["ab + c", "a ^ b ^ c ^ d", "(a + b)(c + d)'", "abc + a'b'c'"] {
+            let tree = parse_boolean_expression(expression);
+            let (bdd, root) = Bdd::from_gate(&tree, &[]);
+            let (sifted, sifted_root) = bdd.sift(root);
+            assert_computes(&sifted, sifted_root, &tree);
+            assert!(
+                sifted.node_count(sifted_root) <= bdd.node_count(root),
+                "{}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn satisfying_count_counts_the_true_rows() {
+        let (bdd, root) = Bdd::from_gate(&parse_boolean_expression("ab + c"), &names("abc"));
+        assert_eq!(bdd.satisfying_count(root), 5);
+        // c is on the last level, so the variables above it double the count twice
+        let (bdd, root) = Bdd::from_gate(&parse_boolean_expression("c"), &names("abc"));
+        assert_eq!(bdd.satisfying_count(root), 4);
+        let (bdd, root) = Bdd::from_gate(&parse_boolean_expression("aa'"), &names("a"));
+        assert_eq!(bdd.satisfying_count(root), 0);
+    }
+
+    #[test]
+    fn satisfying_assignments_make_the_function_true() {
+        for expression in ["ab'c", "(a ^ b)(b + c')", "a' + b"] {
+            let tree = parse_boolean_expression(expression);
+            let (bdd, root) = Bdd::from_gate(&tree, &[]);
+            let assignment: HashMap<String, bool> = bdd
+                .satisfying_assignment(root)
+                .unwrap()
+                .into_iter()
+                .collect();
+            assert!(tree.evaluate(&assignment), "{}", expression);
+        }
+        let (bdd, root) = Bdd::from_gate(&parse_boolean_expression("aa'"), &[]);
+        assert_eq!(bdd.satisfying_assignment(root), None);
+    }
+}
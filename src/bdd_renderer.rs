@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use image::{ImageBuffer, Rgba, RgbaImage};
+use rusttype::Font;
+
+use crate::bdd::{Bdd, NodeId, FALSE, TRUE};
+use crate::renderer::{draw_text, load_font, text_width};
+
+const NODE_RADIUS: i32 = 20;
+const NODE_SPACING: u32 = 70;
+const LEVEL_SPACING: u32 = 80;
+const MARGIN: u32 = 40;
+const FONT_SIZE: f32 = 22.0;
+const DASH_LENGTH: i32 = 6;
+/// How far clear of a node's outline an edge passing its row has to stay.
+const EDGE_CLEARANCE: i32 = 4;
+
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// Draws a BDD top to bottom, one row per variable level and the terminals along the bottom.
+/// 0-edges are dashed and 1-edges are solid.
+pub struct BddRenderer {
+    image: RgbaImage,
+    font: Font<'static>,
+    positions: HashMap<NodeId, (i32, i32)>,
+    /// The row each node is drawn in.
+    row_of: HashMap<NodeId, usize>,
+    /// The x of every node in each row, left to right.
+    row_xs: Vec<Vec<i32>>,
+}
+
+impl BddRenderer {
+    pub fn new(bdd: &Bdd, root: NodeId) -> BddRenderer {
+        let reachable = bdd.reachable(root);
+
+        let mut rows: Vec<Vec<NodeId>> = Vec::new();
+        let mut last_level = None;
+        for &id in &reachable {
+            let level = bdd.node(id).level;
+            if last_level != Some(level) {
+                rows.push(Vec::new());
+                last_level = Some(level);
+            }
+            rows.last_mut().unwrap().push(id);
+        }
+
+        // terminals get a row of their own, even when the function is constant
+        let terminals: Vec<NodeId> = [FALSE, TRUE]
+            .iter()
+            .copied()
+            .filter(|&t| {
+                root == t
+                    || reachable
+                        .iter()
+                        .any(|&id| bdd.node(id).low == t || bdd.node(id).high == t)
+            })
+            .collect();
+        rows.push(terminals);
+
+        let widest = rows.iter().map(|r| r.len()).max().unwrap_or(1) as u32;
+        let width = 2 * MARGIN + widest * NODE_SPACING;
+        let height = 2 * MARGIN + (rows.len() as u32 - 1) * LEVEL_SPACING + 2 * NODE_RADIUS as u32;
+
+        let mut positions = HashMap::new();
+        let mut row_of = HashMap::new();
+        for (row_index, row) in rows.iter().enumerate() {
+            let y = (MARGIN + NODE_RADIUS as u32 + row_index as u32 * LEVEL_SPACING) as i32;
+            let row_width = row.len() as u32 * NODE_SPACING;
+            for (i, &id) in row.iter().enumerate() {
+                let x =
+                    ((width - row_width) / 2 + i as u32 * NODE_SPACING + NODE_SPACING / 2) as i32;
+                positions.insert(id, (x, y));
+                row_of.insert(id, row_index);
+            }
+        }
+        let row_xs = rows
+            .iter()
+            .map(|row| row.iter().map(|id| positions[id].0).collect())
+            .collect();
+
+        BddRenderer {
+            image: ImageBuffer::from_fn(width, height, |_, _| Rgba([255, 255, 255, 255])),
+            font: load_font(),
+            positions,
+            row_of,
+            row_xs,
+        }
+    }
+
+    pub fn draw(&mut self, bdd: &Bdd, root: NodeId) {
+        for id in bdd.reachable(root) {
+            let node = bdd.node(id);
+            // 0-edges leave from the lower left of the node and 1-edges from the lower right,
+            // so the two stay apart when both children sit straight below
+            for (child, dashed, side) in [(node.low, true, -1), (node.high, false, 1)] {
+                let route = self.route(id, child, side);
+                for segment in route.windows(2) {
+                    self.line(segment[0], segment[1], dashed);
+                }
+            }
+        }
+
+        for id in bdd.reachable(root) {
+            let (x, y) = self.positions[&id];
+            self.circle(x, y);
+            let name = bdd.order()[bdd.node(id).level].clone();
+            self.centered_text(&name, x, y);
+        }
+        for terminal in [FALSE, TRUE] {
+            if let Some(&(x, y)) = self.positions.get(&terminal) {
+                self.square(x, y);
+                self.centered_text(if terminal == TRUE { "1" } else { "0" }, x, y);
+            }
+        }
+    }
+
+    /// The points the edge from `parent` to `child` runs through, leaving from `side` of the parent.
+    /// An edge skipping rows bends through the gap nearest its way in each row it crosses,
+    /// rather than running through the nodes there.
+    fn route(&self, parent: NodeId, child: NodeId, side: i32) -> Vec<(i32, i32)> {
+        let (from, to) = (self.positions[&parent], self.positions[&child]);
+        let end = (to.0, to.1 - NODE_RADIUS);
+        let mut points = vec![(from.0 + side * NODE_RADIUS / 2, from.1 + NODE_RADIUS - 3)];
+        for row in self.row_of[&parent] + 1..self.row_of[&child] {
+            let y = (MARGIN + NODE_RADIUS as u32 + row as u32 * LEVEL_SPACING) as i32;
+            let last = points[points.len() - 1];
+            let straight = last.0 + (end.0 - last.0) * (y - last.1) / (end.1 - last.1);
+            let xs = &self.row_xs[row];
+            let blocked = xs
+                .iter()
+                .any(|x| (x - straight).abs() < NODE_RADIUS + EDGE_CLEARANCE);
+            let x = if blocked {
+                // halfway between neighbouring nodes, or half a spacing past the ends of the row
+                let gaps = std::iter::once(xs[0] - NODE_SPACING as i32 / 2)
+                    .chain(xs.windows(2).map(|pair| (pair[0] + pair[1]) / 2))
+                    .chain(std::iter::once(xs[xs.len() - 1] + NODE_SPACING as i32 / 2));
+                gaps.min_by_key(|gap| (gap - straight).abs()).unwrap()
+            } else {
+                straight
+            };
+            points.push((x, y));
+        }
+        points.push(end);
+        points
+    }
+
+    fn put(&mut self, x: i32, y: i32) {
+        self.put_color(x, y, BLACK);
+    }
+
+    fn put_color(&mut self, x: i32, y: i32, color: Rgba<u8>) {
+        if x >= 0 && y >= 0 && (x as u32) < self.image.width() && (y as u32) < self.image.height() {
+            self.image.put_pixel(x as u32, y as u32, color);
+        }
+    }
+
+    /// A two pixel wide line, broken into dashes when `dashed` is set.
+    fn line(&mut self, from: (i32, i32), to: (i32, i32), dashed: bool) {
+        let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).max(1);
+        for step in 0..=steps {
+            if dashed && (step / DASH_LENGTH) % 2 == 1 {
+                continue;
+            }
+            let x = from.0 + (to.0 - from.0) * step / steps;
+            let y = from.1 + (to.1 - from.1) * step / steps;
+            self.put(x, y);
+            self.put(x + 1, y);
+            self.put(x, y + 1);
+        }
+    }
+
+    fn circle(&mut self, x: i32, y: i32) {
+        for dx in -NODE_RADIUS..=NODE_RADIUS {
+            for dy in -NODE_RADIUS..=NODE_RADIUS {
+                let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                if distance <= NODE_RADIUS as f32 && distance > NODE_RADIUS as f32 - 2.0 {
+                    self.put(x + dx, y + dy);
+                } else if distance <= NODE_RADIUS as f32 - 2.0 {
+                    // blank out the edges running underneath the node
+                    self.put_color(x + dx, y + dy, Rgba([255, 255, 255, 255]));
+                }
+            }
+        }
+    }
+
+    fn square(&mut self, x: i32, y: i32) {
+        let half = NODE_RADIUS - 4;
+        for d in -half..=half {
+            for t in 0..2 {
+                self.put(x + d, y - half + t);
+                self.put(x + d, y + half - t);
+                self.put(x - half + t, y + d);
+                self.put(x + half - t, y + d);
+            }
+        }
+    }
+
+    fn centered_text(&mut self, text: &str, x: i32, y: i32) {
+        let width = text_width(&self.font, text, FONT_SIZE) as i32;
+        draw_text(
+            &mut self.image,
+            &self.font,
+            text,
+            (x - width / 2) as u32,
+            (y - FONT_SIZE as i32 / 2 - 2) as u32,
+            FONT_SIZE,
+            BLACK,
+        );
+    }
+
+    pub fn export(&self, path: &str) {
+        self.image.save(path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::parse_boolean_expression;
+
+    #[test]
+    fn edges_skipping_a_row_go_around_its_nodes() {
+        // a.low skips b's row for c's, and b's edges skip c's row for the terminals
+        let order: Vec<String> = ["a", "b", "c"].iter().map(|v| v.to_string()).collect();
+        let (bdd, root) = Bdd::from_gate(&parse_boolean_expression("ab + a'c"), &order);
+        let renderer = BddRenderer::new(&bdd, root);
+
+        for id in bdd.reachable(root) {
+            let node = bdd.node(id);
+            for (child, side) in [(node.low, -1), (node.high, 1)] {
+                let route = renderer.route(id, child, side);
+                for (&other, &(x, y)) in &renderer.positions {
+                    if other == id || other == child {
+                        continue;
+                    }
+                    for segment in route.windows(2) {
+                        let ((x1, y1), (x2, y2)) = (segment[0], segment[1]);
+                        if (y1..=y2).contains(&y) && y1 != y2 {
+                            let crossing = x1 + (x2 - x1) * (y - y1) / (y2 - y1);
+                            assert!(
+                                (crossing - x).abs() >= NODE_RADIUS,
+                                "the edge from {} to {} runs through {}",
+                                id,
+                                child,
+                                other
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::circuit::{self, chained_gate, Circuit, NetlistGate};
+use crate::expression_parser::{Gate, GateType};
+use crate::netlist::{Cell, LogicGate, Netlist};
+
+/// A BLIF model computing `tree` with a `.names` table per gate, joined by nets `n1`, `n2`, ...
+/// in the order they're computed. The constants are the nets `$false` and `$true`, as Yosys writes them.
+pub fn blif(tree: &Gate, model: &str, output: &str) -> String {
+    let netlist = Netlist::new(tree, output);
+    let mut text = format!(
+        ".model {}\n.inputs {}\n.outputs {}\n",
+        model,
+        tree.input_names().join(" "),
+        output
+    );
+    let mut constants_written = Vec::new();
+    for cell in &netlist.cells {
+        let operands = match cell {
+            Cell::Gate { operands, .. } => operands.clone(),
+            Cell::Buffer { source, .. } => vec![source.clone()],
+        };
+        // a constant's table is written before the first table reading it
+        for name in operands {
+            let table = match &name[..] {
+                "0" => ".names $false\n",
+                "1" => ".names $true\n1\n",
+                _ => continue,
+            };
+            if !constants_written.contains(&name) {
+                text.push_str(table);
+                constants_written.push(name);
+            }
+        }
+        match cell {
+            Cell::Gate {
+                gate,
+                output,
+                operands,
+                ..
+            } => {
+                let nets: Vec<String> = operands.iter().map(|name| literal(name)).collect();
+                text.push_str(&format!(".names {} {}\n", nets.join(" "), output));
+                for row in rows(*gate, operands.len()) {
+                    text.push_str(&format!("{} 1\n", row));
+                }
+            }
+            Cell::Buffer { output, source } => {
+                text.push_str(&format!(".names {} {}\n1 1\n", literal(source), output))
+            }
+        }
+    }
+    text.push_str(".end\n");
+    text
+}
+
+/// The input patterns for which a gate of `gate_type` with `fan_in` inputs outputs 1,
+/// using `-` for inputs that don't matter.
+fn rows(gate: LogicGate, fan_in: usize) -> Vec<String> {
+    let with_one = |position: usize, one: char, rest: char| -> String {
+        (0..fan_in)
+            .map(|i| if i == position { one } else { rest })
+            .collect()
+    };
+    match gate {
+        LogicGate::NOT => vec![String::from("0")],
+        LogicGate::AND => vec!["1".repeat(fan_in)],
+        LogicGate::NOR => vec!["0".repeat(fan_in)],
+        LogicGate::OR => (0..fan_in).map(|i| with_one(i, '1', '-')).collect(),
+        LogicGate::NAND => (0..fan_in).map(|i| with_one(i, '0', '-')).collect(),
+        // parity gates have no don't-cares, so every row with the right number of ones is listed
+        LogicGate::XOR | LogicGate::XNOR => {
+            let odd = gate == LogicGate::XOR;
+            (0..1u64 << fan_in)
+                .filter(|row| (row.count_ones() % 2 == 1) == odd)
+                .map(|row| {
+                    (0..fan_in)
+                        .map(|i| {
+                            if row >> (fan_in - 1 - i) & 1 == 1 {
+                                '1'
+                            } else {
+                                '0'
+                            }
+                        })
+                        .collect()
+                })
+                .collect()
+        }
+    }
+}
+
+fn literal(name: &str) -> String {
+    match name {
+        "0" => String::from("$false"),
+        "1" => String::from("$true"),
+        name => String::from(name),
+    }
+}
+
+/// Reads a combinational BLIF model: `.inputs`, `.outputs` and `.names` tables, each table a cover
+/// of cubes like `1-0 1` listing where its output is 1, or where it's 0 if its rows end in 0.
+/// Each table becomes a signal, an OR of its cubes' products. The nets `$false` and `$true`,
+/// as Yosys writes them, are read as the constants rather than as the tables driving them.
+pub fn parse_blif(text: &str) -> Result<Circuit, String> {
+    let mut inputs: Vec<String> = Vec::new();
+    let mut outputs: Vec<String> = Vec::new();
+    let mut tables: Vec<(Vec<String>, Vec<String>)> = Vec::new();
+    let mut models = 0;
+
+    // a backslash at the end of a line continues it on the next
+    let text = text.replace("\\\r\n", " ").replace("\\\n", " ");
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap().trim();
+        let words: Vec<String> = line.split_whitespace().map(String::from).collect();
+        match words.first().map(|word| &word[..]) {
+            None => {}
+            Some(".model") => {
+                models += 1;
+                if models > 1 {
+                    return Err(String::from("only one model can be read"));
+                }
+            }
+            Some(".inputs") => inputs.extend(words[1..].iter().cloned()),
+            Some(".outputs") => outputs.extend(words[1..].iter().cloned()),
+            Some(".names") if words.len() > 1 => tables.push((words[1..].to_vec(), Vec::new())),
+            Some(".end") => break,
+            Some(keyword) if keyword.starts_with('.') => {
+                return Err(format!("only .names tables can be read, found {}", line))
+            }
+            Some(_) => match tables.last_mut() {
+                Some((_, rows)) => rows.push(String::from(line)),
+                None => return Err(format!("{} isn't in a table", line)),
+            },
+        }
+    }
+
+    let gates = tables
+        .iter()
+        .filter(|(nets, _)| !matches!(&nets[nets.len() - 1][..], "$false" | "$true"))
+        .map(|(nets, rows)| table_gate(nets, rows))
+        .collect::<Result<Vec<NetlistGate>, String>>()?;
+    circuit::from_netlist(&inputs, outputs, gates)
+}
+
+/// The gate a `.names` table over `nets`, the last of which it drives, computes with the cover `rows`.
+fn table_gate(nets: &[String], rows: &[String]) -> Result<NetlistGate, String> {
+    let (output, inputs) = nets.split_last().unwrap();
+    let mut products = Vec::new();
+    let mut on_set = true;
+    for row in rows {
+        let (cube, value) = match row.split_whitespace().collect::<Vec<&str>>()[..] {
+            [value] if inputs.is_empty() => ("", value),
+            [cube, value] if cube.len() == inputs.len() => (cube, value),
+            _ => return Err(format!("{} doesn't fit the table driving {}", row, output)),
+        };
+        on_set = match value {
+            "1" => true,
+            "0" => false,
+            _ => return Err(format!("{} isn't an output value in {}", value, row)),
+        };
+        let mut literals = Vec::new();
+        for (c, name) in cube.chars().zip(inputs) {
+            let net = Gate::input(&net_name(name));
+            match c {
+                '1' => literals.push(net),
+                '0' => literals.push(Gate::new(GateType::NOT, vec![net])),
+                '-' => {}
+                _ => return Err(format!("{} isn't a cube in {}", c, row)),
+            }
+        }
+        products.push(match literals.len() {
+            0 => Gate::input("1"),
+            1 => literals.pop().unwrap(),
+            _ => chained_gate(GateType::AND, literals),
+        });
+    }
+
+    // a table listing where its output is 0 computes the complement of its cubes
+    let (gate_type, operands) = match (products.len(), on_set) {
+        (0, _) => (None, vec![Gate::input("0")]),
+        (1, true) => match products[0].get_type() {
+            GateType::INPUT => (None, products),
+            gate_type => (
+                Some(gate_type),
+                products[0].get_inputs().iter().rev().cloned().collect(),
+            ),
+        },
+        (1, false) => (Some(GateType::NOT), products),
+        (_, true) => (Some(GateType::OR), products),
+        (_, false) => (Some(GateType::NOR), products),
+    };
+    Ok(NetlistGate {
+        output: output.clone(),
+        gate_type,
+        operands,
+    })
+}
+
+/// Nets keep their names, except the constants Yosys writes.
+fn net_name(name: &str) -> String {
+    match name {
+        "$false" => String::from("0"),
+        "$true" => String::from("1"),
+        name => String::from(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equivalence::{assert_round_trips, find_circuit_counterexample};
+    use crate::expression_parser::parse_boolean_expression;
+
+    #[test]
+    fn models_read_back_as_the_same_function() {
+        // inputs named like the nets between gates, and constants read by gates
+        let (n1, n2) = (Gate::input("n1"), Gate::input("n2"));
+        let collision = Gate::new(
+            GateType::OR,
+            vec![
+                Gate::new(GateType::XNOR, vec![n1.clone(), Gate::input("0")]),
+                Gate::new(GateType::NAND, vec![n1, n2, Gate::input("1")]),
+            ],
+        );
+        assert_round_trips(&|tree| blif(tree, "m", "f"), parse_blif, &[collision]);
+    }
+
+    #[test]
+    fn tables_can_list_where_their_output_is_0() {
+        let text = ".model m\n.inputs a b\n.outputs f\n.names a b \\\n f\n11 0\n.end\n";
+        let circuit = parse_blif(text).unwrap();
+        let tree = parse_boolean_expression("(ab)'");
+        assert!(find_circuit_counterexample(&tree, &circuit, "f")
+            .unwrap()
+            .is_none());
+    }
+}
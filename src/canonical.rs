@@ -0,0 +1,347 @@
+use crate::aig::Aig;
+use crate::circuit::{Circuit, CircuitBuilder};
+use crate::equivalence::find_circuit_counterexample;
+use crate::expression_parser::{minterm_assignment, Gate, GateType};
+
+/// The canonical sums and products are read off the truth table, which past this many variables
+/// has too many rows to go through and gives far too many terms to draw.
+pub const MAX_TABLE_VARIABLES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanonicalForm {
+    SumOfProducts,
+    ProductOfSums,
+    NandOnly,
+    NorOnly,
+    AndInverter,
+}
+
+impl CanonicalForm {
+    pub fn from_name(name: &str) -> Option<CanonicalForm> {
+        match name {
+            "sop" => Some(CanonicalForm::SumOfProducts),
+            "pos" => Some(CanonicalForm::ProductOfSums),
+            "nand" => Some(CanonicalForm::NandOnly),
+            "nor" => Some(CanonicalForm::NorOnly),
+            "aig" => Some(CanonicalForm::AndInverter),
+            _ => None,
+        }
+    }
+}
+
+/// `tree` rewritten in `form` as a circuit driving `output`, with one signal per distinct gate,
+/// so a subexpression or inverter used in several places is built and drawn once.
+pub fn convert(tree: &Gate, form: CanonicalForm, output: &str) -> Result<Circuit, String> {
+    let mut reserved = tree.input_names();
+    if reserved.iter().any(|name| name == output) {
+        return Err(format!("the output {} is also an input", output));
+    }
+    reserved.push(String::from(output));
+    let mut builder = CircuitBuilder::new(&reserved);
+    let root = match form {
+        CanonicalForm::SumOfProducts => sum_of_products(tree, &mut builder)?,
+        CanonicalForm::ProductOfSums => product_of_sums(tree, &mut builder)?,
+        CanonicalForm::NandOnly => universal_only(tree, GateType::NAND, &mut builder),
+        CanonicalForm::NorOnly => universal_only(tree, GateType::NOR, &mut builder),
+        CanonicalForm::AndInverter => {
+            let (aig, root) = Aig::from_gate(tree);
+            let (aig, roots) = aig.rewritten(&[root]);
+            let (aig, roots) = aig.balanced(&roots);
+            aig.add_to(&mut builder, roots[0])
+        }
+    };
+    let converted = builder.finish(output, root);
+    verify_equivalent(tree, &converted, output, form)?;
+    Ok(converted)
+}
+
+/// Fails if the conversion changed the function, so a broken transform never makes it into a diagram.
+fn verify_equivalent(
+    original: &Gate,
+    converted: &Circuit,
+    output: &str,
+    form: CanonicalForm,
+) -> Result<(), String> {
+    match find_circuit_counterexample(original, converted, output)? {
+        None => Ok(()),
+        Some(counterexample) => Err(format!(
+            "{:?} conversion of {} disagrees with the original: {}",
+            form,
+            original.get_name(),
+            counterexample.describe()
+        )),
+    }
+}
+
+/// The variables of `tree`, if there are few enough to go through its truth table for `form`.
+fn table_variables(tree: &Gate, form: CanonicalForm) -> Result<Vec<String>, String> {
+    let variables = tree.input_names();
+    if variables.len() > MAX_TABLE_VARIABLES {
+        return Err(format!(
+            "{:?} goes through the truth table, and {} variables make {} rows; it stops at {} variables",
+            form,
+            variables.len(),
+            1_u64 << variables.len(),
+            MAX_TABLE_VARIABLES
+        ));
+    }
+    Ok(variables)
+}
+
+/// The variable, or its inverter, which every term sharing it reads.
+fn literal(builder: &mut CircuitBuilder, name: &str, positive: bool) -> String {
+    if positive {
+        String::from(name)
+    } else {
+        builder.gate(GateType::NOT, vec![String::from(name)])
+    }
+}
+
+/// The OR of one full product term per true row of the truth table.
+fn sum_of_products(tree: &Gate, builder: &mut CircuitBuilder) -> Result<String, String> {
+    let variables = table_variables(tree, CanonicalForm::SumOfProducts)?;
+    let mut minterms = Vec::new();
+    for minterm in 0..(1_u64 << variables.len()) {
+        let assignment = minterm_assignment(&variables, minterm);
+        if tree.evaluate(&assignment) {
+            if variables.is_empty() {
+                return Ok(String::from("1"));
+            }
+            let literals = variables
+                .iter()
+                .map(|v| literal(builder, v, assignment[v]))
+                .collect();
+            minterms.push(builder.gate(GateType::AND, literals));
+        }
+    }
+
+    Ok(match minterms.len() {
+        0 => String::from("0"),
+        1 => minterms.pop().unwrap(),
+        _ => builder.gate(GateType::OR, minterms),
+    })
+}
+
+/// The AND of one full sum term per false row of the truth table.
+fn product_of_sums(tree: &Gate, builder: &mut CircuitBuilder) -> Result<String, String> {
+    let variables = table_variables(tree, CanonicalForm::ProductOfSums)?;
+    let mut maxterms = Vec::new();
+    for minterm in 0..(1_u64 << variables.len()) {
+        let assignment = minterm_assignment(&variables, minterm);
+        if !tree.evaluate(&assignment) {
+            if variables.is_empty() {
+                return Ok(String::from("0"));
+            }
+            let literals = variables
+                .iter()
+                .map(|v| literal(builder, v, !assignment[v]))
+                .collect();
+            maxterms.push(builder.gate(GateType::OR, literals));
+        }
+    }
+
+    Ok(match maxterms.len() {
+        0 => String::from("1"),
+        1 => maxterms.pop().unwrap(),
+        _ => builder.gate(GateType::AND, maxterms),
+    })
+}
+
+/// A universal gate wired as an inverter, `x NAND x` or `x NOR x`, made once per signal.
+/// Inverting an inverter just hands back what it was inverting.
+fn invert_with(universal: GateType, builder: &mut CircuitBuilder, name: String) -> String {
+    if let Some(gate) = builder.signal(&name) {
+        let inputs = gate.get_inputs();
+        if gate.get_type() == universal && inputs.len() == 2 && inputs[0] == inputs[1] {
+            return inputs[0].get_name();
+        }
+    }
+    builder.gate(universal, vec![name.clone(), name])
+}
+
+/// Combines `inputs` with two-input universal gates only.
+/// For NAND that gives `(abc)'` as `((ab)')' NAND c`; a single input is just inverted.
+fn universal_of(universal: GateType, builder: &mut CircuitBuilder, inputs: Vec<String>) -> String {
+    let mut iter = inputs.into_iter();
+    let first = iter.next().unwrap();
+    let mut combined = match iter.next() {
+        Some(second) => builder.gate(universal, vec![first, second]),
+        None => return invert_with(universal, builder, first),
+    };
+    for next in iter {
+        let inverted = invert_with(universal, builder, combined);
+        combined = builder.gate(universal, vec![inverted, next]);
+    }
+    combined
+}
+
+/// `gate_type` of the already rewritten `inputs`, using only `universal` gates (NAND or NOR).
+fn combine_with(
+    universal: GateType,
+    builder: &mut CircuitBuilder,
+    gate_type: GateType,
+    inputs: Vec<String>,
+) -> String {
+    let (base, dual_base, dual_universal) = match universal {
+        GateType::NAND => (GateType::AND, GateType::OR, GateType::NOR),
+        GateType::NOR => (GateType::OR, GateType::AND, GateType::NAND),
+        _ => panic!("{} is not a universal gate", universal),
+    };
+    let inverted = |builder: &mut CircuitBuilder, inputs: Vec<String>| -> Vec<String> {
+        inputs
+            .into_iter()
+            .map(|name| invert_with(universal, builder, name))
+            .collect()
+    };
+
+    if gate_type == GateType::NOT {
+        invert_with(universal, builder, inputs.into_iter().next().unwrap())
+    } else if gate_type == universal {
+        universal_of(universal, builder, inputs)
+    } else if gate_type == base {
+        let combined = universal_of(universal, builder, inputs);
+        invert_with(universal, builder, combined)
+    } else if gate_type == dual_base {
+        let inputs = inverted(builder, inputs);
+        universal_of(universal, builder, inputs)
+    } else if gate_type == dual_universal {
+        let inputs = inverted(builder, inputs);
+        let combined = universal_of(universal, builder, inputs);
+        invert_with(universal, builder, combined)
+    } else {
+        panic!("no {} rewrite for {}", universal, gate_type)
+    }
+}
+
+/// Rewrites the tree using only `universal` gates (NAND or NOR) and the inputs,
+/// returning the signal or input its output comes down to.
+/// `x' = x NAND x`, `ab = (a NAND b)'` and `a + b = a' NAND b'`, and dually for NOR.
+fn universal_only(tree: &Gate, universal: GateType, builder: &mut CircuitBuilder) -> String {
+    let gate_type = tree.get_type();
+    if gate_type == GateType::INPUT {
+        return tree.get_name();
+    }
+    let inputs: Vec<String> = tree
+        .get_inputs()
+        .iter()
+        .rev()
+        .map(|g| universal_only(g, universal, builder))
+        .collect();
+    if gate_type == GateType::XOR || gate_type == GateType::XNOR {
+        return expand_xor(universal, builder, gate_type, inputs);
+    }
+    combine_with(universal, builder, gate_type, inputs)
+}
+
+/// Spells out `a ^ b` as `ab' + a'b`, chaining left to right for more inputs,
+/// so the universal rewrites only have to know about AND, OR and NOT.
+/// Each operand is a signal, read by both terms rather than copied into them.
+fn expand_xor(
+    universal: GateType,
+    builder: &mut CircuitBuilder,
+    gate_type: GateType,
+    inputs: Vec<String>,
+) -> String {
+    let mut operands = inputs.into_iter();
+    let mut expanded = operands.next().unwrap();
+    for next in operands {
+        let not_expanded = combine_with(universal, builder, GateType::NOT, vec![expanded.clone()]);
+        let not_next = combine_with(universal, builder, GateType::NOT, vec![next.clone()]);
+        let only_expanded =
+            combine_with(universal, builder, GateType::AND, vec![expanded, not_next]);
+        let only_next = combine_with(universal, builder, GateType::AND, vec![not_expanded, next]);
+        expanded = combine_with(
+            universal,
+            builder,
+            GateType::OR,
+            vec![only_expanded, only_next],
+        );
+    }
+    if gate_type == GateType::XNOR {
+        expanded = combine_with(universal, builder, GateType::NOT, vec![expanded]);
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::parse_boolean_expression;
+
+    const FORMS: [CanonicalForm; 5] = [
+        CanonicalForm::SumOfProducts,
+        CanonicalForm::ProductOfSums,
+        CanonicalForm::NandOnly,
+        CanonicalForm::NorOnly,
+        CanonicalForm::AndInverter,
+    ];
+
+    #[test]
+    fn every_form_keeps_the_function() {
+        for expression in [
+            "ab + c'",
+            "a ^ b ^ c",
+            "(a + b)'(c d)'",
+            "a a'",
+            "a + 1",
+            "a",
+        ] {
+            let tree = parse_boolean_expression(expression);
+            for form in FORMS {
+                let circuit = convert(&tree, form, "f")
+                    .unwrap_or_else(|error| panic!("{} in {:?}: {}", expression, form, error));
+                assert_eq!(circuit.outputs, vec![String::from("f")]);
+                assert!(circuit.topological_order().is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn universal_forms_use_only_their_gate() {
+        let tree = parse_boolean_expression("(ab + c') ^ d");
+        for (form, universal) in [
+            (CanonicalForm::NandOnly, GateType::NAND),
+            (CanonicalForm::NorOnly, GateType::NOR),
+        ] {
+            let circuit = convert(&tree, form, "f").unwrap();
+            for (name, gate) in &circuit.signals {
+                assert_eq!(gate.get_type(), universal, "{} = {}", name, gate.get_name());
+            }
+        }
+    }
+
+    #[test]
+    fn inverters_are_shared_between_their_readers() {
+        // every operand of a chained XOR is read twice, once inverted, which copying would double at each link
+        let variables: Vec<String> = (b'a'..=b'z').map(|c| String::from(c as char)).collect();
+        let tree = parse_boolean_expression(&variables.join("^"));
+        let circuit = convert(&tree, CanonicalForm::NandOnly, "out").unwrap();
+        assert!(circuit.signals.len() < 10 * variables.len());
+
+        let mut inverted: Vec<String> = circuit
+            .signals
+            .iter()
+            .map(|(_, gate)| gate.get_inputs())
+            .filter(|inputs| inputs.len() == 2 && inputs[0] == inputs[1])
+            .map(|inputs| inputs[0].get_name())
+            .collect();
+        let count = inverted.len();
+        inverted.sort();
+        inverted.dedup();
+        assert_eq!(inverted.len(), count);
+    }
+
+    #[test]
+    fn truth_table_forms_stop_at_the_variable_limit() {
+        let variables: String = (b'a'..)
+            .take(MAX_TABLE_VARIABLES + 1)
+            .map(char::from)
+            .collect();
+        let tree = parse_boolean_expression(&variables);
+        for form in [CanonicalForm::SumOfProducts, CanonicalForm::ProductOfSums] {
+            let error = convert(&tree, form, "out").unwrap_err();
+            assert!(error.contains("it stops at 16 variables"), "{}", error);
+        }
+        assert!(convert(&tree, CanonicalForm::NandOnly, "out").is_ok());
+    }
+}
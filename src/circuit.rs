@@ -0,0 +1,869 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::expression_parser::{
+    check_expression, input_value, parse_boolean_expression, Gate, GateType,
+};
+use crate::spritesheet::sprite_info;
+
+/// A block of named signals, like
+/// ```text
+/// input a, b, cin;
+/// t = a ^ b;
+/// sum = t ^ cin;
+/// cout = ab + cin t;
+/// ```
+/// Each signal's gate refers to other signals as INPUT gates carrying the signal's name.
+#[derive(Debug, Clone)]
+pub struct Circuit {
+    pub signals: Vec<(String, Gate)>,
+    pub outputs: Vec<String>,
+}
+
+impl Circuit {
+    pub fn signal(&self, name: &str) -> Option<&Gate> {
+        self.signals.iter().find(|(n, _)| n == name).map(|(_, g)| g)
+    }
+
+    /// The primary inputs, that is every INPUT name that isn't itself a signal, sorted.
+    pub fn input_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .signals
+            .iter()
+            .flat_map(|(_, g)| g.input_names())
+            .filter(|n| self.signal(n).is_none())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The circuit back if every output is assigned and no signal depends on itself.
+    pub fn checked(self) -> Result<Circuit, String> {
+        for output in &self.outputs {
+            if self.signal(output).is_none() {
+                return Err(format!("output {} is never assigned", output));
+            }
+        }
+        self.topological_order()?;
+        Ok(self)
+    }
+
+    /// The indices of the signals in an order where each comes after every signal it reads,
+    /// found depth first with each signal visited once, or the names that read each other in a loop.
+    pub fn topological_order(&self) -> Result<Vec<usize>, String> {
+        let index: HashMap<&str, usize> = self
+            .signals
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (name.as_str(), i))
+            .collect();
+        let reads: Vec<Vec<usize>> = self
+            .signals
+            .iter()
+            .map(|(_, gate)| {
+                gate.input_names()
+                    .iter()
+                    .filter_map(|name| index.get(name.as_str()).copied())
+                    .collect()
+            })
+            .collect();
+
+        let mut visits = vec![Visit::New; self.signals.len()];
+        let mut order = Vec::with_capacity(self.signals.len());
+        for start in 0..self.signals.len() {
+            if visits[start] != Visit::New {
+                continue;
+            }
+            visits[start] = Visit::OnPath;
+            // each entry is a signal on the current path and how many of its reads have been followed
+            let mut path = vec![(start, 0)];
+            while let Some((signal, followed)) = path.last_mut() {
+                let signal = *signal;
+                let read = match reads[signal].get(*followed) {
+                    Some(&read) => read,
+                    None => {
+                        visits[signal] = Visit::Done;
+                        order.push(signal);
+                        path.pop();
+                        continue;
+                    }
+                };
+                *followed += 1;
+                match visits[read] {
+                    Visit::New => {
+                        visits[read] = Visit::OnPath;
+                        path.push((read, 0));
+                    }
+                    Visit::OnPath => {
+                        let first = path.iter().position(|&(s, _)| s == read).unwrap();
+                        let mut names: Vec<&str> = path[first..]
+                            .iter()
+                            .map(|&(s, _)| self.signals[s].0.as_str())
+                            .collect();
+                        names.push(&self.signals[read].0);
+                        return Err(format!(
+                            "signals depend on themselves: {}",
+                            names.join(" -> ")
+                        ));
+                    }
+                    Visit::Done => {}
+                }
+            }
+        }
+        Ok(order)
+    }
+}
+
+/// A gate of `gate_type` over any number of operands. Sprites have two input pins, so wider gates
+/// become a chain of two-input ones, with only the last inverting for NAND, NOR and XNOR.
+pub fn chained_gate(gate_type: GateType, mut operands: Vec<Gate>) -> Gate {
+    if operands.len() <= 2 {
+        return Gate::new(gate_type, operands);
+    }
+    let last = operands.pop().unwrap();
+    let inner = match gate_type {
+        GateType::NAND => GateType::AND,
+        GateType::NOR => GateType::OR,
+        GateType::XNOR => GateType::XOR,
+        other => other,
+    };
+    Gate::new(gate_type, vec![chained_gate(inner, operands), last])
+}
+
+/// One gate of a netlist read from another tool: the net it drives and its operands,
+/// which are INPUT gates naming nets or constants, possibly negated, or ANDs of those like the cubes of a BLIF table.
+/// Without a `gate_type` it's a buffer, driving its net with its one operand.
+#[derive(Debug, Clone)]
+pub struct NetlistGate {
+    pub output: String,
+    pub gate_type: Option<GateType>,
+    pub operands: Vec<Gate>,
+}
+
+/// Builds a circuit from a netlist's gates, with one signal per gate. Buffers are wired straight through,
+/// except where they drive an output, since an output needs a signal of its own.
+pub fn from_netlist(
+    inputs: &[String],
+    outputs: Vec<String>,
+    gates: Vec<NetlistGate>,
+) -> Result<Circuit, String> {
+    let mut driven: HashSet<String> = inputs.iter().cloned().collect();
+    for gate in &gates {
+        if !driven.insert(gate.output.clone()) {
+            return Err(format!("{} is driven more than once", gate.output));
+        }
+    }
+
+    let aliases: HashMap<String, String> = gates
+        .iter()
+        .filter(|g| g.gate_type.is_none() && !outputs.contains(&g.output))
+        .map(|g| match g.operands.as_slice() {
+            [operand] if operand.get_type() == GateType::INPUT => {
+                Ok((g.output.clone(), operand.get_name()))
+            }
+            _ => Err(format!(
+                "the buffer driving {} needs one plain input",
+                g.output
+            )),
+        })
+        .collect::<Result<_, _>>()?;
+    let resolve = |name: &str| {
+        let mut name = String::from(name);
+        // a loop of buffers never settles on a driver, so stop once every alias has been tried
+        for _ in 0..=aliases.len() {
+            match aliases.get(&name) {
+                Some(source) => name = source.clone(),
+                None => break,
+            }
+        }
+        name
+    };
+
+    let mut signals = Vec::new();
+    for gate in gates {
+        if gate.gate_type.is_none() && !outputs.contains(&gate.output) {
+            continue;
+        }
+        let operands: Vec<Gate> = gate
+            .operands
+            .iter()
+            .map(|operand| operand.rename_inputs(&|name: &str| resolve(name)))
+            .collect();
+        for operand in &operands {
+            for name in operand.input_names() {
+                if !driven.contains(&name) || aliases.contains_key(&name) {
+                    return Err(format!("{} is read but never driven", name));
+                }
+            }
+        }
+        let signal = match gate.gate_type {
+            Some(gate_type) => chained_gate(gate_type, operands),
+            None => operands.into_iter().next().unwrap(),
+        };
+        signals.push((gate.output, signal));
+    }
+
+    Circuit { signals, outputs }.checked()
+}
+
+/// Builds a circuit a gate at a time, giving every distinct gate a signal of its own,
+/// so asking for the same gate over the same operands again reads that signal instead of building it twice.
+pub struct CircuitBuilder {
+    signals: Vec<(String, Gate)>,
+    positions: HashMap<String, usize>,
+    made: HashMap<(GateType, Vec<String>), String>,
+    /// Names the signals mustn't take, like the inputs and the output.
+    reserved: HashSet<String>,
+    numbered: usize,
+}
+
+impl CircuitBuilder {
+    pub fn new(reserved: &[String]) -> CircuitBuilder {
+        CircuitBuilder {
+            signals: Vec::new(),
+            positions: HashMap::new(),
+            made: HashMap::new(),
+            reserved: reserved.iter().cloned().collect(),
+            numbered: 0,
+        }
+    }
+
+    pub fn signal(&self, name: &str) -> Option<&Gate> {
+        self.positions.get(name).map(|&i| &self.signals[i].1)
+    }
+
+    /// The signal `gate_type` drives from the named operands, written in order, made the first time it's asked for.
+    pub fn gate(&mut self, gate_type: GateType, operands: Vec<String>) -> String {
+        let key = (gate_type, operands);
+        if let Some(name) = self.made.get(&key) {
+            return name.clone();
+        }
+        let name = self.next_name();
+        let operands = key.1.iter().map(|operand| Gate::input(operand)).collect();
+        self.positions.insert(name.clone(), self.signals.len());
+        self.signals
+            .push((name.clone(), chained_gate(gate_type, operands)));
+        self.made.insert(key, name.clone());
+        name
+    }
+
+    /// `n1`, `n2`, ... on from the last name given out, skipping any that are reserved.
+    fn next_name(&mut self) -> String {
+        loop {
+            self.numbered += 1;
+            let name = format!("n{}", self.numbered);
+            if !self.reserved.contains(&name) {
+                return name;
+            }
+        }
+    }
+
+    /// The circuit with `root` driving the output `output`, keeping only the signals it reads, numbered afresh.
+    /// When nothing else reads `root` it becomes the output; an input, a constant or a signal read elsewhere
+    /// is wired to the output instead.
+    pub fn finish(mut self, output: &str, root: String) -> Circuit {
+        // operands are made before the gates reading them, so walking backwards finds everything used
+        let mut used: HashSet<String> = HashSet::new();
+        used.insert(root.clone());
+        let mut reads = 0;
+        for (name, gate) in self.signals.iter().rev() {
+            if used.contains(name) {
+                for operand in gate.input_names() {
+                    reads += (operand == root) as usize;
+                    used.insert(operand);
+                }
+            }
+        }
+
+        let mut names: HashMap<String, String> = HashMap::new();
+        self.numbered = 0;
+        for i in 0..self.signals.len() {
+            let name = &self.signals[i].0;
+            if !used.contains(name) {
+                continue;
+            }
+            let renamed = if *name == root && reads == 0 {
+                String::from(output)
+            } else {
+                self.next_name()
+            };
+            names.insert(self.signals[i].0.clone(), renamed);
+        }
+        let rename = |name: &str| {
+            names
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| String::from(name))
+        };
+
+        let mut signals: Vec<(String, Gate)> = self
+            .signals
+            .iter()
+            .filter(|(name, _)| used.contains(name))
+            .map(|(name, gate)| (rename(name), gate.rename_inputs(&rename)))
+            .collect();
+        if !signals.iter().any(|(name, _)| name == output) {
+            signals.push((String::from(output), Gate::input(&rename(&root))));
+        }
+        Circuit {
+            signals,
+            outputs: vec![String::from(output)],
+        }
+    }
+}
+
+/// Statement files always assign something, which expressions never do.
+pub fn is_statement_list(input: &str) -> bool {
+    input.contains('=')
+}
+
+fn split_names(list: &str) -> Vec<String> {
+    list.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|n| !n.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Breaks a run of letters and digits into known names, longest first where there's a choice,
+/// so with `a`, `b` and `cin` known `abcin` reads as `a`, `b`, `cin`. A word of digits is a run of constants.
+/// `None` if the word can't be spelt from known names.
+fn resolve_word(word: &str, known: &HashSet<String>) -> Option<Vec<String>> {
+    if word.chars().all(|c| c.is_ascii_digit()) {
+        return Some(word.chars().map(String::from).collect());
+    }
+    // where the name starting at each position ends, working back from the end of the word,
+    // so every choice tried already knows whether the rest can be spelt
+    let mut next_end: Vec<Option<usize>> = vec![None; word.len() + 1];
+    next_end[word.len()] = Some(word.len());
+    for start in (0..word.len()).rev().filter(|&i| word.is_char_boundary(i)) {
+        next_end[start] = (start + 1..=word.len()).rev().find(|&end| {
+            word.is_char_boundary(end)
+                && next_end[end].is_some()
+                && known.contains(&word[start..end])
+        });
+    }
+    let mut names = Vec::new();
+    let mut start = 0;
+    while start < word.len() {
+        let end = next_end[start]?;
+        names.push(String::from(&word[start..end]));
+        start = end;
+    }
+    Some(names)
+}
+
+/// The names the words of `expressions` use, besides those already `known`.
+/// A word is a name of its own unless it can be spelt from shorter names, so `cin` is one name,
+/// but `ab` is `a` and `b` when those are used on their own somewhere.
+fn word_names(expressions: &[&str], known: &HashSet<String>) -> HashSet<String> {
+    let mut words: Vec<&str> = expressions
+        .iter()
+        .flat_map(|expression| expression.split(|c: char| !(c.is_alphanumeric() || c == '_')))
+        .filter(|word| !word.is_empty() && !word.chars().all(|c| c.is_ascii_digit()))
+        .collect();
+    words.sort_by_key(|word| word.len());
+    words.dedup();
+
+    let mut names = known.clone();
+    for word in words {
+        if resolve_word(word, &names).is_none() {
+            names.insert(String::from(word));
+        }
+    }
+    names
+}
+
+/// Parses one right hand side by standing each resolved name in for a single character,
+/// handing the result to the infix parser, then putting the names back.
+fn parse_right_hand_side(expression: &str, known: &HashSet<String>) -> Result<Gate, String> {
+    check_expression(expression)?;
+    let mut placeholders: HashMap<char, String> = HashMap::new();
+    let mut by_name: HashMap<String, char> = HashMap::new();
+    let mut substituted = String::new();
+
+    let mut word = String::new();
+    let flush = |word: &mut String,
+                 substituted: &mut String,
+                 placeholders: &mut HashMap<char, String>,
+                 by_name: &mut HashMap<String, char>| {
+        for name in resolve_word(word, known).unwrap() {
+            if name == "0" || name == "1" {
+                substituted.push_str(&name);
+                continue;
+            }
+            let next = char::from_u32(0xE000 + by_name.len() as u32).unwrap();
+            let placeholder = *by_name.entry(name.clone()).or_insert(next);
+            placeholders.insert(placeholder, name);
+            substituted.push(placeholder);
+        }
+        word.clear();
+    };
+
+    for c in expression.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut substituted, &mut placeholders, &mut by_name);
+            substituted.push(c);
+        }
+    }
+    flush(&mut word, &mut substituted, &mut placeholders, &mut by_name);
+
+    Ok(
+        parse_boolean_expression(&substituted).rename_inputs(&|placeholder: &str| {
+            let c = placeholder.chars().next().unwrap();
+            placeholders
+                .get(&c)
+                .cloned()
+                .unwrap_or_else(|| String::from(placeholder))
+        }),
+    )
+}
+
+/// Parses statements separated by `;` or new lines: `input` and `output` declarations
+/// and `name = expression` assignments. Without an `output` declaration every signal
+/// no other statement uses is an output.
+pub fn parse_statements(input: &str) -> Result<Circuit, String> {
+    let statements: Vec<&str> = input
+        .split([';', '\n'])
+        .map(|s| s.split('#').next().unwrap().trim())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let mut known: HashSet<String> = HashSet::new();
+    let mut declared_outputs: Vec<String> = Vec::new();
+    let mut assignments: Vec<(String, &str)> = Vec::new();
+    for statement in &statements {
+        if let Some(list) = statement.strip_prefix("input ") {
+            known.extend(split_names(list));
+        } else if let Some(list) = statement.strip_prefix("output ") {
+            declared_outputs.extend(split_names(list));
+        } else if let Some(equals) = statement.find('=') {
+            let name = statement[..equals].trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(format!("{} isn't a signal name", name));
+            }
+            if assignments.iter().any(|(n, _)| n == name) {
+                return Err(format!("{} is assigned twice", name));
+            }
+            known.insert(String::from(name));
+            assignments.push((String::from(name), &statement[equals + 1..]));
+        } else {
+            return Err(format!("expected an assignment, found {}", statement));
+        }
+    }
+
+    let expressions: Vec<&str> = assignments
+        .iter()
+        .map(|(_, expression)| *expression)
+        .collect();
+    let known = word_names(&expressions, &known);
+    let signals = assignments
+        .iter()
+        .map(|(name, expression)| {
+            let gate = parse_right_hand_side(expression, &known)
+                .map_err(|error| format!("can't read {}: {}", name, error))?;
+            Ok((name.clone(), gate))
+        })
+        .collect::<Result<Vec<(String, Gate)>, String>>()?;
+
+    let circuit = Circuit {
+        outputs: if declared_outputs.is_empty() {
+            signals
+                .iter()
+                .map(|(name, _)| name.clone())
+                .filter(|name| !signals.iter().any(|(_, g)| g.input_names().contains(name)))
+                .collect()
+        } else {
+            declared_outputs
+        },
+        signals,
+    };
+    circuit.checked()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    New,
+    OnPath,
+    Done,
+}
+
+const COLUMN_WIDTH: u32 = 90;
+/// Room between columns for each signal's vertical run to get a lane of its own.
+const CHANNEL_WIDTH: u32 = 40;
+const GATE_HEIGHT: f32 = 40.0;
+const WIRE_HEIGHT: f32 = 10.0;
+const GAP: f32 = 10.0;
+/// Input names are written above the top of their sprite.
+const INPUT_LABEL_HEIGHT: f32 = 20.0;
+const MARGIN: f32 = 30.0;
+
+/// What sits at one place in a circuit layout.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+    /// A gate sprite, with the name drawn on INPUT terminals.
+    Gate(GateType, String),
+    /// A named output at the right edge of the diagram.
+    Output(String),
+    /// A wire passing straight through a column on its way to a gate further right.
+    Wire,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlacedNode {
+    pub kind: NodeKind,
+    /// Where the pins in `inputs` come from, in sprite pin order.
+    pub inputs: Vec<usize>,
+    pub x: u32,
+    /// The top of a gate sprite, or the height of the wire for outputs and pass-through wires.
+    pub y: u32,
+    /// Where the wires leaving this node turn to run up or down the channel on its right.
+    pub lane: u32,
+}
+
+/// A circuit laid out in columns with shared signals drawn once,
+/// outputs on the right and each gate placed just left of the gates it feeds.
+#[derive(Debug, Clone)]
+pub struct CircuitLayout {
+    pub nodes: Vec<PlacedNode>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PlacedNode {
+    fn output_pin_offset(&self) -> f32 {
+        match &self.kind {
+            NodeKind::Gate(gate_type, _) => sprite_info(gate_type.sprite()).outputs[0] as f32,
+            _ => 0.0,
+        }
+    }
+
+    fn input_pin_offset(&self, pin: usize) -> f32 {
+        match &self.kind {
+            NodeKind::Gate(gate_type, _) => sprite_info(gate_type.sprite()).inputs[pin] as f32,
+            _ => 0.0,
+        }
+    }
+
+    fn height(&self) -> f32 {
+        match &self.kind {
+            NodeKind::Gate(GateType::INPUT, _) => INPUT_LABEL_HEIGHT + GATE_HEIGHT / 2.0 + 5.0,
+            NodeKind::Gate(_, _) => GATE_HEIGHT,
+            _ => WIRE_HEIGHT,
+        }
+    }
+
+    /// How far the top of the node's extent sits above its `y`.
+    fn top_offset(&self) -> f32 {
+        match &self.kind {
+            NodeKind::Gate(GateType::INPUT, _) => INPUT_LABEL_HEIGHT,
+            NodeKind::Gate(_, _) => 0.0,
+            _ => WIRE_HEIGHT / 2.0,
+        }
+    }
+}
+
+struct LayoutBuilder<'a> {
+    circuit: &'a Circuit,
+    nodes: Vec<PlacedNode>,
+    signal_nodes: HashMap<String, usize>,
+}
+
+impl<'a> LayoutBuilder<'a> {
+    fn add(&mut self, kind: NodeKind, inputs: Vec<usize>) -> usize {
+        self.nodes.push(PlacedNode {
+            kind,
+            inputs,
+            x: 0,
+            y: 0,
+            lane: 0,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn add_signal(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.signal_nodes.get(name) {
+            return id;
+        }
+        let id = self.add_gate(self.circuit.signal(name).unwrap());
+        self.signal_nodes.insert(String::from(name), id);
+        id
+    }
+
+    fn add_gate(&mut self, gate: &Gate) -> usize {
+        if gate.get_type() == GateType::INPUT && self.circuit.signal(&gate.get_name()).is_some() {
+            return self.add_signal(&gate.get_name());
+        }
+        let inputs = gate.get_inputs().iter().map(|g| self.add_gate(g)).collect();
+        self.add(NodeKind::Gate(gate.get_type(), gate.get_name()), inputs)
+    }
+}
+
+impl CircuitLayout {
+    pub fn new(circuit: &Circuit) -> CircuitLayout {
+        let mut builder = LayoutBuilder {
+            circuit,
+            nodes: Vec::new(),
+            signal_nodes: HashMap::new(),
+        };
+        for output in &circuit.outputs {
+            let source = builder.add_signal(output);
+            builder.add(NodeKind::Output(output.clone()), vec![source]);
+        }
+        let mut nodes = builder.nodes;
+
+        // columns count leftwards from the outputs; gates are created after their inputs,
+        // so walking backwards settles every consumer before the gates feeding it
+        let mut rank = vec![0_usize; nodes.len()];
+        for id in (0..nodes.len()).rev() {
+            for &input in &nodes[id].inputs {
+                rank[input] = rank[input].max(rank[id] + 1);
+            }
+        }
+
+        // break long connections into pass-through wires, one per column crossed
+        for id in 0..nodes.len() {
+            for pin in 0..nodes[id].inputs.len() {
+                let mut source = nodes[id].inputs[pin];
+                for wire_rank in ((rank[id] + 1)..rank[source]).rev() {
+                    nodes.push(PlacedNode {
+                        kind: NodeKind::Wire,
+                        inputs: vec![source],
+                        x: 0,
+                        y: 0,
+                        lane: 0,
+                    });
+                    rank.push(wire_rank);
+                    source = nodes.len() - 1;
+                }
+                nodes[id].inputs[pin] = source;
+            }
+        }
+
+        let max_rank = rank.iter().copied().max().unwrap_or(0);
+        let mut columns: Vec<Vec<usize>> = vec![Vec::new(); max_rank + 1];
+
+        // outputs in the order given, then each column sorted by where it's consumed
+        columns[0] = (0..nodes.len()).filter(|&id| rank[id] == 0).collect();
+        for r in 0..max_rank {
+            let mut keys: HashMap<usize, (f32, u32)> = HashMap::new();
+            for (position, &consumer) in columns[r].iter().enumerate() {
+                let pins = nodes[consumer].inputs.len() as f32;
+                for (pin, &source) in nodes[consumer].inputs.iter().enumerate() {
+                    let key = keys.entry(source).or_insert((0.0, 0));
+                    key.0 += position as f32 + (pin as f32 + 1.0) / (pins + 1.0);
+                    key.1 += 1;
+                }
+            }
+            let mut next: Vec<usize> = keys.keys().copied().collect();
+            next.sort_by(|a, b| {
+                let key_a = keys[a].0 / keys[a].1 as f32;
+                let key_b = keys[b].0 / keys[b].1 as f32;
+                key_a.partial_cmp(&key_b).unwrap().then(a.cmp(b))
+            });
+            columns[r + 1] = next;
+        }
+
+        // output pin heights, settled by sweeping from the outputs to the inputs,
+        // back again so gates sit between what feeds them, then once more towards the inputs
+        let mut pin_y = vec![0.0_f32; nodes.len()];
+        for (i, &id) in columns[0].iter().enumerate() {
+            pin_y[id] = i as f32 * (GATE_HEIGHT + GAP);
+        }
+
+        let sweep_left = |pin_y: &mut Vec<f32>| {
+            for r in 1..=max_rank {
+                let desired: Vec<f32> = columns[r]
+                    .iter()
+                    .map(|&id| {
+                        let wanted: Vec<f32> = columns[r - 1]
+                            .iter()
+                            .flat_map(|&consumer| {
+                                let node = &nodes[consumer];
+                                let top = pin_y[consumer] - node.output_pin_offset();
+                                node.inputs
+                                    .iter()
+                                    .enumerate()
+                                    .filter(move |(_, &source)| source == id)
+                                    .map(move |(pin, _)| top + node.input_pin_offset(pin))
+                            })
+                            .collect();
+                        wanted.iter().sum::<f32>() / wanted.len() as f32
+                    })
+                    .collect();
+                spread_column(&nodes, &columns[r], pin_y, &desired);
+            }
+        };
+        sweep_left(&mut pin_y);
+        for r in (0..max_rank).rev() {
+            let desired: Vec<f32> = columns[r]
+                .iter()
+                .map(|&id| {
+                    let node = &nodes[id];
+                    if node.inputs.is_empty() {
+                        return pin_y[id];
+                    }
+                    let pins: f32 = node
+                        .inputs
+                        .iter()
+                        .enumerate()
+                        .map(|(pin, &source)| pin_y[source] - node.input_pin_offset(pin))
+                        .sum();
+                    pins / node.inputs.len() as f32 + node.output_pin_offset()
+                })
+                .collect();
+            spread_column(&nodes, &columns[r], &mut pin_y, &desired);
+        }
+        sweep_left(&mut pin_y);
+
+        let top = nodes
+            .iter()
+            .enumerate()
+            .map(|(id, n)| pin_y[id] - n.output_pin_offset() - n.top_offset())
+            .fold(f32::MAX, f32::min);
+        let bottom = nodes
+            .iter()
+            .enumerate()
+            .map(|(id, n)| pin_y[id] - n.output_pin_offset() - n.top_offset() + n.height())
+            .fold(f32::MIN, f32::max);
+
+        for id in 0..nodes.len() {
+            let node_top = pin_y[id] - nodes[id].output_pin_offset();
+            nodes[id].y = (node_top - top + MARGIN).round() as u32;
+            nodes[id].x = (max_rank - rank[id]) as u32 * (COLUMN_WIDTH + CHANNEL_WIDTH);
+        }
+        for column in &columns[1..] {
+            for (k, &id) in column.iter().enumerate() {
+                nodes[id].lane = nodes[id].x
+                    + COLUMN_WIDTH
+                    + CHANNEL_WIDTH * (k as u32 + 1) / (column.len() as u32 + 1);
+            }
+        }
+
+        CircuitLayout {
+            nodes,
+            width: max_rank as u32 * (COLUMN_WIDTH + CHANNEL_WIDTH) + COLUMN_WIDTH,
+            height: (bottom - top + 2.0 * MARGIN).ceil() as u32,
+        }
+    }
+}
+
+impl CircuitLayout {
+    /// The value on each node's output for `assignment`; outputs and pass-through wires carry what feeds them.
+    pub fn values(&self, assignment: &HashMap<String, bool>) -> Vec<bool> {
+        let mut values: Vec<Option<bool>> = vec![None; self.nodes.len()];
+        for id in 0..self.nodes.len() {
+            self.value(id, assignment, &mut values);
+        }
+        values.into_iter().map(|v| v.unwrap()).collect()
+    }
+
+    fn value(
+        &self,
+        id: usize,
+        assignment: &HashMap<String, bool>,
+        values: &mut Vec<Option<bool>>,
+    ) -> bool {
+        if let Some(value) = values[id] {
+            return value;
+        }
+        let node = &self.nodes[id];
+        let inputs: Vec<bool> = node
+            .inputs
+            .iter()
+            .map(|&input| self.value(input, assignment, values))
+            .collect();
+        let value = match &node.kind {
+            NodeKind::Gate(GateType::INPUT, name) => input_value(name, assignment),
+            NodeKind::Gate(gate_type, _) => gate_type.combine(inputs.into_iter()),
+            NodeKind::Output(_) | NodeKind::Wire => inputs[0],
+        };
+        values[id] = Some(value);
+        value
+    }
+}
+
+/// Places a column's nodes as close to `desired` as they'll go without overlapping,
+/// keeping their order and centring the unavoidable pushes on the desired positions.
+fn spread_column(nodes: &[PlacedNode], column: &[usize], pin_y: &mut [f32], desired: &[f32]) {
+    let mut placed: Vec<f32> = Vec::new();
+    for (i, &id) in column.iter().enumerate() {
+        let mut y = desired[i];
+        if i > 0 {
+            let previous = &nodes[column[i - 1]];
+            let node = &nodes[id];
+            let previous_bottom =
+                placed[i - 1] - previous.output_pin_offset() - previous.top_offset()
+                    + previous.height();
+            let lowest_allowed =
+                previous_bottom + GAP + node.output_pin_offset() + node.top_offset();
+            y = y.max(lowest_allowed);
+        }
+        placed.push(y);
+    }
+
+    let drift: f32 =
+        placed.iter().zip(desired).map(|(p, d)| p - d).sum::<f32>() / placed.len().max(1) as f32;
+    for (i, &id) in column.iter().enumerate() {
+        pin_y[id] = placed[i] - drift;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `w0 = a` and then `wN = wN-1 * wN-1`, which reads as 2^n copies of `a` once flattened.
+    fn doubling_chain(length: usize) -> String {
+        let mut statements = String::from("input a\nw0 = a\n");
+        for n in 1..length {
+            statements.push_str(&format!("w{} = w{} * w{}\n", n, n - 1, n - 1));
+        }
+        statements
+    }
+
+    #[test]
+    fn signals_read_twice_are_ordered_once() {
+        let circuit = parse_statements(&doubling_chain(200)).unwrap();
+        assert_eq!(circuit.outputs, vec![String::from("w199")]);
+        let order = circuit.topological_order().unwrap();
+        assert_eq!(order, (0..200).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn signals_come_after_what_they_read() {
+        let circuit = parse_statements("f = t + c\nt = a ^ b\ng = f' t").unwrap();
+        let order: Vec<&str> = circuit
+            .topological_order()
+            .unwrap()
+            .into_iter()
+            .map(|i| circuit.signals[i].0.as_str())
+            .collect();
+        assert_eq!(order, vec!["t", "f", "g"]);
+    }
+
+    #[test]
+    fn loops_are_reported_by_name() {
+        let error = parse_statements("input a\nx = a y\ny = z'\nz = x + a\nf = x").unwrap_err();
+        assert_eq!(error, "signals depend on themselves: x -> y -> z -> x");
+    }
+
+    #[test]
+    fn words_not_spelt_from_other_names_are_names() {
+        let circuit = parse_statements("sum = a^b^cin; cout = ab + cin(a^b)").unwrap();
+        assert_eq!(circuit.input_names(), vec!["a", "b", "cin"]);
+        assert_eq!(circuit.outputs, vec!["sum", "cout"]);
+        assert_eq!(
+            circuit.signal("cout").unwrap().input_names(),
+            vec!["a", "b", "cin"]
+        );
+    }
+
+    #[test]
+    fn malformed_right_hand_sides_are_errors() {
+        for statements in ["s = a +; t = s", "s = (a b", "s = a) + b", "s = ' a", "s ="] {
+            assert!(parse_statements(statements).is_err(), "{}", statements);
+        }
+    }
+}
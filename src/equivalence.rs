@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use crate::aig::{self, Aig, AigNode, Literal};
+use crate::bdd::{self, Bdd, NodeId};
+use crate::circuit::Circuit;
+use crate::expression_parser::{expression_variables, minterm_assignment, parse_into_aig, Gate};
+
+/// Up to this many variables the two sides are simply compared row by row, 64 rows at a time.
+const EXHAUSTIVE_LIMIT: usize = 16;
+
+/// An input assignment on which two expressions disagree.
+#[derive(Debug, Clone)]
+pub struct Counterexample {
+    pub assignment: Vec<(String, bool)>,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl Counterexample {
+    /// The values of `left` and `right` in `aig` for `assignment`, simulated as the first of 64 patterns.
+    fn new(
+        aig: &Aig,
+        assignment: &HashMap<String, bool>,
+        left: Literal,
+        right: Literal,
+    ) -> Counterexample {
+        let patterns: Vec<u64> = aig.inputs().iter().map(|v| assignment[v] as u64).collect();
+        let words = aig.simulate(&patterns);
+        Counterexample {
+            assignment: aig
+                .inputs()
+                .iter()
+                .map(|v| (v.clone(), assignment[v]))
+                .collect(),
+            left: aig::value(&words, left) & 1 == 1,
+            right: aig::value(&words, right) & 1 == 1,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let values: Vec<String> = self
+            .assignment
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, *value as u8))
+            .collect();
+        format!(
+            "{} gives {} and {}",
+            values.join(","),
+            self.left as u8,
+            self.right as u8
+        )
+    }
+}
+
+/// Looks for an assignment that tells `left` and the expression `right` apart,
+/// returning `None` when they are logically equivalent.
+/// Both are built into one and-inverter graph first, `right` straight from the parser,
+/// which settles it straight away when structural hashing makes them the same node.
+pub fn find_expression_counterexample(left: &Gate, right: &str) -> Option<Counterexample> {
+    let mut variables = left.input_names();
+    variables.extend(expression_variables(right));
+    variables.sort();
+    variables.dedup();
+    let mut aig = Aig::new(variables);
+    let left_root = aig.build(left);
+    let right_root = parse_into_aig(right, &mut aig);
+    find_difference(&aig, left_root, right_root)
+}
+
+/// Like `find_expression_counterexample`, against the output `output` of `circuit`,
+/// building each of its signals once rather than once per signal reading it.
+pub fn find_circuit_counterexample(
+    left: &Gate,
+    circuit: &Circuit,
+    output: &str,
+) -> Result<Option<Counterexample>, String> {
+    let mut variables = left.input_names();
+    variables.extend(circuit.input_names());
+    variables.sort();
+    variables.dedup();
+    let mut aig = Aig::new(variables);
+    let left_root = aig.build(left);
+    let right_root = aig.build_circuit(circuit)?[output];
+    Ok(find_difference(&aig, left_root, right_root))
+}
+
+fn find_difference(aig: &Aig, left: Literal, right: Literal) -> Option<Counterexample> {
+    if left == right {
+        return None;
+    }
+    let variables = aig.inputs();
+    let assignment = if variables.len() <= EXHAUSTIVE_LIMIT {
+        aig.find_difference(left, right)
+            .map(|minterm| minterm_assignment(variables, minterm))
+    } else {
+        bdd_search(aig, left, right)
+    };
+    assignment.map(|a| Counterexample::new(aig, &a, left, right))
+}
+
+/// Builds both literals in one BDD manager, an AND at a time in the graph's order,
+/// and asks for an assignment satisfying their XOR.
+fn bdd_search(aig: &Aig, left: Literal, right: Literal) -> Option<HashMap<String, bool>> {
+    let mut bdd = Bdd::new(aig.inputs().to_vec());
+    let mut nodes: HashMap<usize, NodeId> = HashMap::new();
+    nodes.insert(0, bdd::FALSE);
+    for (i, name) in aig.inputs().iter().enumerate() {
+        nodes.insert(i + 1, bdd.variable(name));
+    }
+    let edge = |bdd: &mut Bdd, nodes: &HashMap<usize, NodeId>, literal: Literal| {
+        let node = nodes[&aig::node_index(literal)];
+        if aig::is_complemented(literal) {
+            bdd.not(node)
+        } else {
+            node
+        }
+    };
+    for index in aig.reachable(&[left, right]) {
+        if let AigNode::And(a, b) = aig.node(2 * index as Literal) {
+            let (a, b) = (edge(&mut bdd, &nodes, a), edge(&mut bdd, &nodes, b));
+            let and = bdd.and(a, b);
+            nodes.insert(index, and);
+        }
+    }
+    let (left_root, right_root) = (edge(&mut bdd, &nodes, left), edge(&mut bdd, &nodes, right));
+    let difference = bdd.xor(left_root, right_root);
+    bdd.satisfying_assignment(difference)
+        .map(|assignment| assignment.into_iter().collect())
+}
+
+/// Expressions every structural export has to read back, between them using every gate type,
+/// constants inside and as the whole expression, and a lone variable.
+#[cfg(test)]
+const ROUND_TRIPS: [&str; 7] = [
+    "ab + c'(a ^ b)",
+    "(abc)' + (a + b + c)' + (a ^ b)'",
+    "(a + 1)b ^ 0",
+    "a'",
+    "a",
+    "0",
+    "1",
+];
+
+/// Checks that `import` reads what `export` writes for each of `ROUND_TRIPS` and `extra`
+/// back as a circuit with the one output `f` computing the same function.
+#[cfg(test)]
+pub fn assert_round_trips(
+    export: &dyn Fn(&Gate) -> String,
+    import: fn(&str) -> Result<Circuit, String>,
+    extra: &[Gate],
+) {
+    let parsed = ROUND_TRIPS
+        .iter()
+        .map(|e| crate::expression_parser::parse_boolean_expression(e));
+    for tree in parsed.chain(extra.iter().cloned()) {
+        let text = export(&tree);
+        let circuit =
+            import(&text).unwrap_or_else(|e| panic!("{}: {}\n{}", tree.get_name(), e, text));
+        assert_eq!(circuit.outputs, vec!["f"], "{}", tree.get_name());
+        let counterexample = find_circuit_counterexample(&tree, &circuit, "f").unwrap();
+        assert!(
+            counterexample.is_none(),
+            "{} reads back differently: {}\n{}",
+            tree.get_name(),
+            counterexample.unwrap().describe(),
+            text
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::parse_boolean_expression;
+
+    /// Checks that `left` and `right` really do give the values the counterexample reports, and differ.
+    fn assert_counterexample(left: &str, right: &str) -> Counterexample {
+        let counterexample = find_expression_counterexample(&parse_boolean_expression(left), right)
+            .unwrap_or_else(|| panic!("{} and {} reported equivalent", left, right));
+        let assignment: HashMap<String, bool> = counterexample.assignment.iter().cloned().collect();
+        assert_eq!(
+            parse_boolean_expression(left).evaluate(&assignment),
+            counterexample.left
+        );
+        assert_eq!(
+            parse_boolean_expression(right).evaluate(&assignment),
+            counterexample.right
+        );
+        assert_ne!(
+            counterexample.left,
+            counterexample.right,
+            "{}",
+            counterexample.describe()
+        );
+        counterexample
+    }
+
+    #[test]
+    fn different_expressions_disagree_on_the_counterexample() {
+        assert_counterexample("ab + c", "a + c");
+        assert_counterexample("a ^ b", "(a ^ b)'");
+        assert!(
+            find_expression_counterexample(&parse_boolean_expression("(ab)'"), "a' + b'").is_none()
+        );
+        assert!(
+            find_expression_counterexample(&parse_boolean_expression("ab + ac"), "a(b + c)")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn wide_expressions_are_compared_as_diagrams() {
+        let letters: Vec<char> = ('a'..='z').collect();
+        assert!(letters.len() > EXHAUSTIVE_LIMIT);
+        let all: String = letters.iter().collect();
+        // the one row telling these apart is the last, 2^20 batches into an exhaustive search
+        let counterexample = assert_counterexample(&all, "0");
+        assert!(counterexample.assignment.iter().all(|&(_, value)| value));
+
+        let parity = |letters: &[char]| {
+            letters
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(" ^ ")
+        };
+        let reversed: Vec<char> = letters.iter().rev().copied().collect();
+        assert!(find_expression_counterexample(
+            &parse_boolean_expression(&parity(&letters)),
+            &parity(&reversed)
+        )
+        .is_none());
+    }
+}
@@ -1,19 +1,19 @@
 use core::panic;
 use std::{
-    char, cmp,
-    collections::VecDeque,
-    convert::TryInto,
+    collections::HashMap,
     fmt::{self, Display},
-    u32, vec,
+    vec,
 };
 
+use crate::aig::{self, Aig, Literal};
 use crate::spritesheet::Sprite;
 
 pub fn operator_precedence(c: &char) -> i8 {
     match c {
-        '*' => 3,
+        '*' => 4,
+        '^' => 3,
         '+' => 2,
-        '\'' => 4,
+        '\'' => 5,
         '(' => -1,
         ')' => -1,
         _ => 0,
@@ -24,75 +24,93 @@ fn infix_to_postfix(string: &str) -> String {
     let mut postfix = String::new();
     let mut stack: Vec<char> = Vec::new();
     String::from(string).chars().for_each(|c| {
-        println!("checking char {:}", c);
-
         if operator_precedence(&c) == 0 {
-            println!("\t{:} is variable", c);
             postfix.push(c);
+        } else if c == ')' {
+            let mut popped = stack.pop().unwrap();
+            while popped != '(' && !stack.is_empty() {
+                postfix.push(popped);
+                popped = stack.pop().unwrap();
+            }
+        } else if stack.is_empty()
+            || operator_precedence(&c) >= operator_precedence(stack.last().unwrap())
+            || c == '('
+        {
+            stack.push(c);
         } else {
-            println!("\t{:} is operator", c);
-
-            if c == ')' {
-                let mut popped = stack.pop().unwrap();
-                println!("first ) pop is {:}", popped);
-                while popped != '(' && stack.len() > 0 {
-                    postfix.push(popped);
-                    popped = stack.pop().unwrap();
-                }
-            } else {
-                if stack.len() == 0
-                    || operator_precedence(&c) >= operator_precedence(stack.last().unwrap())
-                    || c == '('
-                {
-                    println!("\t and was higher precidence");
-                    stack.push(c);
-                } else {
-                    println!("\t and was lower precidence");
-                    let mut popped = stack.pop().unwrap();
-                    while operator_precedence(&c) <= operator_precedence(&popped) {
-                        println!("\t and was lower precidence, so we're pushing {:?}", popped);
-                        postfix.push(popped);
-                        let next = stack.last();
-                        match next {
-                            Some(_) => popped = stack.pop().unwrap(),
-                            None => break,
-                        }
-                    }
-                    if operator_precedence(&c) > operator_precedence(&popped) {
-                        stack.push(popped);
-                    }
-                    stack.push(c)
+            let mut popped = stack.pop().unwrap();
+            while operator_precedence(&c) <= operator_precedence(&popped) {
+                postfix.push(popped);
+                let next = stack.last();
+                match next {
+                    Some(_) => popped = stack.pop().unwrap(),
+                    None => break,
                 }
             }
-            println!("Stack state: {:?}", stack);
+            if operator_precedence(&c) > operator_precedence(&popped) {
+                stack.push(popped);
+            }
+            stack.push(c)
         }
     });
 
-    println!("Leftovers {:?}", stack);
     for _ in 0..stack.len() {
         postfix.push(stack.pop().unwrap())
     }
 
-    println!("Finished {:?}", postfix);
-
     postfix
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum GateType {
     AND,
     OR,
     NOT,
+    NAND,
+    NOR,
+    XOR,
+    XNOR,
     INPUT,
 }
 
+impl GateType {
+    /// The output of a gate of this type with the given input values.
+    /// INPUT gates have nothing to combine; their values come from `input_value`.
+    pub fn combine(&self, mut values: impl Iterator<Item = bool>) -> bool {
+        match self {
+            GateType::NOT => !values.next().unwrap(),
+            GateType::AND => values.all(|v| v),
+            GateType::OR => values.any(|v| v),
+            GateType::NAND => !values.all(|v| v),
+            GateType::NOR => !values.any(|v| v),
+            GateType::XOR => values.filter(|&v| v).count() % 2 == 1,
+            GateType::XNOR => values.filter(|&v| v).count() % 2 == 0,
+            GateType::INPUT => panic!("INPUT gates take their value from an assignment"),
+        }
+    }
+
+    pub fn sprite(&self) -> Sprite {
+        match self {
+            GateType::AND => Sprite::AND,
+            GateType::OR => Sprite::OR,
+            GateType::NOT => Sprite::NOT,
+            GateType::NAND => Sprite::NAND,
+            GateType::NOR => Sprite::NOR,
+            GateType::XOR => Sprite::XOR,
+            GateType::XNOR => Sprite::XNOR,
+            GateType::INPUT => Sprite::INPUT,
+        }
+    }
+}
+
 impl Display for GateType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Gate {
     gate_type: GateType,
     inputs: Vec<Gate>,
@@ -106,251 +124,213 @@ impl Display for Gate {
 }
 
 impl Gate {
-    pub fn depth(&self) -> u32 {
-        if self.inputs.len() == 0 {
-            return 1;
-        } else {
-            let mut max_depth = 0;
-            self.inputs.iter().for_each(|e| {
-                max_depth = cmp::max(max_depth, e.depth());
-            });
-            return max_depth + 1;
-        }
-    }
-
-    pub fn get_type(&self) -> GateType {
-        return self.gate_type.clone();
-    }
-    pub fn get_inputs(&self) -> &Vec<Gate> {
-        return &self.inputs;
-    }
-    pub fn get_name(&self) -> String {
-        return self.name.clone();
-    }
-
-    pub fn calculate_drawn_image_width(&self) -> u32 {
-        100 * self.depth() + 30
-    }
+    /// Builds a gate from its operands in the order they were written,
+    /// so `Gate::new(GateType::AND, vec![a, b])` is named `ab`.
+    /// Inputs are stored last operand first, matching the order `create_tree` pops them.
+    pub fn new(gate_type: GateType, mut operands: Vec<Gate>) -> Gate {
+        let names_of_inputs: Vec<String> = operands
+            .iter()
+            .map(|g| {
+                // parenthesize anything that would otherwise bind the wrong way in the name
+                let needs_parentheses = match gate_type {
+                    GateType::NOT => g.gate_type != GateType::INPUT && g.gate_type != GateType::NOT,
+                    GateType::AND | GateType::NAND => {
+                        g.gate_type == GateType::OR || g.gate_type == GateType::XOR
+                    }
+                    GateType::XOR | GateType::XNOR => g.gate_type == GateType::OR,
+                    _ => false,
+                };
+                if needs_parentheses {
+                    format!("({})", g.name)
+                } else {
+                    g.name.clone()
+                }
+            })
+            .collect();
 
-    pub fn sprite_type(&self) -> Sprite {
-        match self.get_type() {
-            GateType::AND => Sprite::AND,
-            GateType::OR => Sprite::OR,
-            GateType::NOT => Sprite::NOT,
-            GateType::INPUT => Sprite::INPUT,
+        let mut created_name = names_of_inputs.join(&gate_type_to_operator_symbol(gate_type)[..]);
+        if names_of_inputs.len() == 1 {
+            created_name.push_str(&gate_type_to_operator_symbol(gate_type))
         }
-    }
-
-    pub fn child_yoffset_function(
-        &self,
-        last_origin: u32,
-        index_relative_to_siblings: u32,
-        num_parent_inputs: u32,
-    ) -> u32 {
-        let signed_index = (index_relative_to_siblings * 2) as i32 - 1;
-
-        if num_parent_inputs == 1 {
-            return last_origin;
+        if matches!(gate_type, GateType::NAND | GateType::NOR | GateType::XNOR) {
+            created_name = format!("({})'", created_name);
         }
 
-        let last_origin_i32 = last_origin as i32;
-        let signed_modifier = signed_index * (self.size_of_largest_column() as i32 * 40);
-
-        if signed_modifier.abs() > last_origin_i32.abs() {
-            println!(
-                "***** {} overflow: {} > {}",
-                self.get_type(),
-                signed_modifier,
-                last_origin_i32
-            );
+        operands.reverse();
+        Gate {
+            gate_type,
+            inputs: operands,
+            name: created_name,
         }
-
-        (last_origin_i32 + signed_modifier) as u32
     }
 
-    fn find_min_y_element(
-        &self,
-        prev_root: u32,
-        index_relative_to_siblings: u32,
-        num_parent_inputs: u32,
-    ) -> u32 {
-        let this_y =
-            self.child_yoffset_function(prev_root, index_relative_to_siblings, num_parent_inputs);
-
-            println!("{}: this_y_min: {}", self.get_name(), this_y);
-        if self.inputs.len() == 0 {
-            return this_y;
-        } else {
-            let mut child_inputs: Vec<u32> = Vec::new();
-            for i in 0..self.inputs.len() {
-                child_inputs.push(self.inputs.get(i).unwrap().find_min_y_element(
-                    this_y,
-                    i as u32,
-                    self.inputs.len() as u32,
-                ));
-            }
-
-            return this_y.min(*child_inputs.iter().min().unwrap_or(&0));
+    pub fn input(name: &str) -> Gate {
+        Gate {
+            gate_type: GateType::INPUT,
+            inputs: Vec::new(),
+            name: String::from(name),
         }
     }
 
-    fn find_max_y_element(
-        &self,
-        prev_root: u32,
-        index_relative_to_siblings: u32,
-        num_parent_inputs: u32,
-    ) -> u32 {
-        let this_y =
-            self.child_yoffset_function(prev_root, index_relative_to_siblings, num_parent_inputs);
-
-            println!("{}: this_y_max: {}", self.get_name(), this_y);
-
-        if self.inputs.len() == 0 {
-            return this_y;
-        } else {
-            let mut child_inputs: Vec<u32> = Vec::new();
-            for i in 0..self.inputs.len() {
-                let found_value = self.inputs.get(i).unwrap().find_max_y_element(
-                    this_y,
-                    i as u32,
-                    self.inputs.len() as u32,
-                );
-                child_inputs.push(found_value);
-            }
-
-            println!("child inputs for {} max {:#?}", self.name, child_inputs);
-            println!("...and max was {}", this_y.max(*child_inputs.iter().max().unwrap_or(&0)));
-
-            return this_y.max(*child_inputs.iter().max().unwrap_or(&0));
-        }
+    pub fn get_type(&self) -> GateType {
+        self.gate_type
     }
-
-    pub fn calculate_drawn_image_height(&self) -> u32 {
-        println!("Calculating drawn image height....");
-        let mut max = self.find_max_y_element((2 as u32).pow(self.depth()) * 50, 0, 1);
-        max = max + 40;
-        let min = self.find_min_y_element((2 as u32).pow(self.depth()) * 50, 0, 1);
-        println!("Found max: {}", max);
-        println!("Found min: {}", min);
-        9*(max - min)/8 + 50
+    pub fn get_inputs(&self) -> &Vec<Gate> {
+        &self.inputs
+    }
+    pub fn get_name(&self) -> String {
+        self.name.clone()
     }
 
-    pub fn adjusted_origin(&self, init_origin: u32) -> u32 {
-        // let imaginary_origin = (2 as u32).pow(self.depth()) * 50;
-        let imaginary_origin = 2048;
-        let max = self.find_max_y_element(imaginary_origin, 0, 1);
-        let min = self.find_min_y_element(imaginary_origin, 0, 1);
-
-        let diff = ((max + min) / 2) as i32 - (imaginary_origin) as i32;
-        println!("We think the origin is off by {}", diff);
-        println!("Informed by max: {}, min: {}, average: {}, origin: {}", max, min, (max + min) / 2, imaginary_origin);
-        // return (init_origin as i32 + diff) as u32;
-        (init_origin as i32 - diff) as u32 - 19
-        // (init_origin as i32) as u32
+    /// Evaluates the tree with each variable taking its value from `assignment`.
+    /// Variables missing from `assignment` panic; the literals `0` and `1` are constants.
+    pub fn evaluate(&self, assignment: &HashMap<String, bool>) -> bool {
+        match self.gate_type {
+            GateType::INPUT => input_value(&self.name, assignment),
+            gate_type => gate_type.combine(self.inputs.iter().map(|g| g.evaluate(assignment))),
+        }
     }
 
-    pub fn column_sizes(&self) -> Vec<u32> {
-        let mut queue: VecDeque<&Gate> = VecDeque::new();
-        queue.push_front(self);
-        let mut columns: Vec<u32> = vec![0];
-
-        let mut this_layer = 1;
-        let mut next_layer = 0;
-        while !queue.is_empty() {
-            *columns.last_mut().unwrap() += 1;
-            this_layer -= 1;
-
-            let this = queue.pop_back().unwrap();
-            for c in &this.inputs {
-                queue.push_front(c);
-                next_layer += 1;
-            }
+    /// Rebuilds the tree with every input renamed by `rename`, recomputing the gate names to match.
+    pub fn rename_inputs<F: Fn(&str) -> String>(&self, rename: &F) -> Gate {
+        if self.gate_type == GateType::INPUT {
+            return Gate::input(&rename(&self.name));
+        }
+        let operands = self
+            .inputs
+            .iter()
+            .rev()
+            .map(|g| g.rename_inputs(rename))
+            .collect();
+        Gate::new(self.gate_type, operands)
+    }
 
-            if this_layer == 0 {
-                this_layer = next_layer;
-                next_layer = 0;
-                if !queue.is_empty() {
-                    columns.push(0);
+    /// The distinct variable names feeding this tree, sorted.
+    /// The literals `0` and `1` are constants, not variables.
+    pub fn input_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        let mut stack: Vec<&Gate> = vec![self];
+        while let Some(gate) = stack.pop() {
+            if gate.gate_type == GateType::INPUT {
+                if gate.name != "0" && gate.name != "1" && !names.contains(&gate.name) {
+                    names.push(gate.name.clone());
                 }
+            } else {
+                stack.extend(gate.inputs.iter());
             }
         }
-
-        columns.reverse();
-        columns
-    }
-
-    pub fn size_of_largest_column(&self) -> u32 {
-        *self.column_sizes().iter().max().unwrap_or(&0)
+        names.sort();
+        names
     }
 }
 
 
 fn gate_type_to_operator_symbol(gate_type: GateType) -> String {
-    match gate_type{
+    match gate_type {
         GateType::AND => String::from(""),
-        GateType::OR =>  String::from("+"),
-        GateType::NOT =>  String::from("'"),
-        GateType::INPUT =>  String::from("")
+        GateType::OR => String::from("+"),
+        GateType::NOT => String::from("'"),
+        GateType::NAND => String::from(""),
+        GateType::NOR => String::from("+"),
+        GateType::XOR => String::from("^"),
+        GateType::XNOR => String::from("^"),
+        GateType::INPUT => String::from(""),
     }
 }
 
-fn create_tree(postfix_string: &str) -> Gate {
-    let mut stack: Vec<Gate> = Vec::new();
+/// The gate an operator character of the postfix stands for.
+fn operator_gate_type(c: char) -> GateType {
+    match c {
+        '+' => GateType::OR,
+        '*' => GateType::AND,
+        '^' => GateType::XOR,
+        '\'' => GateType::NOT,
+        _ => panic!("found operator outside of supported possibilities in create_tree"),
+    }
+}
+
+/// Runs through `postfix_string` with a stack, calling `make` with each variable and no operands,
+/// and with each operator and the operands it takes from the stack, in the order written.
+fn evaluate_postfix<T>(postfix_string: &str, mut make: impl FnMut(char, Vec<T>) -> T) -> T {
+    let mut stack: Vec<T> = Vec::new();
     postfix_string.chars().for_each(|c| {
         if operator_precedence(&c) == 0 {
-            stack.push(Gate {
-                gate_type: GateType::INPUT,
-                inputs: Vec::new(),
-                name: c.to_string(),
-            })
+            stack.push(make(c, Vec::new()))
         } else {
-            let num_to_pop = match c {
-                '+' => 2,
-                '*' => 2,
-                '\'' => 1,
-                _ => panic!("found operator outside of supported possibilities in create_tree"),
+            let num_to_pop = match operator_gate_type(c) {
+                GateType::NOT => 1,
+                _ => 2,
             };
 
-            let mut popped: Vec<Gate> = Vec::new();
+            let mut popped: Vec<T> = Vec::new();
             for _ in 0..num_to_pop {
                 popped.push(stack.pop().unwrap());
             }
-            let gate_type = match c {
-                '+' => GateType::OR,
-                '*' => GateType::AND,
-                '\'' => GateType::NOT,
-                _ => panic!("found operator outside of supported possibilities in create_tree"),
-            };
-
-            let mut names_of_inputs: Vec<String> = Vec::new();
-            for gate in &popped {
-                names_of_inputs.push(gate.name.clone());
-            }
-            names_of_inputs.reverse();
-
-            let mut created_name = names_of_inputs.join(&gate_type_to_operator_symbol(gate_type.clone())[..]);
-            if(names_of_inputs.len() == 1){
-                created_name.push_str(&gate_type_to_operator_symbol(gate_type.clone()))
-            }
-
-            stack.push(Gate {
-                gate_type: gate_type,
-                inputs: popped,
-                name: created_name
-            })
+            popped.reverse();
+            stack.push(make(c, popped))
         }
     });
     stack.remove(0)
 }
 
-pub fn parse_boolean_expression(string: &str) -> Gate {
+fn create_tree(postfix_string: &str) -> Gate {
+    evaluate_postfix(postfix_string, |c, operands| {
+        if operands.is_empty() {
+            Gate::input(&c.to_string())
+        } else {
+            Gate::new(operator_gate_type(c), operands)
+        }
+    })
+}
+
+/// The assignment for row `minterm` of a truth table over `variables`,
+/// with the first variable as the most significant bit.
+pub fn minterm_assignment(variables: &[String], minterm: u64) -> HashMap<String, bool> {
+    let n = variables.len();
+    variables
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (v.clone(), (minterm >> (n - 1 - i)) & 1 == 1))
+        .collect()
+}
+
+/// The value of the input called `name`, where the literals `0` and `1` are constants.
+pub fn input_value(name: &str, assignment: &HashMap<String, bool>) -> bool {
+    match name {
+        "0" => false,
+        "1" => true,
+        name => *assignment
+            .get(name)
+            .unwrap_or_else(|| panic!("no value assigned to input {}", name)),
+    }
+}
+
+/// Parses an assignment written `a=1,b=0,c=1`.
+pub fn parse_assignment(text: &str) -> Result<HashMap<String, bool>, String> {
+    text.split(',')
+        .map(|pair| {
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected name=value, found {}", pair))?;
+            let value = match value.trim() {
+                "0" => false,
+                "1" => true,
+                other => return Err(format!("{} is not 0 or 1", other)),
+            };
+            Ok((String::from(name.trim()), value))
+        })
+        .collect()
+}
+
+/// `string` in postfix, with whitespace dropped and the `*` it leaves implicit written in.
+fn to_postfix(string: &str) -> String {
     let mut condensed = String::from(string);
     condensed.retain(|c| !c.is_whitespace());
 
     let mut explicitly_multiplied = String::new();
 
     let chars: Vec<char> = condensed.chars().collect();
-    for i in 0..(condensed.len() - 1) {
+    for i in 0..(chars.len() - 1) {
         let c1 = chars.get(i).unwrap();
         let c2 = chars.get(i + 1).unwrap();
         let p1 = operator_precedence(c1);
@@ -358,23 +338,87 @@ pub fn parse_boolean_expression(string: &str) -> Gate {
 
         explicitly_multiplied.push(*chars.get(i).unwrap());
 
-        if (p1 == 0 && p2 == 0)
-            || (p1 == 0 && *c2 == '(')
-            || (*c1 == ')' && p2 == 0)
-            || (*c1 == '\'' && p2 == 0)
-            || (*c1 == ')' && *c2 == '(')
-        {
+        let c1_ends_operand = p1 == 0 || *c1 == ')' || *c1 == '\'';
+        let c2_starts_operand = p2 == 0 || *c2 == '(';
+        if c1_ends_operand && c2_starts_operand {
             explicitly_multiplied.push('*');
         }
     }
     explicitly_multiplied.push(*chars.last().unwrap());
 
-    println!("{:?}", explicitly_multiplied);
+    infix_to_postfix(&explicitly_multiplied)
+}
 
-    let postfix = infix_to_postfix(&explicitly_multiplied);
-    let tree = create_tree(&postfix);
+/// Whether `string` is an expression the parser can read, with every operator given its operands
+/// and every bracket closed, or what's wrong with it.
+pub fn check_expression(string: &str) -> Result<(), String> {
+    let mut expecting_operand = true;
+    let mut depth = 0;
+    for c in string.chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            '(' => {
+                depth += 1;
+                expecting_operand = true;
+            }
+            ')' if depth == 0 => {
+                return Err(format!("{} has a ) with nothing to close", string.trim()))
+            }
+            '\'' | '+' | '*' | '^' | ')' if expecting_operand => {
+                return Err(format!(
+                    "{} has a {} with no operand before it",
+                    string.trim(),
+                    c
+                ))
+            }
+            ')' => depth -= 1,
+            '\'' => {}
+            '+' | '*' | '^' => expecting_operand = true,
+            // anything else is a variable, which may follow an operand to be multiplied by it
+            _ => expecting_operand = false,
+        }
+    }
+    if expecting_operand {
+        Err(format!("{} ends without an operand", string.trim()))
+    } else if depth > 0 {
+        Err(format!("{} leaves a ( unclosed", string.trim()))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn parse_boolean_expression(string: &str) -> Gate {
+    create_tree(&to_postfix(string))
+}
 
-    println!("{:?}", tree);
+/// The variables `string` uses, sorted, as `Gate::input_names` gives them for its tree.
+pub fn expression_variables(string: &str) -> Vec<String> {
+    let mut variables: Vec<String> = string
+        .chars()
+        .filter(|c| !c.is_whitespace() && operator_precedence(c) == 0 && *c != '0' && *c != '1')
+        .map(String::from)
+        .collect();
+    variables.sort();
+    variables.dedup();
+    variables
+}
 
-    tree
+/// Parses `string` straight into `aig`, without building a tree, and returns its literal.
+/// Every variable it uses has to be an input of `aig` already; `expression_variables` lists them.
+pub fn parse_into_aig(string: &str, aig: &mut Aig) -> Literal {
+    evaluate_postfix(&to_postfix(string), |c, operands| {
+        match *operands.as_slice() {
+            [] => match c {
+                '0' => aig::FALSE,
+                '1' => aig::TRUE,
+                _ => aig.variable(&c.to_string()),
+            },
+            [a] => aig::negate(a),
+            [a, b] => match operator_gate_type(c) {
+                GateType::AND => aig.and(a, b),
+                GateType::OR => aig.or(a, b),
+                _ => aig.xor(a, b),
+            },
+            _ => unreachable!(),
+        }
+    })
 }
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use crate::expression_parser::{input_value, Gate, GateType};
+use crate::spritesheet::Sprite;
+
+pub type GateId = usize;
+
+/// The output gate, which every other gate feeds into eventually.
+pub const ROOT: GateId = 0;
+
+/// A gate in a `GateGraph`, with the layout figures the renderer asks about worked out once.
+#[derive(Debug, Clone)]
+pub struct GateNode {
+    pub gate_type: GateType,
+    /// The gate's subexpression, as `Gate::get_name` gives it, or an input's variable or constant.
+    pub name: String,
+    /// The same order as `Gate::get_inputs`, last operand first.
+    pub inputs: Vec<GateId>,
+    /// The gate this one feeds and which of its inputs it is, except for the root.
+    pub parent: Option<(GateId, usize)>,
+    /// Gates on the longest path down to an input, counting both ends.
+    pub depth: u32,
+    /// The most gates in any one column of the tree under this gate.
+    pub largest_column: u32,
+    /// The column the gate is drawn in, counting from 1 at the left edge.
+    pub column: u32,
+}
+
+impl GateNode {
+    pub fn sprite_type(&self) -> Sprite {
+        self.gate_type.sprite()
+    }
+}
+
+/// A tree of gates kept in one `Vec` that owns them, each node pointing at its inputs and its parent by index.
+/// Nodes are numbered depth first from the root, so a gate always comes before its inputs.
+/// Since inputs are indices, one node could feed several gates, but the layout gives each node
+/// the one parent it has in the tree it was built from.
+pub struct GateGraph {
+    pub nodes: Vec<GateNode>,
+    columns: Vec<u32>,
+}
+
+impl GateGraph {
+    pub fn new(tree: &Gate) -> GateGraph {
+        let mut nodes = Vec::new();
+        add(tree, None, &mut nodes);
+
+        // Column sizes are gathered from the inputs up, each gate reusing the counts of its deepest input
+        // and adding the others' to them, so every gate is only counted again when a deeper subtree absorbs it.
+        // The counts run from the deepest column to the gate's own, so a gate's count is pushed on the end.
+        let mut counts: Vec<Vec<u32>> = vec![Vec::new(); nodes.len()];
+        for id in (0..nodes.len()).rev() {
+            let inputs = nodes[id].inputs.clone();
+            let deepest = inputs
+                .iter()
+                .copied()
+                .max_by_key(|&input| nodes[input].depth);
+            let (mut columns, mut largest) = match deepest {
+                Some(deepest) => (
+                    std::mem::take(&mut counts[deepest]),
+                    nodes[deepest].largest_column,
+                ),
+                None => (Vec::new(), 0),
+            };
+            for &input in inputs.iter().filter(|&&input| Some(input) != deepest) {
+                let offset = columns.len() - counts[input].len();
+                for (i, count) in std::mem::take(&mut counts[input]).into_iter().enumerate() {
+                    columns[offset + i] += count;
+                    largest = largest.max(columns[offset + i]);
+                }
+            }
+            columns.push(1);
+            nodes[id].depth = columns.len() as u32;
+            nodes[id].largest_column = largest.max(1);
+            counts[id] = columns;
+        }
+
+        let root_depth = nodes[ROOT].depth;
+        for id in 0..nodes.len() {
+            nodes[id].column = match nodes[id].parent {
+                Some((parent, _)) => nodes[parent].column - 1,
+                None => root_depth,
+            };
+        }
+
+        GateGraph {
+            nodes,
+            columns: std::mem::take(&mut counts[ROOT]),
+        }
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.nodes[ROOT].depth
+    }
+
+    /// How many gates are drawn in each column, leftmost first.
+    pub fn column_sizes(&self) -> Vec<u32> {
+        self.columns.clone()
+    }
+
+    /// How far below its parent gate `id` goes, or above when negative. Inputs fan out above and below
+    /// their parent, far enough apart for the widest column of their own trees.
+    fn child_offset(&self, id: GateId) -> i64 {
+        let (parent, index) = match self.nodes[id].parent {
+            Some(parent) => parent,
+            None => return 0,
+        };
+        if self.nodes[parent].inputs.len() == 1 {
+            return 0;
+        }
+        let signed_index = (index * 2) as i64 - 1;
+        signed_index * (self.nodes[id].largest_column as i64 * 40)
+    }
+
+    /// How far below the root every gate's sprite goes, in one pass down from the root.
+    /// These are worked out in `i64` so that deep trees can't overflow.
+    fn offsets(&self) -> Vec<i64> {
+        let mut offsets = vec![0; self.nodes.len()];
+        for id in 1..self.nodes.len() {
+            let (parent, _) = self.nodes[id].parent.unwrap();
+            offsets[id] = offsets[parent] + self.child_offset(id);
+        }
+        offsets
+    }
+
+    /// The value on every gate's output for `assignment`, each gate evaluated once from its inputs' values,
+    /// which come after it.
+    pub fn values(&self, assignment: &HashMap<String, bool>) -> Vec<bool> {
+        let mut values = vec![false; self.nodes.len()];
+        for id in (0..self.nodes.len()).rev() {
+            let node = &self.nodes[id];
+            values[id] = match node.gate_type {
+                GateType::INPUT => input_value(&node.name, assignment),
+                gate_type => gate_type.combine(node.inputs.iter().map(|&input| values[input])),
+            };
+        }
+        values
+    }
+
+    /// The y of every gate's sprite with the root at `root_y`. Anything that would land above the top
+    /// of the image is put at 0, which `adjusted_origin` keeps from happening.
+    pub fn positions(&self, root_y: u32) -> Vec<u32> {
+        self.offsets()
+            .into_iter()
+            .map(|offset| saturating_u32(root_y as i64 + offset))
+            .collect()
+    }
+
+    pub fn drawn_image_width(&self) -> u32 {
+        100 * self.depth() + 30
+    }
+
+    /// Tall enough for every sprite with some room around them, or `u32::MAX` if no image could be.
+    pub fn drawn_image_height(&self) -> u32 {
+        let offsets = self.offsets();
+        let max = offsets.iter().max().unwrap() + 40;
+        let min = offsets.iter().min().unwrap();
+        saturating_u32(9 * (max - min) / 8 + 50)
+    }
+
+    /// Where to put the root so the drawing is centred on `init_origin`,
+    /// found by seeing how far the middle of the layout lands from the root.
+    pub fn adjusted_origin(&self, init_origin: u32) -> u32 {
+        let offsets = self.offsets();
+        let max = *offsets.iter().max().unwrap();
+        let min = *offsets.iter().min().unwrap();
+
+        let diff = (max + min) / 2;
+        saturating_u32(init_origin as i64 - diff - 19)
+    }
+}
+
+fn saturating_u32(value: i64) -> u32 {
+    value.clamp(0, u32::MAX as i64) as u32
+}
+
+fn add(gate: &Gate, parent: Option<(GateId, usize)>, nodes: &mut Vec<GateNode>) -> GateId {
+    let id = nodes.len();
+    nodes.push(GateNode {
+        gate_type: gate.get_type(),
+        name: gate.get_name(),
+        inputs: Vec::new(),
+        parent,
+        depth: 1,
+        largest_column: 1,
+        column: 0,
+    });
+    for (i, input) in gate.get_inputs().iter().enumerate() {
+        let input_id = add(input, Some((id, i)), nodes);
+        nodes[id].inputs.push(input_id);
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::{minterm_assignment, parse_boolean_expression};
+
+    /// Every subtree of `gate` in the order `GateGraph::new` numbers them.
+    fn preorder<'a>(gate: &'a Gate, subtrees: &mut Vec<&'a Gate>) {
+        subtrees.push(gate);
+        for input in gate.get_inputs() {
+            preorder(input, subtrees);
+        }
+    }
+
+    #[test]
+    fn values_agree_with_evaluating_each_gate() {
+        let tree = parse_boolean_expression("(ab)' + c ^ a + (b + 0)'1");
+        let graph = GateGraph::new(&tree);
+        let mut subtrees = Vec::new();
+        preorder(&tree, &mut subtrees);
+        assert_eq!(subtrees.len(), graph.nodes.len());
+        let variables = tree.input_names();
+        for row in 0..1 << variables.len() {
+            let assignment = minterm_assignment(&variables, row);
+            let values = graph.values(&assignment);
+            for ((node, gate), value) in graph.nodes.iter().zip(&subtrees).zip(values) {
+                assert_eq!(node.name, gate.get_name());
+                assert_eq!(gate.evaluate(&assignment), value, "{}", node.name);
+            }
+        }
+    }
+
+    #[test]
+    fn balanced_trees_fill_their_columns() {
+        // OR, then the two ANDs, each followed by its inputs last first: d, c, b, a
+        let graph = GateGraph::new(&parse_boolean_expression("ab + cd"));
+        assert_eq!(graph.depth(), 3);
+        assert_eq!(graph.column_sizes(), vec![4, 2, 1]);
+        let depths: Vec<u32> = graph.nodes.iter().map(|node| node.depth).collect();
+        assert_eq!(depths, vec![3, 2, 1, 1, 2, 1, 1]);
+        let largest: Vec<u32> = graph.nodes.iter().map(|node| node.largest_column).collect();
+        assert_eq!(largest, vec![4, 2, 1, 1, 2, 1, 1]);
+        let columns: Vec<u32> = graph.nodes.iter().map(|node| node.column).collect();
+        assert_eq!(columns, vec![3, 2, 1, 1, 2, 1, 1]);
+    }
+
+    #[test]
+    fn shallow_inputs_sit_next_to_their_gate() {
+        // OR, d, the outer AND, c, NOT, the inner AND, b, a
+        let graph = GateGraph::new(&parse_boolean_expression("(ab)'c + d"));
+        assert_eq!(graph.depth(), 5);
+        assert_eq!(graph.column_sizes(), vec![2, 1, 2, 2, 1]);
+        let depths: Vec<u32> = graph.nodes.iter().map(|node| node.depth).collect();
+        assert_eq!(depths, vec![5, 1, 4, 1, 3, 2, 1, 1]);
+        let largest: Vec<u32> = graph.nodes.iter().map(|node| node.largest_column).collect();
+        assert_eq!(largest, vec![2, 1, 2, 1, 2, 2, 1, 1]);
+        let columns: Vec<u32> = graph.nodes.iter().map(|node| node.column).collect();
+        assert_eq!(columns, vec![5, 4, 4, 3, 3, 2, 1, 1]);
+    }
+
+    #[test]
+    fn deep_trees_are_laid_out_without_overflowing() {
+        let mut tree = Gate::input("x0");
+        for i in 1..100 {
+            tree = Gate::new(GateType::AND, vec![Gate::input(&format!("x{}", i)), tree]);
+        }
+        let graph = GateGraph::new(&tree);
+        assert_eq!(graph.depth(), 100);
+        let height = graph.drawn_image_height();
+        let positions = graph.positions(graph.adjusted_origin(height / 2));
+        assert!(positions.iter().all(|&y| y + 40 <= height), "{}", height);
+    }
+}
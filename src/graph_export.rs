@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use crate::expression_parser::{Gate, GateType};
+
+/// The circuit as a graph: one node per gate and one per distinct variable, with edges from each
+/// gate's operands to it, numbered from the output in the order they're met.
+struct Graph<'a> {
+    nodes: Vec<&'a Gate>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl<'a> Graph<'a> {
+    fn new(tree: &'a Gate) -> Graph<'a> {
+        let mut graph = Graph {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        };
+        graph.add(tree, &mut HashMap::new());
+        graph
+    }
+
+    fn add(&mut self, gate: &'a Gate, variables: &mut HashMap<String, usize>) -> usize {
+        if gate.get_type() == GateType::INPUT {
+            if let Some(&id) = variables.get(&gate.get_name()) {
+                return id;
+            }
+        }
+        let id = self.nodes.len();
+        self.nodes.push(gate);
+        if gate.get_type() == GateType::INPUT {
+            variables.insert(gate.get_name(), id);
+        }
+        // operands are stored last first
+        for input in gate.get_inputs().iter().rev() {
+            let input_id = self.add(input, variables);
+            self.edges.push((input_id, id));
+        }
+        id
+    }
+}
+
+/// A Graphviz digraph drawn left to right, with a node shape standing in for each gate type.
+pub fn dot(tree: &Gate) -> String {
+    let graph = Graph::new(tree);
+    let mut text = String::from("digraph circuit {\n    rankdir=LR;\n");
+    for (id, gate) in graph.nodes.iter().enumerate() {
+        text.push_str(&format!(
+            "    n{} [label=\"{}\", shape={}];\n",
+            id,
+            gate.get_name().replace('\\', "\\\\").replace('"', "\\\""),
+            dot_shape(gate.get_type())
+        ));
+    }
+    for (from, to) in graph.edges {
+        text.push_str(&format!("    n{} -> n{};\n", from, to));
+    }
+    text.push_str("}\n");
+    text
+}
+
+/// A Mermaid flowchart drawn left to right, shaped like `dot`.
+pub fn mermaid(tree: &Gate) -> String {
+    let graph = Graph::new(tree);
+    let mut text = String::from("flowchart LR\n");
+    for (id, gate) in graph.nodes.iter().enumerate() {
+        let (open, close) = mermaid_shape(gate.get_type());
+        text.push_str(&format!(
+            "    n{}{}\"{}\"{}\n",
+            id,
+            open,
+            gate.get_name().replace('"', "#quot;"),
+            close
+        ));
+    }
+    for (from, to) in graph.edges {
+        text.push_str(&format!("    n{} --> n{}\n", from, to));
+    }
+    text
+}
+
+fn dot_shape(gate_type: GateType) -> &'static str {
+    match gate_type {
+        GateType::INPUT => "plaintext",
+        GateType::AND => "box",
+        GateType::OR => "ellipse",
+        GateType::NOT => "triangle",
+        GateType::NAND => "Msquare",
+        GateType::NOR => "Mcircle",
+        GateType::XOR => "diamond",
+        GateType::XNOR => "Mdiamond",
+    }
+}
+
+fn mermaid_shape(gate_type: GateType) -> (&'static str, &'static str) {
+    match gate_type {
+        GateType::INPUT => ("((", "))"),
+        GateType::AND => ("[", "]"),
+        GateType::OR => ("([", "])"),
+        GateType::NOT => (">", "]"),
+        GateType::NAND => ("[[", "]]"),
+        GateType::NOR => ("[(", ")]"),
+        GateType::XOR => ("{", "}"),
+        GateType::XNOR => ("{{", "}}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::parse_boolean_expression;
+
+    // five gates and the variables a, b and c once each, with nine gate inputs between them
+    const EXPRESSION: &str = "ab + c'(a ^ b)";
+
+    #[test]
+    fn dot_has_a_node_per_gate_and_variable() {
+        let text = dot(&parse_boolean_expression(EXPRESSION));
+        assert!(
+            text.starts_with("digraph circuit {\n    rankdir=LR;\n"),
+            "{}",
+            text
+        );
+        assert_eq!(text.matches(" [label=").count(), 8, "{}", text);
+        assert_eq!(text.matches(" -> ").count(), 9, "{}", text);
+        // numbered from the output: the OR, ab, then a, which feeds both ab and a ^ b
+        assert!(
+            text.contains("    n0 [label=\"ab+c'(a^b)\", shape=ellipse];"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("    n2 [label=\"a\", shape=plaintext];"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("    n2 -> n1;\n") && text.contains("    n2 -> n7;\n"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn mermaid_has_the_same_graph() {
+        let text = mermaid(&parse_boolean_expression(EXPRESSION));
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "flowchart LR");
+        let edges = lines.iter().filter(|line| line.contains(" --> ")).count();
+        assert_eq!((lines.len() - 1 - edges, edges), (8, 9), "{}", text);
+        assert!(text.contains("    n2((\"a\"))\n"), "{}", text);
+        assert!(text.contains("    n2 --> n7\n"), "{}", text);
+    }
+}
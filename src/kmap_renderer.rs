@@ -0,0 +1,269 @@
+use image::{ImageBuffer, Rgba, RgbaImage};
+use rusttype::Font;
+
+use crate::expression_parser::{minterm_assignment, Gate};
+use crate::minimizer::{Cover, Cube};
+use crate::renderer::{draw_text, load_font, text_width};
+
+const CELL_SIZE: u32 = 50;
+const MARGIN: u32 = 30;
+const HEADER_SIZE: u32 = 60;
+const FONT_SIZE: f32 = 22.0;
+
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const GROUP_COLORS: [Rgba<u8>; 6] = [
+    Rgba([214, 39, 40, 255]),
+    Rgba([31, 119, 180, 255]),
+    Rgba([44, 160, 44, 255]),
+    Rgba([255, 127, 14, 255]),
+    Rgba([148, 103, 189, 255]),
+    Rgba([23, 190, 207, 255]),
+];
+
+fn gray_code(i: u32) -> u32 {
+    i ^ (i >> 1)
+}
+
+fn binary_label(value: u32, bits: u32) -> String {
+    (0..bits)
+        .rev()
+        .map(|b| if value >> b & 1 == 1 { '1' } else { '0' })
+        .collect()
+}
+
+/// Splits sorted indices into runs of neighbours, so a group that wraps
+/// around the edge of the map is drawn as one rectangle per side.
+fn contiguous_runs(indices: &[u32]) -> Vec<(u32, u32)> {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &i in indices {
+        match runs.last_mut() {
+            Some(run) if run.1 + 1 == i => run.1 = i,
+            _ => runs.push((i, i)),
+        }
+    }
+    runs
+}
+
+/// Draws a Karnaugh map for expressions of 2 to 6 variables.
+/// The first half of the variables run down the rows, the rest across the columns,
+/// both in Gray code order.
+pub struct KarnaughRenderer {
+    image: RgbaImage,
+    font: Font<'static>,
+    variables: Vec<String>,
+    row_bits: u32,
+    column_bits: u32,
+}
+
+impl KarnaughRenderer {
+    /// A blank map for `tree`, or why it can't have one.
+    pub fn new(tree: &Gate) -> Result<KarnaughRenderer, String> {
+        let variables = tree.input_names();
+        if variables.len() < 2 || variables.len() > 6 {
+            return Err(format!(
+                "Karnaugh maps need 2 to 6 variables, {} has {}",
+                tree.get_name(),
+                variables.len()
+            ));
+        }
+
+        let row_bits = variables.len() as u32 / 2;
+        let column_bits = variables.len() as u32 - row_bits;
+
+        Ok(KarnaughRenderer {
+            image: ImageBuffer::from_fn(
+                2 * MARGIN + HEADER_SIZE + CELL_SIZE * (1 << column_bits),
+                2 * MARGIN + HEADER_SIZE + CELL_SIZE * (1 << row_bits),
+                |_, _| Rgba([255, 255, 255, 255]),
+            ),
+            font: load_font(),
+            variables,
+            row_bits,
+            column_bits,
+        })
+    }
+
+    fn cell_origin(&self, row: u32, column: u32) -> (u32, u32) {
+        (
+            MARGIN + HEADER_SIZE + column * CELL_SIZE,
+            MARGIN + HEADER_SIZE + row * CELL_SIZE,
+        )
+    }
+
+    fn minterm_at(&self, row: u32, column: u32) -> u64 {
+        ((gray_code(row) << self.column_bits) | gray_code(column)) as u64
+    }
+
+    /// Draws the map of `tree`, outlining each cube of `groups` in its own colour when given.
+    pub fn draw(&mut self, tree: &Gate, groups: Option<&Cover>) {
+        self.draw_headers();
+
+        for row in 0..(1 << self.row_bits) {
+            for column in 0..(1 << self.column_bits) {
+                let assignment = minterm_assignment(&self.variables, self.minterm_at(row, column));
+                let value = if tree.evaluate(&assignment) { "1" } else { "0" };
+                self.draw_centered_text(value, self.cell_origin(row, column), CELL_SIZE);
+            }
+        }
+
+        self.draw_grid();
+
+        if let Some(cover) = groups {
+            for (index, cube) in cover.cubes.iter().enumerate() {
+                self.draw_group(cube, index);
+            }
+        }
+    }
+
+    fn draw_headers(&mut self) {
+        let row_names: String = self.variables[..self.row_bits as usize].concat();
+        let column_names: String = self.variables[self.row_bits as usize..].concat();
+
+        // a diagonal through the corner cell, row variables below it and column variables above
+        for i in 0..HEADER_SIZE {
+            self.image.put_pixel(MARGIN + i, MARGIN + i, BLACK);
+        }
+        draw_text(
+            &mut self.image,
+            &self.font,
+            &row_names,
+            MARGIN,
+            MARGIN + HEADER_SIZE - FONT_SIZE as u32 - 4,
+            FONT_SIZE,
+            BLACK,
+        );
+        let column_names_width = text_width(&self.font, &column_names, FONT_SIZE);
+        draw_text(
+            &mut self.image,
+            &self.font,
+            &column_names,
+            MARGIN + HEADER_SIZE - column_names_width - 4,
+            MARGIN,
+            FONT_SIZE,
+            BLACK,
+        );
+
+        for column in 0..(1 << self.column_bits) {
+            let label = binary_label(gray_code(column), self.column_bits);
+            let (x, _) = self.cell_origin(0, column);
+            self.draw_centered_text(&label, (x, MARGIN + HEADER_SIZE - CELL_SIZE), CELL_SIZE);
+        }
+        for row in 0..(1 << self.row_bits) {
+            let label = binary_label(gray_code(row), self.row_bits);
+            let (_, y) = self.cell_origin(row, 0);
+            self.draw_centered_text(&label, (MARGIN + HEADER_SIZE - CELL_SIZE, y), CELL_SIZE);
+        }
+    }
+
+    fn draw_centered_text(&mut self, text: &str, origin: (u32, u32), box_size: u32) {
+        let width = text_width(&self.font, text, FONT_SIZE);
+        draw_text(
+            &mut self.image,
+            &self.font,
+            text,
+            origin.0 + (box_size - width) / 2,
+            origin.1 + (box_size - FONT_SIZE as u32) / 2,
+            FONT_SIZE,
+            BLACK,
+        );
+    }
+
+    fn draw_grid(&mut self) {
+        let (left, top) = self.cell_origin(0, 0);
+        let (right, bottom) = self.cell_origin(1 << self.row_bits, 1 << self.column_bits);
+
+        for row in 0..=(1 << self.row_bits) {
+            let (_, y) = self.cell_origin(row, 0);
+            self.rectangle(left, y, right + 1, y + 1, BLACK);
+        }
+        for column in 0..=(1 << self.column_bits) {
+            let (x, _) = self.cell_origin(0, column);
+            self.rectangle(x, top, x + 1, bottom + 1, BLACK);
+        }
+    }
+
+    /// Outlines the cells covered by `cube`.
+    /// Successive groups are inset a little further so overlapping groups stay visible.
+    fn draw_group(&mut self, cube: &Cube, index: usize) {
+        let n = self.variables.len();
+        let contains = |r, c| cube.contains_minterm(self.minterm_at(r, c), n);
+        let rows: Vec<u32> = (0..(1 << self.row_bits))
+            .filter(|&r| (0..(1 << self.column_bits)).any(|c| contains(r, c)))
+            .collect();
+        let columns: Vec<u32> = (0..(1 << self.column_bits))
+            .filter(|&c| (0..(1 << self.row_bits)).any(|r| contains(r, c)))
+            .collect();
+
+        let inset = 4 + 3 * (index as u32 % 5);
+        let color = GROUP_COLORS[index % GROUP_COLORS.len()];
+        for &(first_row, last_row) in &contiguous_runs(&rows) {
+            for &(first_column, last_column) in &contiguous_runs(&columns) {
+                let (left, top) = self.cell_origin(first_row, first_column);
+                let (right, bottom) = self.cell_origin(last_row + 1, last_column + 1);
+                self.outline(
+                    left + inset,
+                    top + inset,
+                    right - inset,
+                    bottom - inset,
+                    color,
+                );
+            }
+        }
+    }
+
+    fn outline(&mut self, left: u32, top: u32, right: u32, bottom: u32, color: Rgba<u8>) {
+        self.rectangle(left, top, right, top + 2, color);
+        self.rectangle(left, bottom - 2, right, bottom, color);
+        self.rectangle(left, top, left + 2, bottom, color);
+        self.rectangle(right - 2, top, right, bottom, color);
+    }
+
+    fn rectangle(&mut self, left: u32, top: u32, right: u32, bottom: u32, color: Rgba<u8>) {
+        for x in left..right {
+            for y in top..bottom {
+                self.image.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    pub fn export(&self, path: &str) {
+        self.image.save(path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::parse_boolean_expression;
+
+    #[test]
+    fn maps_need_two_to_six_variables() {
+        for expression in ["a", "a + 1", "abcdefg"] {
+            let error = KarnaughRenderer::new(&parse_boolean_expression(expression)).err();
+            assert!(error.is_some(), "{}", expression);
+        }
+        // four columns by two rows for three variables, eight by eight for six
+        for (expression, columns, rows) in [("ab", 2, 2), ("abc", 4, 2), ("abcdef", 8, 8)] {
+            let renderer = KarnaughRenderer::new(&parse_boolean_expression(expression)).unwrap();
+            let (width, height) = renderer.image.dimensions();
+            assert_eq!(width, 2 * MARGIN + HEADER_SIZE + CELL_SIZE * columns);
+            assert_eq!(height, 2 * MARGIN + HEADER_SIZE + CELL_SIZE * rows);
+        }
+    }
+
+    #[test]
+    fn cells_follow_gray_code() {
+        let renderer = KarnaughRenderer::new(&parse_boolean_expression("abcd")).unwrap();
+        // neighbouring cells, wrapping around the edges, differ in one variable
+        for row in 0..4 {
+            for column in 0..4 {
+                let here = renderer.minterm_at(row, column);
+                let right = renderer.minterm_at(row, (column + 1) % 4);
+                let below = renderer.minterm_at((row + 1) % 4, column);
+                assert_eq!((here ^ right).count_ones(), 1);
+                assert_eq!((here ^ below).count_ones(), 1);
+            }
+        }
+        assert_eq!(renderer.minterm_at(2, 3), 0b1110);
+    }
+}
@@ -0,0 +1,778 @@
+use std::collections::HashMap;
+
+use crate::circuit::{self, Circuit, NetlistGate};
+use crate::expression_parser::{Gate, GateType};
+use crate::gate_graph::{GateGraph, GateNode};
+use crate::spritesheet::sprite_info;
+
+/// Logisim places everything on a 10 unit grid.
+const GRID: i32 = 10;
+/// The renderer's pixels are scaled up by this much, since Logisim's gates are about twice the height of our sprites.
+const SCALE: i32 = 2;
+/// Room for the widest gate (XNOR, with its extra curve and bubble) between a column's inputs and the channel after it.
+const BODY_WIDTH: i32 = 70;
+const MARGIN: i32 = 60;
+/// Room on the left for the input pins, which reach the places each variable is used through tunnels.
+const PIN_AREA: i32 = 120;
+const PIN_SPACING: i32 = 40;
+
+/// A gate or pin placed on Logisim's grid, in the column the renderer draws it in.
+struct Component<'a> {
+    node: &'a GateNode,
+    column: usize,
+    /// Where its output is, which Logisim takes as a gate's location.
+    y: i32,
+    /// The component and pin its output feeds, unless it's the root.
+    feeds: Option<(usize, usize)>,
+}
+
+/// A `.circ` file for Logisim-evolution holding one circuit named `name`, with a gate per gate placed
+/// where the renderer draws it and wired up to an output pin named `output`. Each variable gets one input pin,
+/// tunnelled to wherever the tree uses it.
+pub fn circ(tree: &Gate, name: &str, output: &str) -> String {
+    let graph = GateGraph::new(tree);
+    let mut components = place(&graph);
+    spread_columns(&mut components, graph.depth() as usize);
+    let top = components.iter().map(|c| c.y).min().unwrap_or(0);
+    for component in &mut components {
+        component.y += MARGIN - top;
+    }
+
+    // the wires leaving each column run through its own channel, one lane each unless they go straight across
+    let columns = graph.depth() as usize;
+    let mut channels: Vec<Vec<usize>> = vec![Vec::new(); columns];
+    for (id, component) in components.iter().enumerate() {
+        if let Some((parent, pin)) = component.feeds {
+            if component.y != input_y(&components[parent], pin) {
+                channels[component.column].push(id);
+            }
+        }
+    }
+    for channel in &mut channels {
+        *channel = order_lanes(channel, &components);
+    }
+    let mut input_x = vec![MARGIN + PIN_AREA; columns];
+    for column in 1..columns {
+        input_x[column] =
+            input_x[column - 1] + BODY_WIDTH + GRID * (channels[column - 1].len() as i32 + 2);
+    }
+
+    let mut text = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+         <project source=\"3.8.0\" version=\"1.0\">\n  \
+         This file is intended to be loaded by Logisim-evolution v3.8.0(https://github.com/logisim-evolution/).\n\n  \
+         <lib desc=\"#Wiring\" name=\"0\"/>\n  \
+         <lib desc=\"#Gates\" name=\"1\"/>\n  \
+         <main name=\"{0}\"/>\n  \
+         <circuit name=\"{0}\">\n    \
+         <a name=\"appearance\" val=\"logisim_evolution\"/>\n    \
+         <a name=\"circuit\" val=\"{0}\"/>\n",
+        escape(name)
+    );
+    let output_x =
+        |component: &Component| input_x[component.column] + axis_length(component.node.gate_type);
+
+    for (i, name) in tree.input_names().iter().enumerate() {
+        let location = format!("loc=\"({},{})\"", MARGIN, MARGIN + PIN_SPACING * i as i32);
+        text.push_str(&format!(
+            "    <comp lib=\"0\" {0} name=\"Pin\">\n      \
+             <a name=\"tristate\" val=\"false\"/>\n      \
+             <a name=\"label\" val=\"{1}\"/>\n    \
+             </comp>\n    \
+             <comp lib=\"0\" {0} name=\"Tunnel\">\n      \
+             <a name=\"label\" val=\"{1}\"/>\n    \
+             </comp>\n",
+            location,
+            escape(name)
+        ));
+    }
+    for component in &components {
+        text.push_str(&component_xml(component, output_x(component)));
+    }
+
+    for (id, component) in components.iter().enumerate() {
+        let (parent, pin) = match component.feeds {
+            Some(feeds) => feeds,
+            None => continue,
+        };
+        let from = [output_x(component), component.y];
+        let to = [
+            input_x[components[parent].column],
+            input_y(&components[parent], pin),
+        ];
+        match channels[component.column].iter().position(|&i| i == id) {
+            None => text.push_str(&wire(from, to)),
+            Some(lane) => {
+                let lane_x = input_x[component.column] + BODY_WIDTH + GRID * (lane as i32 + 1);
+                text.push_str(&wire(from, [lane_x, from[1]]));
+                text.push_str(&wire([lane_x, from[1]], [lane_x, to[1]]));
+                text.push_str(&wire([lane_x, to[1]], to));
+            }
+        }
+    }
+
+    // the output pin sits past the root's column, facing back towards it
+    let root = &components[0];
+    let pin_x = input_x[columns - 1] + BODY_WIDTH + 4 * GRID;
+    text.push_str(&wire([output_x(root), root.y], [pin_x, root.y]));
+    text.push_str(&format!(
+        "    <comp lib=\"0\" loc=\"({},{})\" name=\"Pin\">\n      \
+         <a name=\"facing\" val=\"west\"/>\n      \
+         <a name=\"output\" val=\"true\"/>\n      \
+         <a name=\"label\" val=\"{}\"/>\n    \
+         </comp>\n",
+        pin_x,
+        root.y,
+        escape(output)
+    ));
+
+    text.push_str("  </circuit>\n</project>\n");
+    text
+}
+
+/// Places every gate as `draw_tree` does, then snaps them to the grid.
+/// Components are numbered like the graph's nodes, so they feed the components their parents became.
+fn place(graph: &GateGraph) -> Vec<Component<'_>> {
+    let positions = graph.positions(graph.adjusted_origin(graph.drawn_image_height() / 2));
+    graph
+        .nodes
+        .iter()
+        .zip(positions)
+        .map(|(node, y_origin)| {
+            let output = y_origin + sprite_info(node.sprite_type()).outputs[0];
+            Component {
+                node,
+                column: node.column as usize - 1,
+                y: snap(output as i32 * SCALE),
+                feeds: node.parent,
+            }
+        })
+        .collect()
+}
+
+/// The tree layout can put two subtrees' gates on top of each other, which Logisim would join up,
+/// so each column keeps its order but is pushed apart until its components clear each other.
+fn spread_columns(components: &mut [Component], columns: usize) {
+    for column in 0..columns {
+        let mut ids: Vec<usize> = (0..components.len())
+            .filter(|&id| components[id].column == column)
+            .collect();
+        ids.sort_by_key(|&id| (components[id].y, id));
+        for pair in ids.windows(2) {
+            let lowest = components[pair[0]].y
+                + half_height(&components[pair[0]])
+                + half_height(&components[pair[1]]);
+            let next = &mut components[pair[1]];
+            next.y = next.y.max(lowest);
+        }
+    }
+}
+
+/// How far above and below its output a component reaches, counting the pins reaching it.
+fn half_height(component: &Component) -> i32 {
+    let widest_pin = (0..component.node.inputs.len())
+        .map(|pin| (input_y(component, pin) - component.y).abs())
+        .max()
+        .unwrap_or(0);
+    (widest_pin + GRID).max(2 * GRID)
+}
+
+/// Orders the lanes in a channel so no wire's end lands on another wire, which Logisim would take as a connection.
+/// That can only happen where one wire leaves at the height another arrives at, and it's avoided
+/// by giving the one leaving the lane further left.
+fn order_lanes(channel: &[usize], components: &[Component]) -> Vec<usize> {
+    let leaves = |id: usize| components[id].y;
+    let arrives = |id: usize| {
+        let (parent, pin) = components[id].feeds.unwrap();
+        input_y(&components[parent], pin)
+    };
+    let mut remaining: Vec<usize> = channel.to_vec();
+    let mut ordered = Vec::new();
+    while !remaining.is_empty() {
+        // take the first wire nothing remaining has to be left of; in a cycle, just the first
+        let next = remaining
+            .iter()
+            .position(|&a| !remaining.iter().any(|&b| b != a && leaves(b) == arrives(a)))
+            .unwrap_or(0);
+        ordered.push(remaining.remove(next));
+    }
+    ordered
+}
+
+fn component_xml(component: &Component, x: i32) -> String {
+    let node = component.node;
+    let location = format!("loc=\"({},{})\"", x, component.y);
+    match node.gate_type {
+        GateType::INPUT => match &node.name[..] {
+            constant @ ("0" | "1") => format!(
+                "    <comp lib=\"0\" {} name=\"Constant\">\n      \
+                 <a name=\"value\" val=\"0x{}\"/>\n    \
+                 </comp>\n",
+                location, constant
+            ),
+            name => format!(
+                "    <comp lib=\"0\" {} name=\"Tunnel\">\n      \
+                 <a name=\"facing\" val=\"east\"/>\n      \
+                 <a name=\"label\" val=\"{}\"/>\n    \
+                 </comp>\n",
+                location,
+                escape(name)
+            ),
+        },
+        GateType::NOT => format!(
+            "    <comp lib=\"1\" {} name=\"NOT Gate\">\n      \
+             <a name=\"size\" val=\"30\"/>\n    \
+             </comp>\n",
+            location
+        ),
+        gate_type => {
+            let inputs = node.inputs.len();
+            // past two inputs Logisim's XOR is true for exactly one unless told otherwise
+            let parity = if inputs > 2 && matches!(gate_type, GateType::XOR | GateType::XNOR) {
+                "      <a name=\"xor\" val=\"odd\"/>\n"
+            } else {
+                ""
+            };
+            format!(
+                "    <comp lib=\"1\" {} name=\"{} Gate\">\n      \
+                 <a name=\"size\" val=\"50\"/>\n      \
+                 <a name=\"inputs\" val=\"{}\"/>\n{}    \
+                 </comp>\n",
+                location, gate_type, inputs, parity
+            )
+        }
+    }
+}
+
+fn wire(from: [i32; 2], to: [i32; 2]) -> String {
+    if from == to {
+        return String::new();
+    }
+    format!(
+        "    <wire from=\"({},{})\" to=\"({},{})\"/>\n",
+        from[0], from[1], to[0], to[1]
+    )
+}
+
+/// How far left of its output an exported component's inputs are: the medium gates, and a wide NOT.
+/// Tunnels and constants have no inputs, so they're given the length of a NOT to stay clear of the channel.
+fn axis_length(gate_type: GateType) -> i32 {
+    match gate_type {
+        GateType::INPUT | GateType::NOT => 30,
+        gate_type => gate_length(gate_type, 50),
+    }
+}
+
+/// How far a Logisim gate of `size` reaches back from its output to its inputs:
+/// its size, 10 more for XOR's extra curve and 10 more for an inverting bubble.
+fn gate_length(gate_type: GateType, size: i32) -> i32 {
+    match gate_type {
+        GateType::NAND | GateType::NOR | GateType::XOR => size + 10,
+        GateType::XNOR => size + 20,
+        _ => size,
+    }
+}
+
+fn input_y(component: &Component, pin: usize) -> i32 {
+    component.y + input_offset(component.node.inputs.len() as i32, 50, pin as i32)
+}
+
+/// How far below the output input `pin` of a Logisim gate facing east is, following Logisim's own spacing:
+/// 20 apart for up to three inputs on a medium gate and 10 apart past that, with a gap in the middle of an even count.
+fn input_offset(inputs: i32, size: i32, pin: i32) -> i32 {
+    let (start, step, lower_even) = if inputs <= 3 {
+        if size < 40 {
+            (-5, 10, 10)
+        } else if size < 60 || inputs <= 2 {
+            (-10, 20, 20)
+        } else {
+            (-15, 15, 30)
+        }
+    } else if inputs == 4 && size >= 60 {
+        (-5, 20, 0)
+    } else {
+        (-5, 10, 10)
+    };
+    if inputs % 2 == 1 {
+        start * (inputs - 1) + pin * step
+    } else {
+        start * inputs + pin * step + if pin >= inputs / 2 { lower_even } else { 0 }
+    }
+}
+
+fn snap(value: i32) -> i32 {
+    (value + GRID / 2).div_euclid(GRID) * GRID
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+type Point = (i32, i32);
+
+/// One XML tag, opening or closing an element or both at once.
+struct Tag {
+    name: String,
+    attributes: HashMap<String, String>,
+    opens: bool,
+    closes: bool,
+}
+
+/// The tags of an XML document in order, skipping the declaration and comments.
+/// Logisim's files need nothing more than this to read.
+fn tags(text: &str) -> Result<Vec<Tag>, String> {
+    let mut tags = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let (skip_to, skip) = if rest.starts_with("<?") {
+            ("?>", true)
+        } else if rest.starts_with("<!--") {
+            ("-->", true)
+        } else {
+            (">", false)
+        };
+        let end = rest
+            .find(skip_to)
+            .ok_or_else(|| String::from("the file ends inside a tag"))?;
+        let inside = &rest[1..end];
+        rest = &rest[end + skip_to.len()..];
+        if skip {
+            continue;
+        }
+
+        let closes_only = inside.starts_with('/');
+        let inside = inside.trim_start_matches('/');
+        let self_closing = inside.ends_with('/');
+        let inside = inside.trim_end_matches('/');
+        let name_end = inside.find(char::is_whitespace).unwrap_or(inside.len());
+        let mut attributes = HashMap::new();
+        let mut attribute_text = &inside[name_end..];
+        while let Some(equals) = attribute_text.find('=') {
+            let key = attribute_text[..equals].trim();
+            let after = attribute_text[equals + 1..].trim_start();
+            let quote = after
+                .chars()
+                .next()
+                .filter(|&q| q == '"' || q == '\'')
+                .ok_or_else(|| format!("expected a quoted value for {}", key))?;
+            let value_end = after[1..]
+                .find(quote)
+                .ok_or_else(|| format!("the value of {} is never closed", key))?;
+            attributes.insert(String::from(key), unescape(&after[1..1 + value_end]));
+            attribute_text = &after[value_end + 2..];
+        }
+        tags.push(Tag {
+            name: String::from(&inside[..name_end]),
+            attributes,
+            opens: !closes_only,
+            closes: closes_only || self_closing,
+        });
+    }
+    Ok(tags)
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// What a Logisim component contributes to the netlist, at the points it connects to wires.
+enum Part {
+    Input(String, Point),
+    Output(String, Point),
+    Constant(bool, Point),
+    Tunnel(String, Point),
+    /// A gate, or a buffer without a `gate_type`, with each input's point and whether it's negated.
+    Gate {
+        gate_type: Option<GateType>,
+        output: Point,
+        inputs: Vec<(Point, bool)>,
+    },
+}
+
+/// Reads the main circuit of a Logisim `.circ` file, which has to be built from single-bit pins,
+/// constants, tunnels and basic gates, into a circuit with a signal per gate.
+pub fn parse_circ(text: &str) -> Result<Circuit, String> {
+    let tags = tags(text)?;
+    let main = tags
+        .iter()
+        .find(|t| t.name == "main")
+        .and_then(|t| t.attributes.get("name").cloned());
+
+    // the components and wires of the main circuit, or of the first if none is marked
+    let mut chosen: Option<String> = None;
+    let mut in_circuit = false;
+    let mut in_component = false;
+    let mut components: Vec<(HashMap<String, String>, HashMap<String, String>)> = Vec::new();
+    let mut wires: Vec<(Point, Point)> = Vec::new();
+    for tag in &tags {
+        match &tag.name[..] {
+            "circuit" if tag.opens => {
+                let name = tag.attributes.get("name").cloned().unwrap_or_default();
+                if chosen.is_none() && main.as_ref().is_none_or(|m| *m == name) {
+                    chosen = Some(name);
+                    in_circuit = true;
+                }
+            }
+            "circuit" => in_circuit = false,
+            _ if !in_circuit => {}
+            "comp" => {
+                if tag.opens {
+                    components.push((tag.attributes.clone(), HashMap::new()));
+                }
+                in_component = tag.opens && !tag.closes;
+            }
+            "a" if in_component => {
+                if let (Some(name), Some(value)) =
+                    (tag.attributes.get("name"), tag.attributes.get("val"))
+                {
+                    let attributes = &mut components.last_mut().unwrap().1;
+                    attributes.insert(name.clone(), value.clone());
+                }
+            }
+            "wire" => {
+                let end = |key: &str| {
+                    tag.attributes
+                        .get(key)
+                        .ok_or_else(|| format!("a wire has no {}", key))
+                        .and_then(|text| point(text))
+                };
+                wires.push((end("from")?, end("to")?));
+            }
+            _ => {}
+        }
+    }
+    if chosen.is_none() {
+        return Err(String::from("the file has no circuit"));
+    }
+
+    let mut parts = Vec::new();
+    let (mut unnamed_inputs, mut unnamed_outputs) = (0, 0);
+    for (component, attributes) in &components {
+        let name = component.get("name").map_or("", |n| &n[..]);
+        let location = point(component.get("loc").map_or("", |l| &l[..]))?;
+        let attribute = |key: &str| attributes.get(key).map(|v| &v[..]);
+        if attribute("width").is_some_and(|w| w != "1") {
+            return Err(format!(
+                "the {} at {:?} is more than one bit wide",
+                name, location
+            ));
+        }
+        let label = attribute("label")
+            .filter(|l| !l.is_empty())
+            .map(String::from);
+        let part = match name {
+            "Pin" if attribute("output") == Some("true") || attribute("type") == Some("output") => {
+                let label = label.unwrap_or_else(|| {
+                    unnamed_outputs += 1;
+                    format!("out{}", unnamed_outputs)
+                });
+                Part::Output(label, location)
+            }
+            "Pin" => {
+                let label = label.unwrap_or_else(|| {
+                    unnamed_inputs += 1;
+                    format!("in{}", unnamed_inputs)
+                });
+                Part::Input(label, location)
+            }
+            "Constant" => {
+                let value = attribute("value").unwrap_or("0x1");
+                let value = i64::from_str_radix(value.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("{} isn't a constant", value))?;
+                Part::Constant(value != 0, location)
+            }
+            "Tunnel" => Part::Tunnel(label.unwrap_or_default(), location),
+            "Text" | "Probe" => continue,
+            gate_name => {
+                let gate_type = match gate_name {
+                    "AND Gate" => Some(GateType::AND),
+                    "OR Gate" => Some(GateType::OR),
+                    "NAND Gate" => Some(GateType::NAND),
+                    "NOR Gate" => Some(GateType::NOR),
+                    "XOR Gate" => Some(GateType::XOR),
+                    "XNOR Gate" => Some(GateType::XNOR),
+                    "NOT Gate" => Some(GateType::NOT),
+                    "Buffer" => None,
+                    other => return Err(format!("can't read the {} at {:?}", other, location)),
+                };
+                let single_input = matches!(gate_type, None | Some(GateType::NOT));
+                let inputs: i32 = if single_input {
+                    1
+                } else {
+                    attribute("inputs").map_or(Ok(2), str::parse).map_err(|_| {
+                        format!("the {} at {:?} has a bad input count", gate_name, location)
+                    })?
+                };
+                if inputs > 2
+                    && matches!(gate_type, Some(GateType::XOR) | Some(GateType::XNOR))
+                    && attribute("xor") != Some("odd")
+                {
+                    return Err(format!(
+                        "the {} at {:?} is true for exactly one input, which only parity gates can be read as",
+                        gate_name, location
+                    ));
+                }
+                let size = match attribute("size") {
+                    Some("narrow") => 30,
+                    Some("medium") => 50,
+                    Some("wide") => 70,
+                    Some(size) => size.parse().unwrap_or(50),
+                    None if single_input => {
+                        if gate_type.is_none() {
+                            20
+                        } else {
+                            30
+                        }
+                    }
+                    None => 50,
+                };
+                let length = match gate_type {
+                    Some(gate_type) if !single_input => gate_length(gate_type, size),
+                    _ => size,
+                };
+                let facing = attribute("facing").unwrap_or("east");
+                let inputs = (0..inputs)
+                    .map(|pin| {
+                        let along = if single_input {
+                            0
+                        } else {
+                            input_offset(inputs, size, pin)
+                        };
+                        let (dx, dy) = match facing {
+                            "west" => (length, along),
+                            "north" => (along, length),
+                            "south" => (along, -length),
+                            _ => (-length, along),
+                        };
+                        let negated = attribute(&format!("negate{}", pin)) == Some("true");
+                        ((location.0 + dx, location.1 + dy), negated)
+                    })
+                    .collect();
+                Part::Gate {
+                    gate_type,
+                    output: location,
+                    inputs,
+                }
+            }
+        };
+        parts.push(part);
+    }
+
+    netlist(&parts, &wires)
+}
+
+/// Joins up the parts' points through the wires and tunnels into nets and reads the gates off them.
+fn netlist(parts: &[Part], wires: &[(Point, Point)]) -> Result<Circuit, String> {
+    let mut nets = Nets::default();
+    let mut points: Vec<Point> = wires.iter().flat_map(|&(a, b)| vec![a, b]).collect();
+    for part in parts {
+        match part {
+            Part::Input(_, p) | Part::Output(_, p) | Part::Constant(_, p) | Part::Tunnel(_, p) => {
+                points.push(*p)
+            }
+            Part::Gate { output, inputs, .. } => {
+                points.push(*output);
+                points.extend(inputs.iter().map(|(p, _)| *p));
+            }
+        }
+    }
+    for &(a, b) in wires {
+        if a.0 != b.0 && a.1 != b.1 {
+            return Err(format!("the wire from {:?} to {:?} isn't straight", a, b));
+        }
+        nets.join(a, b);
+        // anything ending partway along a wire connects to it
+        for &p in &points {
+            let between = |p: i32, a: i32, b: i32| a.min(b) < p && p < a.max(b);
+            if (a.0 == p.0 && b.0 == p.0 && between(p.1, a.1, b.1))
+                || (a.1 == p.1 && b.1 == p.1 && between(p.0, a.0, b.0))
+            {
+                nets.join(p, a);
+            }
+        }
+    }
+    let mut tunnels: HashMap<&str, Point> = HashMap::new();
+    for part in parts {
+        if let Part::Tunnel(label, p) = part {
+            match tunnels.get(&label[..]) {
+                Some(&first) => nets.join(*p, first),
+                None => {
+                    tunnels.insert(label, *p);
+                }
+            }
+        }
+    }
+
+    // name each net after what drives it, and make sure only one thing does
+    let mut names: HashMap<Point, String> = HashMap::new();
+    for part in parts {
+        let (net, name) = match part {
+            Part::Input(label, p) => (nets.find(*p), label.clone()),
+            Part::Constant(value, p) => {
+                (nets.find(*p), String::from(if *value { "1" } else { "0" }))
+            }
+            Part::Gate { output, .. } => (nets.find(*output), String::new()),
+            _ => continue,
+        };
+        if names.insert(net, name).is_some() {
+            return Err(format!(
+                "more than one component drives the wire at {:?}",
+                net
+            ));
+        }
+    }
+    // gates' nets are named after an output or tunnel on them if there is one, and numbered otherwise
+    let mut taken: Vec<String> = names.values().cloned().collect();
+    for part in parts {
+        if let Part::Output(label, p) | Part::Tunnel(label, p) = part {
+            let net = nets.find(*p);
+            if let Some(name) = names.get_mut(&net).filter(|n| n.is_empty()) {
+                *name = label.clone();
+                taken.push(label.clone());
+            }
+        }
+    }
+    let mut numbered = 0;
+    for name in names.values_mut().filter(|n| n.is_empty()) {
+        loop {
+            numbered += 1;
+            let candidate = format!("n{}", numbered);
+            if !taken.contains(&candidate) {
+                *name = candidate;
+                break;
+            }
+        }
+    }
+
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    let mut gates = Vec::new();
+    for part in parts {
+        match part {
+            Part::Input(label, _) => inputs.push(label.clone()),
+            Part::Output(label, p) => {
+                let net = names
+                    .get(&nets.find(*p))
+                    .ok_or_else(|| format!("output {} isn't connected", label))?;
+                if net != label {
+                    gates.push(NetlistGate {
+                        output: label.clone(),
+                        gate_type: None,
+                        operands: vec![Gate::input(net)],
+                    });
+                }
+                outputs.push(label.clone());
+            }
+            Part::Gate {
+                gate_type,
+                output,
+                inputs: pins,
+            } => {
+                // Logisim leaves unconnected inputs out. The renderer draws the last operand on top,
+                // so the pins are read bottom up to keep the gate's inputs in the order they're drawn
+                let operands: Vec<Gate> = pins
+                    .iter()
+                    .rev()
+                    .filter_map(|(p, negated)| {
+                        let operand = Gate::input(names.get(&nets.find(*p))?);
+                        Some(if *negated {
+                            Gate::new(GateType::NOT, vec![operand])
+                        } else {
+                            operand
+                        })
+                    })
+                    .collect();
+                if operands.is_empty() {
+                    return Err(format!("the gate at {:?} has no inputs connected", output));
+                }
+                gates.push(NetlistGate {
+                    output: names[&nets.find(*output)].clone(),
+                    gate_type: *gate_type,
+                    operands,
+                });
+            }
+            _ => {}
+        }
+    }
+    circuit::from_netlist(&inputs, outputs, gates)
+}
+
+/// Points joined into nets, union-find style.
+#[derive(Default)]
+struct Nets {
+    parent: HashMap<Point, Point>,
+}
+
+impl Nets {
+    fn find(&mut self, p: Point) -> Point {
+        let parent = *self.parent.entry(p).or_insert(p);
+        if parent == p {
+            return p;
+        }
+        let root = self.find(parent);
+        self.parent.insert(p, root);
+        root
+    }
+
+    fn join(&mut self, a: Point, b: Point) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent.insert(a, b);
+        }
+    }
+}
+
+fn point(text: &str) -> Result<Point, String> {
+    let coordinates: Vec<Result<i32, _>> = text
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(|c| c.trim().parse())
+        .collect();
+    match coordinates.as_slice() {
+        [Ok(x), Ok(y)] => Ok((*x, *y)),
+        _ => Err(format!("{} isn't a point", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equivalence::assert_round_trips;
+
+    #[test]
+    fn projects_read_back_as_the_same_function() {
+        // input pins labelled like the ones Logisim leaves unlabelled and the output
+        let (in1, out1) = (Gate::input("in1"), Gate::input("out1"));
+        let collision = Gate::new(
+            GateType::AND,
+            vec![in1.clone(), Gate::new(GateType::NOT, vec![out1.clone()])],
+        );
+        let (a, b, c) = (Gate::input("a"), Gate::input("b"), Gate::input("c"));
+        let parity = Gate::new(GateType::XOR, vec![a.clone(), b.clone(), c.clone()]);
+        let inverted = Gate::new(GateType::XNOR, vec![a, b, c, Gate::input("1")]);
+        assert_round_trips(
+            &|tree| circ(tree, "m", "f"),
+            parse_circ,
+            &[collision, parity, inverted],
+        );
+    }
+
+    #[test]
+    fn wide_xor_gates_have_to_be_parity_gates() {
+        let (a, b, c) = (Gate::input("a"), Gate::input("b"), Gate::input("c"));
+        let text = circ(&Gate::new(GateType::XOR, vec![a, b, c]), "m", "f");
+        assert!(text.contains("<a name=\"xor\" val=\"odd\"/>"), "{}", text);
+        let one_hot = text.replace("<a name=\"xor\" val=\"odd\"/>", "");
+        assert!(parse_circ(&one_hot).is_err());
+    }
+}
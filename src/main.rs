@@ -1,48 +1,509 @@
 extern crate image;
+mod aig;
+mod aiger;
+mod animation;
+mod bdd;
+mod bdd_renderer;
+mod blif;
+mod canonical;
+mod circuit;
+mod equivalence;
 mod expression_parser;
+mod gate_graph;
+mod graph_export;
+mod kmap_renderer;
+mod logisim;
+mod minimizer;
+mod netlist;
+mod preview;
 mod renderer;
+mod repl;
+mod specification;
 mod spritesheet;
-use std::{
-    io::{stdin},
-    time::SystemTime,
-};
+mod stats;
+mod svg;
+mod text_renderer;
+mod timing;
+mod typesetting;
+mod verilog;
+mod vhdl;
+use std::{env, fs, io::stdin, path::Path, process, time::SystemTime};
 
+use bdd::Bdd;
+use bdd_renderer::BddRenderer;
+use canonical::CanonicalForm;
+use circuit::{Circuit, CircuitLayout};
 use expression_parser::parse_boolean_expression;
-use renderer::Renderer;
+use gate_graph::GateGraph;
+use kmap_renderer::KarnaughRenderer;
+use preview::Protocol;
+use renderer::{RenderOptions, Renderer};
+use specification::Specification;
+use stats::Stats;
+use text_renderer::TextRenderer;
+use timing::DelayTable;
 
-fn main() {
+/// The argument following `flag`, if `flag` was passed.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|a| a == flag)
+        .map(|position| {
+            args.get(position + 1)
+                .unwrap_or_else(|| panic!("{} needs a value after it", flag))
+        })
+}
 
-    println!("Awaiting boolean function from stdin");
+fn synthesize(specification: Result<Specification, String>) -> expression_parser::Gate {
+    let specification = specification.unwrap_or_else(|error| {
+        eprintln!("Couldn't read the truth table: {}", error);
+        process::exit(1);
+    });
+    let tree = specification.synthesize();
+    eprintln!(
+        "Synthesized {}({}) = {}",
+        specification.name,
+        specification.variables.join(","),
+        tree.get_name()
+    );
+    tree
+}
 
-    let mut buffer = String::new();
-    stdin().read_line(&mut buffer).unwrap();
+/// The diagram options given on the command line, with `--label` defaulting to `default_label`.
+/// An `--assign` has to give every one of `inputs` a value.
+fn render_options(args: &[String], default_label: String, inputs: &[String]) -> RenderOptions {
+    let options = RenderOptions {
+        output_label: flag_value(args, "--label").cloned().unwrap_or(default_label),
+        title: flag_value(args, "--title").cloned(),
+        wire_labels: args.iter().any(|a| a == "--wire-labels"),
+        overbars: args.iter().any(|a| a == "--overbars"),
+        assignment: flag_value(args, "--assign").map(|text| {
+            expression_parser::parse_assignment(text).unwrap_or_else(|error| {
+                eprintln!("Couldn't read the assignment: {}", error);
+                process::exit(1);
+            })
+        }),
+        critical_path: None,
+    };
+    if let Some(assignment) = &options.assignment {
+        let missing: Vec<&str> = inputs
+            .iter()
+            .filter(|name| !assignment.contains_key(*name))
+            .map(|name| &name[..])
+            .collect();
+        if !missing.is_empty() {
+            eprintln!("The assignment leaves out {}", missing.join(","));
+            process::exit(1);
+        }
+    }
+    options
+}
 
-    println!("{:}", buffer);
+/// The gate delays to analyse timing with, if `--timing`, `--delays` or `--critical-path` asks for it.
+fn delay_table(args: &[String]) -> Option<DelayTable> {
+    if let Some(text) = flag_value(args, "--delays") {
+        return Some(DelayTable::parse(text).unwrap_or_else(|error| {
+            eprintln!("Couldn't read the delays: {}", error);
+            process::exit(1);
+        }));
+    }
+    if args
+        .iter()
+        .any(|a| a == "--timing" || a == "--critical-path")
+    {
+        Some(DelayTable::default())
+    } else {
+        None
+    }
+}
 
-    let start_time = SystemTime::now();
-    let tree = parse_boolean_expression(&buffer);
+/// Prints when `tree`'s output settles and the path that holds it up, returning that path.
+fn report_timing(name: &str, tree: &expression_parser::Gate, delays: &DelayTable) -> Vec<usize> {
+    let path = timing::critical_path(tree, delays);
     println!(
-        "Parsed boolean expression in {:?}",
+        "{} settles at {}, critical path {}",
+        name,
+        timing::arrival_time(tree, delays),
+        timing::describe_path(tree, &path, delays)
+    );
+    path
+}
+
+/// The output port of an exported module: `--label` made an identifier, or `f`, renamed if an input has that name.
+fn output_port_name(args: &[String], inputs: &[String]) -> String {
+    let mut name = flag_value(args, "--label")
+        .map(|label| verilog::identifier(label))
+        .unwrap_or_else(|| String::from("f"));
+    while inputs.contains(&name) {
+        name.push_str("_out");
+    }
+    name
+}
+
+/// The module an export to `path` declares, named after the file.
+fn module_name(path: &str) -> String {
+    verilog::identifier(
+        &Path::new(path)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy(),
+    )
+}
+
+/// Writes `text`, an export of the circuit described as `kind`, to `path`.
+fn write_export(path: &str, kind: &str, text: String) {
+    fs::write(path, text).unwrap_or_else(|error| {
+        eprintln!("Couldn't write {}: {}", path, error);
+        process::exit(1);
+    });
+    eprintln!("Wrote {} to {}", kind, path);
+}
+
+/// With `--animate path`, draws one frame per input combination with `draw` and saves them to `path`.
+fn animate<F>(args: &[String], variables: &[String], options: &RenderOptions, mut draw: F)
+where
+    F: FnMut(RenderOptions) -> image::RgbaImage,
+{
+    let path = match flag_value(args, "--animate") {
+        Some(path) => path,
+        None => return,
+    };
+
+    let start_time = SystemTime::now();
+    let title = options.title.as_deref();
+    let frames = animation::truth_table_frames(variables, title, |assignment, caption| {
+        let mut frame_options = options.clone();
+        frame_options.assignment = Some(assignment);
+        frame_options.title = Some(caption);
+        draw(frame_options)
+    });
+    let frame_count = frames.as_ref().map_or(0, |f| f.len());
+    if let Err(error) = frames.and_then(|frames| animation::save_frames(frames, path)) {
+        eprintln!("Couldn't animate: {}", error);
+        process::exit(1);
+    }
+    eprintln!(
+        "Animated {} frames to {} in {:?}",
+        frame_count,
+        path,
+        SystemTime::now().duration_since(start_time).unwrap()
+    );
+}
+
+/// With `--preview kitty|iterm|sixel`, also draws `image` inline in the terminal.
+fn preview(args: &[String], image: &image::RgbaImage) {
+    if let Some(name) = flag_value(args, "--preview") {
+        let protocol =
+            Protocol::from_name(name).expect("--preview takes one of kitty, iterm or sixel");
+        print!("{}", preview::escape(image, protocol));
+    }
+}
+
+/// Draws every output of a statement list into one diagram, sharing the signals they have in common.
+fn render_circuit(circuit: Result<Circuit, String>, args: &[String]) {
+    let circuit = circuit.unwrap_or_else(|error| {
+        eprintln!("Couldn't read the circuit: {}", error);
+        process::exit(1);
+    });
+    for (name, gate) in &circuit.signals {
+        println!("{} = {}", name, gate.get_name());
+    }
+    let layout = CircuitLayout::new(&circuit);
+    let mut critical_wires = Vec::new();
+    if let Some(delays) = delay_table(args) {
+        let signal_times =
+            timing::signal_arrival_times(&circuit, &delays).unwrap_or_else(|error| {
+                eprintln!("Couldn't time the circuit: {}", error);
+                process::exit(1);
+            });
+        for output in &circuit.outputs {
+            println!(
+                "{} settles at {}, critical path {}",
+                output,
+                signal_times[output],
+                timing::describe_signal_path(&circuit, output, &signal_times, &delays)
+            );
+        }
+        if args.iter().any(|a| a == "--critical-path") {
+            critical_wires = timing::layout_critical_wires(&layout, &delays);
+        }
+    }
+
+    let start_time = SystemTime::now();
+    let options = render_options(args, String::new(), &circuit.input_names());
+    animate(args, &circuit.input_names(), &options, |frame_options| {
+        let mut renderer = Renderer::for_circuit(&layout, frame_options);
+        renderer.draw_circuit(&layout, &critical_wires);
+        renderer.into_image()
+    });
+    let mut renderer = Renderer::for_circuit(&layout, options);
+    renderer.draw_circuit(&layout, &critical_wires);
+    renderer.export();
+    preview(args, renderer.image());
+    eprintln!(
+        "Rendered {} outputs over {} in {:?}",
+        circuit.outputs.len(),
+        circuit.input_names().join(","),
         SystemTime::now().duration_since(start_time).unwrap()
     );
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if args.iter().any(|a| a == "--repl") {
+        repl::run(&args);
+        return;
+    }
+
+    if let Some(path) = flag_value(&args, "--circuit") {
+        let contents = fs::read_to_string(path).unwrap_or_else(|error| {
+            eprintln!("Couldn't read {}: {}", path, error);
+            process::exit(1);
+        });
+        // netlists from other tools are told apart by their extension
+        let circuit = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("v") => verilog::parse_netlist(&contents),
+            Some("circ") => logisim::parse_circ(&contents),
+            Some("aag") => aiger::parse_aag(&contents),
+            Some("blif") => blif::parse_blif(&contents),
+            _ => circuit::parse_statements(&contents),
+        };
+        render_circuit(circuit, &args);
+        return;
+    }
+
     let start_time = SystemTime::now();
+    let mut tree = if let Some(path) = flag_value(&args, "--csv") {
+        let contents = fs::read_to_string(path).unwrap_or_else(|error| {
+            eprintln!("Couldn't read {}: {}", path, error);
+            process::exit(1);
+        });
+        synthesize(specification::parse_truth_table_csv(&contents))
+    } else {
+        eprintln!("Awaiting boolean function from stdin");
 
+        let mut buffer = String::new();
+        stdin().read_line(&mut buffer).unwrap();
 
-    let mut renderer = Renderer::new(&tree);
-    println!(
+        eprintln!("{:}", buffer);
+
+        if specification::is_minterm_list(&buffer) {
+            synthesize(specification::parse_minterm_list(&buffer))
+        } else if circuit::is_statement_list(&buffer) {
+            render_circuit(circuit::parse_statements(&buffer), &args);
+            return;
+        } else {
+            parse_boolean_expression(&buffer)
+        }
+    };
+    eprintln!(
+        "Parsed boolean expression in {:?}",
+        SystemTime::now().duration_since(start_time).unwrap()
+    );
+
+    if let Some(expression) = flag_value(&args, "--equiv") {
+        let start_time = SystemTime::now();
+        // the other expression is only ever compared, so it goes straight into the and-inverter graph
+        match equivalence::find_expression_counterexample(&tree, expression) {
+            None => println!("{} and {} are equivalent", tree.get_name(), expression.trim()),
+            Some(counterexample) => println!(
+                "{} and {} are not equivalent: {}",
+                tree.get_name(),
+                expression.trim(),
+                counterexample.describe()
+            ),
+        }
+        eprintln!(
+            "Checked equivalence in {:?}",
+            SystemTime::now().duration_since(start_time).unwrap()
+        );
+        return;
+    }
+
+    if args.iter().any(|a| a == "--sat") {
+        let (bdd, root) = Bdd::from_gate(&tree, &tree.input_names());
+        match bdd.satisfying_assignment(root) {
+            None => println!("{} is unsatisfiable", tree.get_name()),
+            Some(assignment) => {
+                let values: Vec<String> = assignment
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, *value as u8))
+                    .collect();
+                println!(
+                    "{} is satisfied by {} ({} of {} assignments)",
+                    tree.get_name(),
+                    values.join(","),
+                    bdd.satisfying_count(root),
+                    1_u128 << bdd.order().len()
+                );
+            }
+        }
+    }
+
+    if args.iter().any(|a| a == "--bdd" || a == "--bdd-sift") {
+        let start_time = SystemTime::now();
+        let order: Vec<String> = match flag_value(&args, "--bdd-order") {
+            Some(order) => order.split(',').map(String::from).collect(),
+            None => tree.input_names(),
+        };
+
+        let (mut bdd, mut root) = Bdd::from_gate(&tree, &order);
+        if args.iter().any(|a| a == "--bdd-sift") {
+            let before = bdd.node_count(root);
+            let (sifted, sifted_root) = bdd.sift(root);
+            bdd = sifted;
+            root = sifted_root;
+            eprintln!(
+                "Sifting took the BDD from {} to {} nodes with order {}",
+                before,
+                bdd.node_count(root),
+                bdd.order().join(",")
+            );
+        }
+
+        let mut bdd_renderer = BddRenderer::new(&bdd, root);
+        bdd_renderer.draw(&bdd, root);
+        bdd_renderer.export("bdd.png");
+        eprintln!(
+            "Rendered BDD of {} nodes in {:?}",
+            bdd.node_count(root),
+            SystemTime::now().duration_since(start_time).unwrap()
+        );
+    }
+
+    if args.iter().any(|a| a == "--kmap" || a == "--kmap-groups") {
+        let start_time = SystemTime::now();
+        let groups = if args.iter().any(|a| a == "--kmap-groups") {
+            Some(minimizer::minimized_cover(&tree))
+        } else {
+            None
+        };
+
+        let mut kmap_renderer = KarnaughRenderer::new(&tree).unwrap_or_else(|error| {
+            eprintln!("Couldn't draw the Karnaugh map: {}", error);
+            process::exit(1);
+        });
+        kmap_renderer.draw(&tree, groups.as_ref());
+        kmap_renderer.export("kmap.png");
+        eprintln!(
+            "Rendered Karnaugh map in {:?}",
+            SystemTime::now().duration_since(start_time).unwrap()
+        );
+    }
+
+    if args.iter().any(|a| a == "--minimize") {
+        let start_time = SystemTime::now();
+        tree = minimizer::minimize(&tree);
+        eprintln!(
+            "Minimized to {} in {:?}",
+            tree.get_name(),
+            SystemTime::now().duration_since(start_time).unwrap()
+        );
+    }
+
+    if args.iter().any(|a| a == "--stats") {
+        println!("{}", Stats::of(&tree).describe());
+    }
+    if let Some(path) = flag_value(&args, "--stats-json") {
+        fs::write(path, Stats::of(&tree).to_json()).unwrap_or_else(|error| {
+            eprintln!("Couldn't write {}: {}", path, error);
+            process::exit(1);
+        });
+    }
+
+    let output_port = output_port_name(&args, &tree.input_names());
+    if let Some(path) = flag_value(&args, "--verilog") {
+        let module = verilog::structural(&tree, &module_name(path), &output_port);
+        write_export(path, "structural Verilog", module);
+    }
+    if let Some(path) = flag_value(&args, "--verilog-assign") {
+        let module = verilog::behavioural(&tree, &module_name(path), &output_port);
+        write_export(path, "behavioural Verilog", module);
+    }
+    if let Some(path) = flag_value(&args, "--vhdl") {
+        let entity = vhdl::structural(&tree, &module_name(path), &output_port);
+        write_export(path, "structural VHDL", entity);
+    }
+    if let Some(path) = flag_value(&args, "--vhdl-dataflow") {
+        let entity = vhdl::dataflow(&tree, &module_name(path), &output_port);
+        write_export(path, "dataflow VHDL", entity);
+    }
+    if let Some(path) = flag_value(&args, "--dot") {
+        write_export(path, "Graphviz graph", graph_export::dot(&tree));
+    }
+    if let Some(path) = flag_value(&args, "--mermaid") {
+        write_export(path, "Mermaid flowchart", graph_export::mermaid(&tree));
+    }
+    if let Some(path) = flag_value(&args, "--logisim") {
+        let project = logisim::circ(&tree, &module_name(path), &output_port);
+        write_export(path, "Logisim circuit", project);
+    }
+    if let Some(path) = flag_value(&args, "--blif") {
+        let model = blif::blif(&tree, &module_name(path), &output_port);
+        write_export(path, "BLIF model", model);
+    }
+    if let Some(path) = flag_value(&args, "--aiger") {
+        write_export(path, "AIGER graph", aiger::aag(&tree, &output_port));
+    }
+
+    if let Some(name) = flag_value(&args, "--form") {
+        let form =
+            CanonicalForm::from_name(name).expect("--form takes one of sop, pos, nand, nor or aig");
+
+        let start_time = SystemTime::now();
+        let circuit = canonical::convert(&tree, form, &output_port).unwrap_or_else(|error| {
+            eprintln!("Couldn't convert {}: {}", tree.get_name(), error);
+            process::exit(1);
+        });
+        eprintln!(
+            "Converted to {:?} in {:?}",
+            form,
+            SystemTime::now().duration_since(start_time).unwrap()
+        );
+        // shared gates only stay shared drawn as a circuit
+        render_circuit(Ok(circuit), &args);
+        return;
+    }
+
+    let start_time = SystemTime::now();
+
+    let mut options = render_options(&args, tree.get_name(), &tree.input_names());
+    if let Some(delays) = delay_table(&args) {
+        let path = report_timing(&tree.get_name(), &tree, &delays);
+        if args.iter().any(|a| a == "--critical-path") {
+            options.critical_path = Some(path);
+        }
+    }
+    let graph = GateGraph::new(&tree);
+    if args.iter().any(|a| a == "--text") {
+        print!("{}", TextRenderer::new(&graph, &options.output_label));
+        return;
+    }
+    animate(&args, &tree.input_names(), &options, |frame_options| {
+        let mut renderer = Renderer::new(&graph, frame_options);
+        renderer.draw_tree(&graph);
+        renderer.into_image()
+    });
+    let mut renderer = Renderer::new(&graph, options);
+    eprintln!(
         "Instanced renderer in {:?}",
         SystemTime::now().duration_since(start_time).unwrap()
     );
     let start_time = SystemTime::now();
-    
-    renderer.draw_tree(&tree);
+
+    renderer.draw_tree(&graph);
     renderer.export();
+    preview(&args, renderer.image());
 
-    println!(
+    eprintln!(
         "Rendered diagram in {:?}",
         SystemTime::now().duration_since(start_time).unwrap()
     );
 
     let start_time = SystemTime::now();
-    println!("After all that, columns are {:#?}. Took {:?}", tree.column_sizes(), SystemTime::now().duration_since(start_time).unwrap());
+    eprintln!(
+        "After all that, columns are {:#?}. Took {:?}",
+        graph.column_sizes(),
+        SystemTime::now().duration_since(start_time).unwrap()
+    );
 }
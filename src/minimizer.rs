@@ -0,0 +1,596 @@
+use std::collections::HashSet;
+
+use crate::expression_parser::{Gate, GateType};
+
+/// A product term over at most 64 variables.
+/// Bit `i` of `mask` is set when variable `i` appears in the term,
+/// and the same bit of `bits` gives its polarity (1 for `x`, 0 for `x'`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cube {
+    pub mask: u64,
+    pub bits: u64,
+}
+
+impl Cube {
+    pub fn universe() -> Cube {
+        Cube { mask: 0, bits: 0 }
+    }
+
+    pub fn literal(variable: usize, positive: bool) -> Cube {
+        Cube {
+            mask: 1 << variable,
+            bits: (positive as u64) << variable,
+        }
+    }
+
+    /// The single row `minterm` of a truth table over `variable_count` variables,
+    /// numbered the same way as `contains_minterm`.
+    pub fn minterm(minterm: u64, variable_count: usize) -> Cube {
+        let mut cube = Cube::universe();
+        for i in 0..variable_count {
+            cube = cube.with_literal(i, (minterm >> (variable_count - 1 - i)) & 1 == 1);
+        }
+        cube
+    }
+
+    pub fn literal_count(&self) -> u32 {
+        self.mask.count_ones()
+    }
+
+    pub fn intersects(&self, other: &Cube) -> bool {
+        (self.bits ^ other.bits) & self.mask & other.mask == 0
+    }
+
+    pub fn intersection(&self, other: &Cube) -> Option<Cube> {
+        if !self.intersects(other) {
+            return None;
+        }
+        Some(Cube {
+            mask: self.mask | other.mask,
+            bits: self.bits | other.bits,
+        })
+    }
+
+    /// True when every minterm of `other` is also a minterm of `self`.
+    pub fn contains(&self, other: &Cube) -> bool {
+        self.mask & other.mask == self.mask && (self.bits ^ other.bits) & self.mask == 0
+    }
+
+    /// Whether truth table row `minterm` lies in the cube, numbering rows
+    /// with the first variable as the most significant bit like `minterm_assignment`.
+    pub fn contains_minterm(&self, minterm: u64, variable_count: usize) -> bool {
+        (0..variable_count).all(|i| {
+            !self.has_variable(i)
+                || ((minterm >> (variable_count - 1 - i)) & 1 == 1) == self.polarity(i)
+        })
+    }
+
+    pub fn has_variable(&self, variable: usize) -> bool {
+        self.mask & (1 << variable) != 0
+    }
+
+    pub fn polarity(&self, variable: usize) -> bool {
+        self.bits & (1 << variable) != 0
+    }
+
+    fn without_variable(&self, variable: usize) -> Cube {
+        Cube {
+            mask: self.mask & !(1 << variable),
+            bits: self.bits & !(1 << variable),
+        }
+    }
+
+    fn with_literal(&self, variable: usize, positive: bool) -> Cube {
+        self.without_variable(variable)
+            .intersection(&Cube::literal(variable, positive))
+            .unwrap()
+    }
+
+    /// The part of `self` left over once the literals of `by` are fixed, or `None`
+    /// if the two cubes don't meet.
+    fn cofactor(&self, by: &Cube) -> Option<Cube> {
+        if !self.intersects(by) {
+            return None;
+        }
+        Some(Cube {
+            mask: self.mask & !by.mask,
+            bits: self.bits & !by.mask,
+        })
+    }
+}
+
+/// A sum of products over a fixed, ordered list of variable names.
+#[derive(Debug, Clone)]
+pub struct Cover {
+    pub variables: Vec<String>,
+    pub cubes: Vec<Cube>,
+}
+
+impl Cover {
+    pub fn new(variables: Vec<String>, cubes: Vec<Cube>) -> Cover {
+        if variables.len() > 64 {
+            panic!(
+                "covers support at most 64 variables, got {}",
+                variables.len()
+            );
+        }
+        Cover { variables, cubes }
+    }
+
+    /// Derives the ON-set and OFF-set covers of `tree` without enumerating its truth table,
+    /// so it stays usable for expressions with many more inputs than a table can hold.
+    pub fn from_gate(tree: &Gate) -> (Cover, Cover) {
+        let variables = tree.input_names();
+        let (on, off) = gate_covers(tree, &variables);
+        (
+            Cover::new(variables.clone(), on),
+            Cover::new(variables, off),
+        )
+    }
+
+    /// The cover of every row this one leaves out, found by splitting on variables as `is_tautology` does
+    /// rather than by listing rows, so it stays usable for covers over many variables.
+    pub fn complement(&self) -> Cover {
+        Cover::new(self.variables.clone(), complement(&self.cubes))
+    }
+
+    /// Builds an OR of ANDs that the renderer can draw.
+    /// An empty cover is the constant `0`, a cover holding the universal cube is `1`.
+    pub fn to_gate(&self) -> Gate {
+        if self.cubes.is_empty() {
+            return Gate::input("0");
+        }
+        if self.cubes.iter().any(|c| c.mask == 0) {
+            return Gate::input("1");
+        }
+
+        let mut cubes = self.cubes.clone();
+        cubes.sort_by_key(|c| (c.mask.trailing_zeros(), c.literal_count(), c.mask, c.bits));
+
+        let products: Vec<Gate> = cubes.iter().map(|c| self.cube_to_gate(c)).collect();
+        fold_gates(GateType::OR, products)
+    }
+
+    fn cube_to_gate(&self, cube: &Cube) -> Gate {
+        let mut literals = Vec::new();
+        for (i, name) in self.variables.iter().enumerate() {
+            if !cube.has_variable(i) {
+                continue;
+            }
+            let input = Gate::input(name);
+            if cube.polarity(i) {
+                literals.push(input);
+            } else {
+                literals.push(Gate::new(GateType::NOT, vec![input]));
+            }
+        }
+        fold_gates(GateType::AND, literals)
+    }
+}
+
+/// Chains two-input gates left to right, the same shape the parser gives `a+b+c`.
+pub fn fold_gates(gate_type: GateType, gates: Vec<Gate>) -> Gate {
+    let mut iter = gates.into_iter();
+    let mut result = iter.next().expect("can't fold an empty list of gates");
+    for gate in iter {
+        result = Gate::new(gate_type, vec![result, gate]);
+    }
+    result
+}
+
+fn gate_covers(tree: &Gate, variables: &[String]) -> (Vec<Cube>, Vec<Cube>) {
+    let inputs = tree.get_inputs();
+    match tree.get_type() {
+        GateType::INPUT => match &tree.get_name()[..] {
+            "0" => (vec![], vec![Cube::universe()]),
+            "1" => (vec![Cube::universe()], vec![]),
+            name => {
+                let index = variables.iter().position(|v| v == name).unwrap();
+                (
+                    vec![Cube::literal(index, true)],
+                    vec![Cube::literal(index, false)],
+                )
+            }
+        },
+        GateType::NOT => {
+            let (on, off) = gate_covers(&inputs[0], variables);
+            (off, on)
+        }
+        gate_type => {
+            let mut children = inputs.iter().map(|g| gate_covers(g, variables));
+            let (mut on, mut off) = children.next().unwrap();
+            for (child_on, child_off) in children {
+                match gate_type {
+                    GateType::AND | GateType::NAND => {
+                        on = product(&on, &child_on);
+                        off = union(off, child_off);
+                    }
+                    GateType::OR | GateType::NOR => {
+                        on = union(on, child_on);
+                        off = product(&off, &child_off);
+                    }
+                    _ => {
+                        // a ^ b is on where exactly one side is on
+                        let new_on = union(product(&on, &child_off), product(&off, &child_on));
+                        let new_off = union(product(&on, &child_on), product(&off, &child_off));
+                        on = new_on;
+                        off = new_off;
+                    }
+                }
+            }
+            if matches!(gate_type, GateType::NAND | GateType::NOR | GateType::XNOR) {
+                (off, on)
+            } else {
+                (on, off)
+            }
+        }
+    }
+}
+
+fn union(mut a: Vec<Cube>, b: Vec<Cube>) -> Vec<Cube> {
+    a.extend(b);
+    remove_contained(a)
+}
+
+fn product(a: &[Cube], b: &[Cube]) -> Vec<Cube> {
+    let mut result = Vec::new();
+    for x in a {
+        for y in b {
+            if let Some(cube) = x.intersection(y) {
+                result.push(cube);
+            }
+        }
+    }
+    remove_contained(result)
+}
+
+/// Drops duplicate cubes and cubes wholly inside another single cube.
+fn remove_contained(cubes: Vec<Cube>) -> Vec<Cube> {
+    let mut seen = HashSet::new();
+    let mut cubes: Vec<Cube> = cubes.into_iter().filter(|c| seen.insert(*c)).collect();
+    cubes.sort_by_key(|c| c.literal_count());
+
+    let mut kept: Vec<Cube> = Vec::new();
+    for cube in cubes {
+        if !kept.iter().any(|k| k.contains(&cube)) {
+            kept.push(cube);
+        }
+    }
+    kept
+}
+
+fn contains_cube(cubes: &[Cube], cube: &Cube) -> bool {
+    let cofactors: Vec<Cube> = cubes.iter().filter_map(|c| c.cofactor(cube)).collect();
+    is_tautology(&cofactors)
+}
+
+/// Unate-recursive tautology check: split on the most binate variable,
+/// and drop unate variables since they can never be what makes a cover complete.
+fn is_tautology(cubes: &[Cube]) -> bool {
+    if cubes.iter().any(|c| c.mask == 0) {
+        return true;
+    }
+    if cubes.is_empty() {
+        return false;
+    }
+
+    let mut best: Option<(usize, usize)> = None;
+    for variable in 0..64 {
+        let positive = cubes
+            .iter()
+            .filter(|c| c.has_variable(variable) && c.polarity(variable))
+            .count();
+        let negative = cubes
+            .iter()
+            .filter(|c| c.has_variable(variable) && !c.polarity(variable))
+            .count();
+
+        if positive + negative == 0 {
+            continue;
+        }
+        if positive == 0 || negative == 0 {
+            let reduced: Vec<Cube> = cubes
+                .iter()
+                .filter(|c| !c.has_variable(variable))
+                .copied()
+                .collect();
+            return is_tautology(&reduced);
+        }
+        if best.is_none_or(|(_, count)| positive + negative > count) {
+            best = Some((variable, positive + negative));
+        }
+    }
+
+    let variable = best.unwrap().0;
+    [true, false].iter().all(|&value| {
+        let split = Cube::literal(variable, value);
+        let cofactors: Vec<Cube> = cubes.iter().filter_map(|c| c.cofactor(&split)).collect();
+        is_tautology(&cofactors)
+    })
+}
+
+/// The cubes covering exactly the minterms `cubes` miss. A single cube's complement is one cube per literal,
+/// flipped; otherwise the cover is split on the variable most cubes use, and a cube found in both halves'
+/// complements is kept without that variable.
+fn complement(cubes: &[Cube]) -> Vec<Cube> {
+    if cubes.iter().any(|c| c.mask == 0) {
+        return Vec::new();
+    }
+    match cubes {
+        [] => return vec![Cube::universe()],
+        [cube] => {
+            return (0..64)
+                .filter(|&i| cube.has_variable(i))
+                .map(|i| Cube::literal(i, !cube.polarity(i)))
+                .collect()
+        }
+        _ => {}
+    }
+
+    let variable = (0..64)
+        .max_by_key(|&i| cubes.iter().filter(|c| c.has_variable(i)).count())
+        .unwrap();
+    let halves: Vec<Vec<Cube>> = [true, false]
+        .iter()
+        .map(|&value| {
+            let split = Cube::literal(variable, value);
+            let cofactors: Vec<Cube> = cubes.iter().filter_map(|c| c.cofactor(&split)).collect();
+            complement(&cofactors)
+        })
+        .collect();
+    let mut result: Vec<Cube> = halves[0]
+        .iter()
+        .filter(|c| halves[1].contains(c))
+        .copied()
+        .collect();
+    let shared = result.clone();
+    for (half, value) in halves.iter().zip([true, false]) {
+        for cube in half.iter().filter(|c| !shared.contains(c)) {
+            result.push(cube.with_literal(variable, value));
+        }
+    }
+    remove_contained(result)
+}
+
+/// Raises each cube into a prime implicant that stays clear of `off`,
+/// then drops the cubes the new prime swallows.
+fn expand(cubes: Vec<Cube>, off: &[Cube]) -> Vec<Cube> {
+    let mut pending = cubes;
+    pending.sort_by_key(|c| c.literal_count());
+
+    let mut expanded: Vec<Cube> = Vec::new();
+    while !pending.is_empty() {
+        let mut cube = pending.remove(0);
+
+        // raise the literals that block the fewest OFF-set cubes first
+        let mut variables: Vec<usize> = (0..64).filter(|&v| cube.has_variable(v)).collect();
+        variables.sort_by_key(|&v| {
+            off.iter()
+                .filter(|o| o.has_variable(v) && o.polarity(v) != cube.polarity(v))
+                .count()
+        });
+
+        for variable in variables {
+            let raised = cube.without_variable(variable);
+            if !off.iter().any(|o| o.intersects(&raised)) {
+                cube = raised;
+            }
+        }
+
+        pending.retain(|p| !cube.contains(p));
+        expanded.retain(|e| !cube.contains(e));
+        expanded.push(cube);
+    }
+    expanded
+}
+
+/// Greedily removes cubes that the rest of the cover plus the don't-cares already cover.
+fn irredundant(cubes: Vec<Cube>, dont_care: &[Cube]) -> Vec<Cube> {
+    let mut cubes = cubes;
+    cubes.sort_by_key(|c| std::cmp::Reverse(c.literal_count()));
+
+    let mut i = 0;
+    while i < cubes.len() {
+        let mut others: Vec<Cube> = cubes
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, c)| *c)
+            .collect();
+        others.extend_from_slice(dont_care);
+
+        if contains_cube(&others, &cubes[i]) {
+            cubes.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    cubes
+}
+
+/// Shrinks each cube to the smallest cube still needed, given everything else in the cover,
+/// so the next expand can grow it in a different direction.
+fn reduce(cubes: Vec<Cube>, dont_care: &[Cube]) -> Vec<Cube> {
+    let mut cubes = cubes;
+    cubes.sort_by_key(|c| c.literal_count());
+
+    for i in 0..cubes.len() {
+        let mut others: Vec<Cube> = cubes
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, c)| *c)
+            .collect();
+        others.extend_from_slice(dont_care);
+
+        let mut cube = cubes[i];
+        let free: Vec<usize> = (0..64).filter(|&v| !cube.has_variable(v)).collect();
+        for variable in free {
+            if contains_cube(&others, &cube.with_literal(variable, false)) {
+                cube = cube.with_literal(variable, true);
+            } else if contains_cube(&others, &cube.with_literal(variable, true)) {
+                cube = cube.with_literal(variable, false);
+            }
+        }
+        cubes[i] = cube;
+    }
+    cubes
+}
+
+fn cost(cubes: &[Cube]) -> (usize, u32) {
+    (cubes.len(), cubes.iter().map(|c| c.literal_count()).sum())
+}
+
+/// Heuristic two-level minimization in the style of Espresso:
+/// expand to primes, drop redundant primes, then reduce and re-expand until the cost stops improving.
+/// `off` must be the complement of `on` plus `dont_care`.
+pub fn espresso(on: &Cover, dont_care: &Cover, off: &Cover) -> Cover {
+    let mut cubes = expand(on.cubes.clone(), &off.cubes);
+    cubes = irredundant(cubes, &dont_care.cubes);
+    let mut best_cost = cost(&cubes);
+
+    loop {
+        let mut candidate = reduce(cubes.clone(), &dont_care.cubes);
+        candidate = expand(candidate, &off.cubes);
+        candidate = irredundant(candidate, &dont_care.cubes);
+
+        let candidate_cost = cost(&candidate);
+        if candidate_cost >= best_cost {
+            break;
+        }
+        cubes = candidate;
+        best_cost = candidate_cost;
+    }
+
+    Cover::new(on.variables.clone(), cubes)
+}
+
+pub fn minimized_cover(tree: &Gate) -> Cover {
+    let (on, off) = Cover::from_gate(tree);
+    let dont_care = Cover::new(on.variables.clone(), vec![]);
+    espresso(&on, &dont_care, &off)
+}
+
+pub fn minimize(tree: &Gate) -> Gate {
+    minimized_cover(tree).to_gate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::{minterm_assignment, parse_boolean_expression};
+
+    fn minterms(rows: &[u64], variable_count: usize) -> Vec<Cube> {
+        rows.iter()
+            .map(|&row| Cube::minterm(row, variable_count))
+            .collect()
+    }
+
+    fn product_term(literals: &[(usize, bool)]) -> Cube {
+        literals
+            .iter()
+            .fold(Cube::universe(), |cube, &(variable, positive)| {
+                cube.with_literal(variable, positive)
+            })
+    }
+
+    #[test]
+    fn expand_raises_minterms_to_one_prime() {
+        // a over a,b: rows 2 and 3 are on, 0 and 1 off
+        let expanded = expand(minterms(&[2, 3], 2), &minterms(&[0, 1], 2));
+        assert_eq!(expanded, vec![Cube::literal(0, true)]);
+    }
+
+    #[test]
+    fn expand_gives_primes_clear_of_the_off_set() {
+        let on = minterms(&[0, 1, 3, 7], 3);
+        let off = minterms(&[2, 4, 5, 6], 3);
+        for cube in expand(on, &off) {
+            assert!(!off.iter().any(|o| o.intersects(&cube)));
+            for variable in (0..3).filter(|&v| cube.has_variable(v)) {
+                let raised = cube.without_variable(variable);
+                assert!(
+                    off.iter().any(|o| o.intersects(&raised)),
+                    "{:?} isn't prime",
+                    cube
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn irredundant_drops_the_consensus_term() {
+        // ab + a'c + bc, where bc is covered by the other two
+        let ab = product_term(&[(0, true), (1, true)]);
+        let not_a_c = product_term(&[(0, false), (2, true)]);
+        let bc = product_term(&[(1, true), (2, true)]);
+        assert_eq!(irredundant(vec![bc, ab, not_a_c], &[]), vec![ab, not_a_c]);
+    }
+
+    #[test]
+    fn irredundant_drops_cubes_inside_the_dont_cares() {
+        let a = Cube::literal(0, true);
+        let not_a_not_b = product_term(&[(0, false), (1, false)]);
+        assert_eq!(irredundant(vec![a, not_a_not_b], &[not_a_not_b]), vec![a]);
+    }
+
+    #[test]
+    fn reduce_shrinks_a_cube_to_what_others_leave_uncovered() {
+        // in a + b, the minterm ab is covered by b, so a only needs ab'
+        let a = Cube::literal(0, true);
+        let b = Cube::literal(1, true);
+        let reduced = reduce(vec![a, b], &[]);
+        assert_eq!(reduced, vec![product_term(&[(0, true), (1, false)]), b]);
+    }
+
+    #[test]
+    fn espresso_finds_the_minimum_cover() {
+        let tree = parse_boolean_expression("ab + ab' + a'b");
+        let cover = minimized_cover(&tree);
+        assert_eq!(cost(&cover.cubes), (2, 2));
+    }
+
+    #[test]
+    fn minimize_preserves_every_row() {
+        for expression in [
+            "ab + a'c + bc",
+            "(a + b)(a' + c)(b + c')",
+            "a ^ b ^ c",
+            "(ab)' + (cd)'",
+            "a b' c + a' b c' + a b c + d",
+            "(a + 0)(b + 1) + c'",
+            "a a'",
+        ] {
+            let tree = parse_boolean_expression(expression);
+            let minimized = minimize(&tree);
+            let variables = tree.input_names();
+            for row in 0..1_u64 << variables.len() {
+                let assignment = minterm_assignment(&variables, row);
+                assert_eq!(
+                    tree.evaluate(&assignment),
+                    minimized.evaluate(&assignment),
+                    "{} minimized to {} differs on row {}",
+                    expression,
+                    minimized.get_name(),
+                    row
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn complement_covers_exactly_the_rows_left_out() {
+        let on = [1, 2, 7, 11, 12, 13];
+        let off = complement(&minterms(&on, 4));
+        for row in 0..16 {
+            assert_eq!(
+                off.iter().any(|c| c.contains_minterm(row, 4)),
+                !on.contains(&row),
+                "row {}",
+                row
+            );
+        }
+        assert_eq!(complement(&[Cube::universe()]), Vec::new());
+        assert_eq!(complement(&[]), vec![Cube::universe()]);
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashSet;
+
+use crate::expression_parser::{Gate, GateType};
+
+/// The gates a structural export instances: every `GateType` but INPUT, which is a net rather than a gate.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogicGate {
+    AND,
+    OR,
+    NOT,
+    NAND,
+    NOR,
+    XOR,
+    XNOR,
+}
+
+impl LogicGate {
+    /// The gate of `gate_type`, or `None` for INPUT.
+    pub fn of(gate_type: GateType) -> Option<LogicGate> {
+        match gate_type {
+            GateType::AND => Some(LogicGate::AND),
+            GateType::OR => Some(LogicGate::OR),
+            GateType::NOT => Some(LogicGate::NOT),
+            GateType::NAND => Some(LogicGate::NAND),
+            GateType::NOR => Some(LogicGate::NOR),
+            GateType::XOR => Some(LogicGate::XOR),
+            GateType::XNOR => Some(LogicGate::XNOR),
+            GateType::INPUT => None,
+        }
+    }
+}
+
+/// What drives one net of a `Netlist`.
+#[derive(Debug, Clone)]
+pub enum Cell {
+    /// A gate reading `operands` in the order they're written, computing the subexpression `expression`.
+    Gate {
+        gate: LogicGate,
+        output: String,
+        operands: Vec<String>,
+        expression: String,
+    },
+    /// Joins the output straight to an input or constant, when that's the whole expression.
+    Buffer { output: String, source: String },
+}
+
+/// A tree of gates flattened into the cells driving each net, the form every structural export writes out.
+/// Cells come after the cells driving what they read, and the nets between them are `n1`, `n2`, ...
+/// in the order they're computed, skipping any name an input or the output already has.
+/// Inputs keep their names and the constants are the nets `0` and `1`.
+pub struct Netlist {
+    pub wires: Vec<String>,
+    pub cells: Vec<Cell>,
+    /// Names the wires mustn't take: the inputs and the output.
+    reserved: HashSet<String>,
+    numbered: usize,
+}
+
+impl Netlist {
+    pub fn new(tree: &Gate, output: &str) -> Netlist {
+        let mut reserved: HashSet<String> = tree.input_names().into_iter().collect();
+        reserved.insert(String::from(output));
+        let mut netlist = Netlist {
+            wires: Vec::new(),
+            cells: Vec::new(),
+            reserved,
+            numbered: 0,
+        };
+        if tree.get_type() == GateType::INPUT {
+            netlist.cells.push(Cell::Buffer {
+                output: String::from(output),
+                source: tree.get_name(),
+            });
+        } else {
+            netlist.add(tree, Some(output));
+        }
+        netlist
+    }
+
+    /// Adds the cells computing `gate` and returns the net carrying its value,
+    /// which is `target` if given and a new wire otherwise.
+    fn add(&mut self, gate: &Gate, target: Option<&str>) -> String {
+        let logic_gate = match LogicGate::of(gate.get_type()) {
+            Some(logic_gate) => logic_gate,
+            None => return gate.get_name(),
+        };
+        // operands are stored last first
+        let operands = gate
+            .get_inputs()
+            .iter()
+            .rev()
+            .map(|input| self.add(input, None))
+            .collect();
+        let output = match target {
+            Some(target) => String::from(target),
+            None => {
+                let wire = self.next_name();
+                self.wires.push(wire.clone());
+                wire
+            }
+        };
+        self.cells.push(Cell::Gate {
+            gate: logic_gate,
+            output: output.clone(),
+            operands,
+            expression: gate.get_name(),
+        });
+        output
+    }
+
+    fn next_name(&mut self) -> String {
+        loop {
+            self.numbered += 1;
+            let name = format!("n{}", self.numbered);
+            if !self.reserved.contains(&name) {
+                return name;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wires_skip_the_names_of_inputs_and_the_output() {
+        // n1 n2' + n1' n2, as a header like f(n1,n2) names its variables
+        let (n1, n2) = (Gate::input("n1"), Gate::input("n2"));
+        let not = |gate: &Gate| Gate::new(GateType::NOT, vec![gate.clone()]);
+        let tree = Gate::new(
+            GateType::OR,
+            vec![
+                Gate::new(GateType::AND, vec![n1.clone(), not(&n2)]),
+                Gate::new(GateType::AND, vec![not(&n1), n2.clone()]),
+            ],
+        );
+        let netlist = Netlist::new(&tree, "n4");
+        assert_eq!(netlist.wires, vec!["n3", "n5", "n6", "n7"]);
+        for cell in &netlist.cells {
+            if let Cell::Gate { output, .. } = cell {
+                assert!(
+                    output != "n1" && output != "n2",
+                    "{} drives an input",
+                    output
+                );
+            }
+        }
+    }
+}
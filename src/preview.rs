@@ -0,0 +1,212 @@
+use image::{png::PngEncoder, ColorType, Rgba, RgbaImage};
+
+/// The escape sequences a terminal can draw an image inline with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    ITerm,
+    Sixel,
+}
+
+impl Protocol {
+    pub fn from_name(name: &str) -> Option<Protocol> {
+        match name {
+            "kitty" => Some(Protocol::Kitty),
+            "iterm" => Some(Protocol::ITerm),
+            "sixel" => Some(Protocol::Sixel),
+            _ => None,
+        }
+    }
+}
+
+/// Kitty takes its payload in pieces of at most this many bytes.
+const KITTY_CHUNK: usize = 4096;
+
+/// The escape sequence drawing `image` at the cursor in a terminal speaking `protocol`.
+pub fn escape(image: &RgbaImage, protocol: Protocol) -> String {
+    match protocol {
+        Protocol::Kitty => kitty(image),
+        Protocol::ITerm => iterm(image),
+        Protocol::Sixel => sixel(image),
+    }
+}
+
+fn kitty(image: &RgbaImage) -> String {
+    let payload = base64(&png(image));
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut text = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        // only the first chunk says what the image is, and every chunk but the last says more follow
+        let keys = if i == 0 { "a=T,f=100," } else { "" };
+        let more = (i + 1 < chunks.len()) as u8;
+        text.push_str(&format!(
+            "\x1b_G{}m={};{}\x1b\\",
+            keys,
+            more,
+            std::str::from_utf8(chunk).unwrap()
+        ));
+    }
+    text.push('\n');
+    text
+}
+
+fn iterm(image: &RgbaImage) -> String {
+    let data = png(image);
+    format!(
+        "\x1b]1337;File=inline=1;size={};preserveAspectRatio=1:{}\x07\n",
+        data.len(),
+        base64(&data)
+    )
+}
+
+/// Sixel draws six rows of pixels at a time, one pass per colour, so the image is reduced to
+/// a 6×6×6 colour cube over white first. Runs of the same column pattern are run-length encoded.
+fn sixel(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let colors: Vec<Vec<usize>> = (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| cube_index(*image.get_pixel(x, y)))
+                .collect()
+        })
+        .collect();
+
+    let mut text = format!("\x1bPq\"1;1;{};{}", width, height);
+    for index in 0..216 {
+        let level = |digit: usize| digit * 100 / 5;
+        text.push_str(&format!(
+            "#{};2;{};{};{}",
+            index,
+            level(index / 36),
+            level(index / 6 % 6),
+            level(index % 6)
+        ));
+    }
+    for band in (0..height as usize).step_by(6) {
+        let rows = &colors[band..(band + 6).min(height as usize)];
+        let mut used: Vec<usize> = rows.iter().flatten().copied().collect();
+        used.sort_unstable();
+        used.dedup();
+        for (i, &color) in used.iter().enumerate() {
+            if i > 0 {
+                text.push('$');
+            }
+            text.push_str(&format!("#{}", color));
+            let columns: Vec<u8> = (0..width as usize)
+                .map(|x| {
+                    rows.iter()
+                        .enumerate()
+                        .filter(|(_, row)| row[x] == color)
+                        .fold(0, |bits, (dy, _)| bits | 1 << dy)
+                })
+                .collect();
+            push_runs(&mut text, &columns);
+        }
+        text.push('-');
+    }
+    text.push_str("\x1b\\\n");
+    text
+}
+
+/// Writes each column pattern as a sixel character, with `!` and a count in front of repeats.
+fn push_runs(text: &mut String, columns: &[u8]) {
+    let mut x = 0;
+    while x < columns.len() {
+        let run = columns[x..]
+            .iter()
+            .take_while(|&&c| c == columns[x])
+            .count();
+        let character = (63 + columns[x]) as char;
+        if run > 3 {
+            text.push_str(&format!("!{}{}", run, character));
+        } else {
+            text.extend(std::iter::repeat_n(character, run));
+        }
+        x += run;
+    }
+}
+
+/// The nearest colour of the 6×6×6 cube to `pixel` laid over white.
+fn cube_index(pixel: Rgba<u8>) -> usize {
+    let Rgba([r, g, b, a]) = pixel;
+    let level = |channel: u8| {
+        let over_white = (channel as u32 * a as u32 + 255 * (255 - a as u32)) / 255;
+        ((over_white * 5 + 127) / 255) as usize
+    };
+    36 * level(r) + 6 * level(g) + level(b)
+}
+
+fn png(image: &RgbaImage) -> Vec<u8> {
+    let mut data = Vec::new();
+    PngEncoder::new(&mut data)
+        .encode(image, image.width(), image.height(), ColorType::Rgba8)
+        .unwrap();
+    data
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let word = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(ALPHABET[(word >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_pads_the_last_group() {
+        // the examples from RFC 4648
+        for (data, text) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(data.as_bytes()), text);
+        }
+        assert_eq!(base64(&[0xfb, 0xff, 0xfe]), "+//+");
+    }
+
+    #[test]
+    fn sixel_draws_each_colour_of_a_band_in_turn() {
+        let black = Rgba([0, 0, 0, 255]);
+        let white = Rgba([255, 255, 255, 255]);
+        let clear = Rgba([0, 0, 0, 0]);
+        // black on the diagonal, and the transparent corner is drawn as the white under it
+        let image = RgbaImage::from_fn(2, 2, |x, y| match (x, y) {
+            (0, 1) => clear,
+            _ if x == y => black,
+            _ => white,
+        });
+        let text = sixel(&image);
+        assert!(
+            text.starts_with("\x1bPq\"1;1;2;2#0;2;0;0;0#1;2;0;0;20"),
+            "{:?}",
+            text
+        );
+        assert!(text.contains("#215;2;100;100;100#0@A"), "{:?}", text);
+        assert!(text.ends_with("#0@A$#215A@-\x1b\\\n"), "{:?}", text);
+
+        let row = RgbaImage::from_pixel(5, 1, black);
+        assert!(sixel(&row).ends_with("#0!5@-\x1b\\\n"));
+    }
+}
@@ -1,126 +1,465 @@
-use std::{collections::VecDeque, convert::TryInto};
+use std::collections::{HashMap, VecDeque};
 
 use image::{GenericImageView, ImageBuffer, Rgba, RgbaImage};
 use rusttype::{point, Font, Scale};
 
-use crate::expression_parser::{Gate, GateType};
-use crate::spritesheet::{Sprite, SpriteCreator, SpriteInfo};
+use crate::circuit::{CircuitLayout, NodeKind};
+use crate::expression_parser::GateType;
+use crate::gate_graph::{GateGraph, GateId, ROOT};
+use crate::spritesheet::{Sprite, SpriteCreator};
+use crate::svg::{self, Element};
+use crate::typesetting::{draw_label, label_width};
+
+const TITLE_HEIGHT: u32 = 50;
+const TITLE_SIZE: f32 = 30.0;
+const LABEL_SIZE: f32 = 25.0;
+const WIRE_LABEL_SIZE: f32 = 18.0;
+/// Where an output's label starts, past the tip of the terminal sprite.
+const TERMINAL_LABEL_X: u32 = 78;
+/// How far before the next column a labelled wire turns towards its pin.
+const WIRE_TURN: u32 = 8;
+
+/// What gets written on a diagram besides the gates themselves.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Names the terminal on the root gate's output.
+    pub output_label: String,
+    /// Captioned above the diagram.
+    pub title: Option<String>,
+    /// Writes each gate's subexpression over its output wire.
+    pub wire_labels: bool,
+    /// Sets negations in labels as overbars and variable numbers as subscripts.
+    pub overbars: bool,
+    /// Simulates the circuit, colouring each wire by its value and writing the values on the inputs and output.
+    pub assignment: Option<HashMap<String, bool>>,
+    /// Draws the wires along this path, given as `timing::critical_path` gives it, heavier and in `CRITICAL`.
+    pub critical_path: Option<Vec<usize>>,
+}
 
 pub struct Renderer {
     sprite_creator: SpriteCreator,
     image: RgbaImage,
     font: Font<'static>,
+    options: RenderOptions,
+    title_height: u32,
+    /// The x of each column of the tree, leftmost first.
+    column_x: Vec<u32>,
+    /// Everything drawn so far, for writing the diagram as an SVG.
+    elements: Vec<Element>,
 }
 
 impl Renderer {
-    pub fn new(tree: &Gate) -> Renderer {
-        let to_return = Renderer {
+    pub fn new(graph: &GateGraph, mut options: RenderOptions) -> Renderer {
+        let font = load_font();
+        if let Some(assignment) = &options.assignment {
+            options.output_label =
+                with_value(&options.output_label, graph.values(assignment)[ROOT]);
+        }
+        let title_height = if options.title.is_some() {
+            TITLE_HEIGHT
+        } else {
+            0
+        };
+
+        // with wire labels each column gets a channel after it wide enough for the labels it writes
+        let mut channels = vec![0; graph.depth() as usize];
+        if options.wire_labels {
+            for node in &graph.nodes {
+                if node.gate_type != GateType::INPUT && node.column != graph.depth() {
+                    let width = measure_label(&font, &node.name, WIRE_LABEL_SIZE, options.overbars)
+                        + 2 * WIRE_TURN;
+                    let channel = &mut channels[node.column as usize - 1];
+                    *channel = (*channel).max(width);
+                }
+            }
+        }
+        let column_x: Vec<u32> = (0..channels.len())
+            .map(|column| column as u32 * 90 + channels[..column].iter().sum::<u32>())
+            .collect();
+
+        let width = (graph.drawn_image_width() + channels.iter().sum::<u32>())
+            .max(
+                column_x[column_x.len() - 1]
+                    + 90
+                    + terminal_width(&font, &options.output_label, options.overbars),
+            )
+            .max(title_width(&font, options.title.as_deref()));
+        Renderer {
             sprite_creator: SpriteCreator::new(),
             image: ImageBuffer::from_fn(
-                tree.calculate_drawn_image_width(),
-                tree.calculate_drawn_image_height(),
+                width,
+                graph.drawn_image_height() + title_height,
                 |_, _| Rgba([255, 255, 255, 255]),
             ),
-            font: Font::try_from_bytes(include_bytes!("../assets/fonts/cmunrm.ttf"))
-                .expect("error constructing a Font from bytes"),
+            font,
+            options,
+            title_height,
+            column_x,
+            elements: Vec::new(),
+        }
+    }
+
+    pub fn for_circuit(layout: &CircuitLayout, options: RenderOptions) -> Renderer {
+        let font = load_font();
+        let title_height = if options.title.is_some() {
+            TITLE_HEIGHT
+        } else {
+            0
+        };
+        let width = layout
+            .nodes
+            .iter()
+            .filter_map(|node| match &node.kind {
+                NodeKind::Output(name) => {
+                    // leave room for the widest value that could be written after it
+                    let name = with_value(name, false);
+                    Some(node.x + terminal_width(&font, &name, options.overbars))
+                }
+                _ => None,
+            })
+            .fold(layout.width, u32::max)
+            .max(title_width(&font, options.title.as_deref()));
+        Renderer {
+            sprite_creator: SpriteCreator::new(),
+            image: ImageBuffer::from_fn(width, layout.height + title_height, |_, _| {
+                Rgba([255, 255, 255, 255])
+            }),
+            font,
+            options,
+            title_height,
+            column_x: Vec::new(),
+            elements: Vec::new(),
+        }
+    }
+
+    /// Draws `layout`, with `critical_wires` (as `timing::layout_critical_wires` gives them)
+    /// heavier and in `CRITICAL`, like the critical path of a tree.
+    pub fn draw_circuit(&mut self, layout: &CircuitLayout, critical_wires: &[(usize, usize)]) {
+        self.draw_title();
+
+        let mut layout = layout.clone();
+        for node in &mut layout.nodes {
+            node.y += self.title_height;
+        }
+        let values = self.options.assignment.as_ref().map(|a| layout.values(a));
+        let value_of = |id: usize| values.as_ref().map(|v| v[id]);
+        let on_critical_path = |id: usize| {
+            critical_wires
+                .iter()
+                .any(|&(node, pin)| node == id || layout.nodes[node].inputs[pin] == id)
         };
 
-        to_return
+        for (id, node) in layout.nodes.iter().enumerate() {
+            let heavy = on_critical_path(id);
+            let color = self.path_color(value_of(id), heavy);
+            match &node.kind {
+                NodeKind::Gate(gate_type, name) => {
+                    let name = match value_of(id) {
+                        Some(value) if *gate_type == GateType::INPUT => input_label(name, value),
+                        _ => name.clone(),
+                    };
+                    self.draw(gate_type.sprite(), name, node.x, node.y);
+                    let output_y = node.y
+                        + self
+                            .sprite_creator
+                            .get_sprite_info(gate_type.sprite())
+                            .outputs[0];
+                    self.tint_stub(node.x + 89, output_y, true, color);
+                }
+                NodeKind::Wire => {
+                    self.path_wire([node.x, node.y], [node.x + 90, node.y], color, heavy)
+                }
+                NodeKind::Output(name) => {
+                    let label = match value_of(id) {
+                        Some(value) => with_value(name, value),
+                        None => name.clone(),
+                    };
+                    self.output_terminal(&label, node.x, node.y, color);
+                }
+            }
+
+            let pins = match &node.kind {
+                NodeKind::Gate(gate_type, _) => self
+                    .sprite_creator
+                    .get_sprite_info(gate_type.sprite())
+                    .inputs
+                    .iter()
+                    .map(|pin| node.y + pin)
+                    .collect(),
+                _ => vec![node.y],
+            };
+            for (pin, (&source_id, pin_y)) in node.inputs.iter().zip(pins).enumerate() {
+                let source = &layout.nodes[source_id];
+                let heavy = critical_wires.contains(&(id, pin));
+                let color = self.path_color(value_of(source_id), heavy);
+                let source_y = match &source.kind {
+                    NodeKind::Gate(gate_type, _) => {
+                        source.y
+                            + self
+                                .sprite_creator
+                                .get_sprite_info(gate_type.sprite())
+                                .outputs[0]
+                    }
+                    _ => source.y,
+                };
+                self.path_wire(
+                    [source.x + 90, source_y],
+                    [source.lane, source_y],
+                    color,
+                    heavy,
+                );
+                self.path_wire([source.lane, source_y], [node.x, pin_y], color, heavy);
+                if let NodeKind::Gate(_, _) = node.kind {
+                    self.tint_stub(node.x, pin_y, false, color);
+                }
+
+                // mark where a signal feeding several pins branches off its lane
+                let fanout = layout
+                    .nodes
+                    .iter()
+                    .flat_map(|n| n.inputs.iter())
+                    .filter(|&&i| i == source_id)
+                    .count();
+                if fanout > 1 && pin_y != source_y {
+                    self.junction(source.lane, pin_y, color);
+                }
+            }
+        }
+    }
+
+    fn junction(&mut self, x: u32, y: u32, color: Rgba<u8>) {
+        self.elements.push(Element::Junction { x, y, color });
+        for dx in 0..6 {
+            for dy in 0..6 {
+                self.image.put_pixel(x + dx - 2, y + dy - 2, color);
+            }
+        }
+    }
+
+    /// Recolours the stub a sprite draws for one of its pins, walking along the two pixel rows from `y`
+    /// (leftwards from an output, rightwards from an input) until the gate's outline is reached.
+    /// The walk starts over any of the wire already drawn in `color` where it meets the pin.
+    fn tint_stub(&mut self, x: u32, y: u32, leftwards: bool, color: Rgba<u8>) {
+        if color == BLACK {
+            return;
+        }
+        // sprites are drawn in dark greys, so anything coloured is a wire already drawn
+        let is_sprite = |pixel: &Rgba<u8>| {
+            (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) < 300
+                && pixel[0].max(pixel[1]).max(pixel[2]) - pixel[0].min(pixel[1]).min(pixel[2]) < 40
+        };
+        let start = x as i32;
+        let mut x = start;
+        while x >= 0
+            && (x as u32) < self.image.width()
+            && (is_sprite(self.image.get_pixel(x as u32, y))
+                || *self.image.get_pixel(x as u32, y) == color)
+            && !is_sprite(self.image.get_pixel(x as u32, y - 1))
+        {
+            self.image.put_pixel(x as u32, y, color);
+            self.image.put_pixel(x as u32, y + 1, color);
+            x += if leftwards { -1 } else { 1 };
+        }
+        if x != start {
+            // the stub as a wire along the pixels just recoloured
+            let (first, last) = if leftwards {
+                (x + 1, start)
+            } else {
+                (start, x - 1)
+            };
+            self.elements.push(Element::Wire {
+                from: [first as u32, y],
+                to: [last as u32 + 1, y],
+                color,
+            });
+        }
     }
 
-    pub fn draw_tree(&mut self, tree: &Gate) {
+    pub fn draw_tree(&mut self, graph: &GateGraph) {
+        self.draw_title();
+
+        let y_origin = graph.adjusted_origin((self.image.height() - self.title_height) / 2)
+            + self.title_height;
+        let critical_path = self.options.critical_path.clone();
+        let positions = graph.positions(y_origin);
+        let values = self.options.assignment.as_ref().map(|a| graph.values(a));
         self.draw_tree_recursive(
-            &tree,
-            tree.adjusted_origin(self.image.height() / 2),
-            tree.depth(),
-            tree.depth(),
+            graph,
+            ROOT,
+            &positions,
+            values.as_deref(),
             None,
+            critical_path.as_deref(),
+        );
+        // self.draw_tree_breadth_first(graph, self.image.height()/2);
+
+        let root_output = y_origin
+            + self
+                .sprite_creator
+                .get_sprite_info(graph.nodes[ROOT].sprite_type())
+                .outputs[0];
+        let label = self.options.output_label.clone();
+        let x = self.column_x[graph.depth() as usize - 1] + 90;
+        let color = self.path_color(values.map(|v| v[ROOT]), critical_path.is_some());
+        self.tint_stub(x - 1, root_output, true, color);
+        self.output_terminal(&label, x, root_output, color);
+    }
+
+    /// The output terminal sprite with its wire meeting `pin_y`, labelled past its tip.
+    fn output_terminal(&mut self, label: &str, x: u32, pin_y: u32, color: Rgba<u8>) {
+        let pin = self.sprite_creator.get_sprite_info(Sprite::OUTPUT).inputs[0];
+        self.draw(Sprite::OUTPUT, String::from(label), x, pin_y - pin);
+        self.tint_stub(x, pin_y, false, color);
+        self.label(
+            label,
+            x + TERMINAL_LABEL_X,
+            pin_y - LABEL_SIZE as u32 / 2 - 2,
+            LABEL_SIZE,
         );
-        // self.draw_tree_breadth_first(tree, self.image.height()/2);
+    }
+
+    /// Writes a gate name or expression, typeset if the options ask for it.
+    fn label(&mut self, text: &str, x: u32, y: u32, size: f32) {
+        self.elements.push(Element::Text {
+            text: String::from(text),
+            x: x as f32,
+            y: y as f32,
+            size,
+            typeset: self.options.overbars,
+        });
+        let black = Rgba([0, 0, 0, 255]);
+        if self.options.overbars {
+            draw_label(&mut self.image, &self.font, text, x, y, size, black);
+        } else {
+            draw_text(&mut self.image, &self.font, text, x, y, size, black);
+        }
+    }
+
+    fn draw_title(&mut self) {
+        if let Some(title) = self.options.title.clone() {
+            let x = self
+                .image
+                .width()
+                .saturating_sub(text_width(&self.font, &title, TITLE_SIZE))
+                / 2;
+            self.elements.push(Element::Text {
+                text: title.clone(),
+                x: x as f32,
+                y: ((TITLE_HEIGHT - TITLE_SIZE as u32) / 2) as f32,
+                size: TITLE_SIZE,
+                typeset: false,
+            });
+            draw_text(
+                &mut self.image,
+                &self.font,
+                &title,
+                x,
+                (TITLE_HEIGHT - TITLE_SIZE as u32) / 2,
+                TITLE_SIZE,
+                Rgba([0, 0, 0, 255]),
+            );
+        }
     }
 
     fn draw_tree_recursive(
         &mut self,
-        tree: &Gate,
-        y_origin: u32,
-        depth: u32,
-        max_depth: u32,
+        graph: &GateGraph,
+        id: GateId,
+        positions: &[u32],
+        values: Option<&[bool]>,
         connect_to_point: Option<[u32; 2]>,
+        critical_path: Option<&[usize]>,
     ) {
-        let x_offset = 90 * (depth - 1);
-
-        println!("Given last y_origin as {}", y_origin);
-
-        let sprite_type = tree.sprite_type();
-        self.draw(sprite_type, tree.get_name(), x_offset, y_origin);
-
-        match connect_to_point {
-            Some(point) => {
-                self.wire(
-                    x_offset + 90,
-                    y_origin
-                        + self
-                            .sprite_creator
-                            .get_sprite_info(sprite_type.clone())
-                            .outputs[0],
-                    point[0],
-                    point[1],
+        let node = &graph.nodes[id];
+        let y_origin = positions[id];
+        let x_offset = self.column_x[node.column as usize - 1];
+
+        let sprite_type = node.sprite_type();
+        let value = values.map(|v| v[id]);
+        let name = match value {
+            Some(value) if node.gate_type == GateType::INPUT => input_label(&node.name, value),
+            _ => node.name.clone(),
+        };
+        self.draw(sprite_type, name, x_offset, y_origin);
+
+        let heavy = critical_path.is_some();
+        let color = self.path_color(value, heavy);
+        let output_y = y_origin + self.sprite_creator.get_sprite_info(sprite_type).outputs[0];
+        self.tint_stub(x_offset + 89, output_y, true, color);
+
+        if let Some(point) = connect_to_point {
+            if point[0] > x_offset + 90 {
+                // run out along the channel under the label before turning towards the pin
+                self.path_wire(
+                    [x_offset + 90, output_y],
+                    [point[0] - WIRE_TURN, output_y],
+                    color,
+                    heavy,
+                );
+                self.path_wire([point[0] - WIRE_TURN, output_y], point, color, heavy);
+            } else {
+                self.path_wire([x_offset + 90, output_y], point, color, heavy);
+            }
+            self.tint_stub(point[0], point[1], false, color);
+
+            if self.options.wire_labels && node.gate_type != GateType::INPUT {
+                self.label(
+                    &node.name,
+                    x_offset + 90 + WIRE_TURN / 2,
+                    output_y - WIRE_LABEL_SIZE as u32,
+                    WIRE_LABEL_SIZE,
                 );
             }
-            None => {}
         }
 
-        for i in 0..tree.get_inputs().len() {
+        for (i, &input) in node.inputs.iter().enumerate() {
             self.draw_tree_recursive(
-                tree.get_inputs().get(i).unwrap(),
-                tree.get_inputs().get(i).unwrap().child_yoffset_function(
-                    y_origin,
-                    i as u32,
-                    tree.get_inputs().len() as u32,
-                ),
-                depth - 1,
-                max_depth,
+                graph,
+                input,
+                positions,
+                values,
                 Some([
                     x_offset,
-                    y_origin
-                        + self
-                            .sprite_creator
-                            .get_sprite_info(sprite_type.clone())
-                            .inputs[i],
+                    y_origin + self.sprite_creator.get_sprite_info(sprite_type).inputs[i],
                 ]),
+                critical_path
+                    .and_then(|path| path.split_first())
+                    .filter(|(first, _)| **first == i)
+                    .map(|(_, rest)| rest),
             );
         }
     }
 
-    fn draw_tree_breadth_first(&mut self, tree: &Gate, y_origin: u32) {
-        let mut queue: VecDeque<&Gate> = VecDeque::new();
-        queue.push_back(tree);
-        let columns = tree.column_sizes();
-        println!("render columns: {:#?}", columns);
-        let mut current_depth = tree.depth();
+    #[allow(dead_code)]
+    fn draw_tree_breadth_first(&mut self, graph: &GateGraph, y_origin: u32) {
+        let mut queue: VecDeque<GateId> = VecDeque::new();
+        queue.push_back(ROOT);
+        let columns = graph.column_sizes();
+        eprintln!("render columns: {:#?}", columns);
+        let mut current_depth = graph.depth();
         let mut remaining_in_level = 1;
         let mut next_level = 0;
-        const gate_padding: u32 = 40;
+        const GATE_PADDING: u32 = 40;
         while !queue.is_empty() {
-            let root = queue.pop_front().unwrap();
+            let root = &graph.nodes[queue.pop_front().unwrap()];
             let x_offset = 100 * (current_depth - 1);
 
-            println!(
+            eprintln!(
                 "In column {}, height should be {}",
                 current_depth - 1,
-                (40 + gate_padding) * columns.get(current_depth as usize - 1).unwrap()
+                (40 + GATE_PADDING) * columns.get(current_depth as usize - 1).unwrap()
             );
 
             remaining_in_level -= 1;
             self.draw(
                 root.sprite_type(),
-                root.get_name(),
+                root.name.clone(),
                 x_offset,
-                y_origin + (40 + gate_padding) * remaining_in_level
-                    - (40 + gate_padding) / 2 * columns.get(current_depth as usize - 1).unwrap(),
+                y_origin + (40 + GATE_PADDING) * remaining_in_level
+                    - (40 + GATE_PADDING) / 2 * columns.get(current_depth as usize - 1).unwrap(),
             );
 
-            for child in root.get_inputs().iter() {
+            for &child in &root.inputs {
                 queue.push_back(child);
                 next_level += 1;
             }
@@ -165,7 +504,12 @@ impl Renderer {
     // }
 
     pub fn draw(&mut self, sprite: Sprite, name: String, x_offset: u32, y_offset: u32) {
-        let sprite_image = self.sprite_creator.get_sprite(sprite.clone());
+        self.elements.push(Element::Sprite {
+            sprite,
+            x: x_offset,
+            y: y_offset,
+        });
+        let sprite_image = self.sprite_creator.get_sprite(sprite);
 
         for i in 0..sprite_image.width() {
             for j in 0..sprite_image.height() {
@@ -183,16 +527,22 @@ impl Renderer {
         }
 
         let size: f32 = 25.0; // arbitrary font size
-        let pixel_size = size.ceil() as usize;
 
-        // 2x scale in x direction to counter the aspect ratio of monospace characters.
-        let scale = Scale {
-            x: size,
-            y: size,
-        };
+        if self.options.overbars {
+            let ascent = self.font.v_metrics(Scale::uniform(size)).ascent as u32;
+            self.label(&name, x_offset + 39, y_offset - 3 - ascent, size);
+            return;
+        }
 
+        let scale = Scale { x: size, y: size };
+        self.elements.push(Element::Text {
+            text: name.clone(),
+            x: (x_offset + 39) as f32,
+            y: y_offset as f32 - 3.0 - self.font.v_metrics(scale).ascent,
+            size,
+            typeset: false,
+        });
 
-        let v_metrics = self.font.v_metrics(scale);
         let offset = point(0.0, 0.0);
 
         let glyphs: Vec<_> = self.font.layout(&name[..], scale, offset).collect();
@@ -204,8 +554,10 @@ impl Renderer {
                 // println!("Pixel height {}", );
                 let origin = x_offset + 39;
                 img.put_pixel(
-                    (origin + x + g.position().x as u32),
-                    ((y_offset + y - 12) as i32 + (g.pixel_bounding_box().unwrap().max.y) as i32 + (g.pixel_bounding_box().unwrap().min.y) as i32) as u32,
+                    origin + x + g.position().x as u32,
+                    ((y_offset + y - 12) as i32
+                        + g.pixel_bounding_box().unwrap().max.y
+                        + g.pixel_bounding_box().unwrap().min.y) as u32,
                     Rgba([brightness, brightness, brightness, 255]),
                 );
             })
@@ -213,34 +565,250 @@ impl Renderer {
         // DrawCallback::new(self, x_offset, y_offset, self.sprite_creator.get_sprite_info(sprite))
     }
 
-    pub fn wire(&mut self, x_origin: u32, y_origin: u32, x_dest: u32, y_dest: u32) {
-        println!(
-            "A wire from {},{} to {},{}",
-            x_origin, y_origin, x_dest, y_dest
-        );
+    /// The colour of a wire carrying `value`, if simulating, else `CRITICAL` if it's on the critical path.
+    fn path_color(&self, value: Option<bool>, on_critical_path: bool) -> Rgba<u8> {
+        if on_critical_path && value.is_none() {
+            CRITICAL
+        } else {
+            value_color(value)
+        }
+    }
 
+    /// A wire from `origin` to `dest`, twice as thick when `heavy`.
+    fn path_wire(&mut self, origin: [u32; 2], dest: [u32; 2], color: Rgba<u8>, heavy: bool) {
+        self.wire(origin[0], origin[1], dest[0], dest[1], color);
+        if heavy {
+            self.wire(
+                origin[0] - 1,
+                origin[1] - 1,
+                dest[0] - 1,
+                dest[1] - 1,
+                color,
+            );
+            let dest_x = dest[0].max(origin[0] + 1);
+            self.wire(origin[0] + 1, origin[1] + 1, dest_x, dest[1] + 1, color);
+        }
+    }
+
+    pub fn wire(
+        &mut self,
+        x_origin: u32,
+        y_origin: u32,
+        x_dest: u32,
+        y_dest: u32,
+        color: Rgba<u8>,
+    ) {
+        self.elements.push(Element::Wire {
+            from: [x_origin, y_origin],
+            to: [x_dest, y_dest],
+            color,
+        });
         let dy: i32 = y_dest as i32 - y_origin as i32;
 
         let output_offset = (dy < 0) as i32 * 2;
 
-        for y in (0)..((dy).abs() + output_offset) {
+        for y in 0..(dy.abs() + output_offset) {
             let pixel_x = x_origin;
             let pixel_y = (y_origin.min(y_dest) as i32 + y) as u32;
-            self.image.put_pixel(pixel_x, pixel_y, Rgba([0, 0, 0, 255]));
-            self.image
-                .put_pixel(pixel_x + 1, pixel_y, Rgba([0, 0, 0, 255]));
+            self.image.put_pixel(pixel_x, pixel_y, color);
+            self.image.put_pixel(pixel_x + 1, pixel_y, color);
         }
 
         for x in 0..(x_dest - x_origin) {
-            let pixel_x = (x_origin + x) as u32;
+            let pixel_x = x_origin + x;
             let pixel_y = (y_origin as i32 + dy) as u32;
-            self.image.put_pixel(pixel_x, pixel_y, Rgba([0, 0, 0, 255]));
-            self.image
-                .put_pixel(pixel_x, pixel_y + 1, Rgba([0, 0, 0, 255]));
+            self.image.put_pixel(pixel_x, pixel_y, color);
+            self.image.put_pixel(pixel_x, pixel_y + 1, color);
         }
     }
 
     pub fn export(&self) {
         self.image.save("output.png").unwrap();
     }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    pub fn into_image(self) -> RgbaImage {
+        self.image
+    }
+
+    /// The diagram drawn so far as an SVG, with the gates and wires as shapes rather than pixels.
+    pub fn svg(&self) -> String {
+        svg::document(
+            self.image.width(),
+            self.image.height(),
+            &self.elements,
+            &self.font,
+        )
+    }
+}
+
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const CRITICAL: Rgba<u8> = Rgba([230, 120, 0, 255]);
+
+/// Green for 1 and red for 0 when simulating, black otherwise.
+fn value_color(value: Option<bool>) -> Rgba<u8> {
+    match value {
+        Some(true) => Rgba([0, 160, 0, 255]),
+        Some(false) => Rgba([210, 0, 0, 255]),
+        None => BLACK,
+    }
+}
+
+fn with_value(label: &str, value: bool) -> String {
+    format!("{} = {}", label, value as u8)
+}
+
+/// Inputs show their value, except the constants whose name already is one.
+fn input_label(name: &str, value: bool) -> String {
+    match name {
+        "0" | "1" => String::from(name),
+        _ => format!("{}={}", name, value as u8),
+    }
+}
+
+/// How far right of its x an output terminal and its label reach.
+fn terminal_width(font: &Font, label: &str, overbars: bool) -> u32 {
+    TERMINAL_LABEL_X + measure_label(font, label, LABEL_SIZE, overbars) + 10
+}
+
+fn measure_label(font: &Font, text: &str, size: f32, overbars: bool) -> u32 {
+    if overbars {
+        label_width(font, text, size)
+    } else {
+        text_width(font, text, size)
+    }
+}
+
+fn title_width(font: &Font, title: Option<&str>) -> u32 {
+    title.map_or(0, |t| text_width(font, t, TITLE_SIZE) + 20)
+}
+
+pub fn load_font() -> Font<'static> {
+    Font::try_from_bytes(include_bytes!("../assets/fonts/cmunrm.ttf"))
+        .expect("error constructing a Font from bytes")
+}
+
+pub fn text_width(font: &Font, text: &str, size: f32) -> u32 {
+    font.layout(text, Scale::uniform(size), point(0.0, 0.0))
+        .filter_map(|g| g.pixel_bounding_box())
+        .map(|bb| bb.max.x)
+        .max()
+        .unwrap_or(0) as u32
+}
+
+/// Draws `text` with its top-left corner at (`x`, `y`), blending the glyph coverage into what's already there.
+pub fn draw_text(
+    image: &mut RgbaImage,
+    font: &Font,
+    text: &str,
+    x: u32,
+    y: u32,
+    size: f32,
+    color: Rgba<u8>,
+) {
+    let scale = Scale::uniform(size);
+    let ascent = font.v_metrics(scale).ascent;
+
+    for g in font.layout(text, scale, point(x as f32, y as f32 + ascent)) {
+        if let Some(bb) = g.pixel_bounding_box() {
+            g.draw(|gx, gy, o| {
+                let pixel_x = bb.min.x + gx as i32;
+                let pixel_y = bb.min.y + gy as i32;
+                if pixel_x < 0
+                    || pixel_y < 0
+                    || pixel_x as u32 >= image.width()
+                    || pixel_y as u32 >= image.height()
+                {
+                    return;
+                }
+                let pixel = image.get_pixel_mut(pixel_x as u32, pixel_y as u32);
+                for channel in 0..3 {
+                    pixel[channel] =
+                        (pixel[channel] as f32 * (1.0 - o) + color[channel] as f32 * o) as u8;
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::parse_boolean_expression;
+    use crate::spritesheet::sprite_info;
+    use crate::timing::{self, DelayTable};
+
+    #[test]
+    fn stubs_on_the_critical_path_are_tinted() {
+        let tree = parse_boolean_expression("ab");
+        let graph = GateGraph::new(&tree);
+        let options = RenderOptions {
+            output_label: tree.get_name(),
+            title: None,
+            wire_labels: false,
+            overbars: false,
+            assignment: None,
+            critical_path: Some(timing::critical_path(&tree, &DelayTable::default())),
+        };
+        let mut renderer = Renderer::new(&graph, options);
+        renderer.draw_tree(&graph);
+
+        let y_origin = graph.adjusted_origin(renderer.image.height() / 2);
+        let x = renderer.column_x[graph.nodes[ROOT].column as usize - 1];
+        let pin = sprite_info(graph.nodes[ROOT].sprite_type()).inputs[1];
+        // past the wire's end, into the stub the AND gate's sprite draws for its first input
+        assert_eq!(*renderer.image.get_pixel(x + 2, y_origin + pin), CRITICAL);
+    }
+
+    #[test]
+    fn circuits_draw_their_critical_wires_heavier() {
+        let circuit = crate::circuit::parse_statements("t = a ^ b; f = t + c").unwrap();
+        let layout = CircuitLayout::new(&circuit);
+        let critical_wires = timing::layout_critical_wires(&layout, &DelayTable::default());
+        let options = RenderOptions {
+            output_label: String::new(),
+            title: None,
+            wire_labels: false,
+            overbars: false,
+            assignment: None,
+            critical_path: None,
+        };
+        let mut renderer = Renderer::for_circuit(&layout, options);
+        renderer.draw_circuit(&layout, &critical_wires);
+
+        let mut checked = 0;
+        for (id, node) in layout.nodes.iter().enumerate() {
+            for pin in 0..node.inputs.len() {
+                let pin_y = match &node.kind {
+                    NodeKind::Gate(gate_type, _) => {
+                        node.y + sprite_info(gate_type.sprite()).inputs[pin]
+                    }
+                    _ => node.y,
+                };
+                // where the wire meets the pin, and the extra rows a heavy wire has below
+                let (end, below) = (
+                    *renderer.image.get_pixel(node.x - 1, pin_y),
+                    *renderer.image.get_pixel(node.x - 1, pin_y + 2),
+                );
+                if critical_wires.contains(&(id, pin)) {
+                    assert_eq!(
+                        (end, below),
+                        (CRITICAL, CRITICAL),
+                        "pin {} of {:?}",
+                        pin,
+                        node.kind
+                    );
+                    checked += 1;
+                } else {
+                    assert_eq!(end, BLACK, "pin {} of {:?}", pin, node.kind);
+                    assert_ne!(below, CRITICAL, "pin {} of {:?}", pin, node.kind);
+                }
+            }
+        }
+        assert_eq!(checked, critical_wires.len());
+        assert!(checked >= 3, "{:?}", critical_wires);
+    }
 }
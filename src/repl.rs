@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use crate::expression_parser::{minterm_assignment, parse_boolean_expression, Gate};
+use crate::gate_graph::GateGraph;
+use crate::renderer::Renderer;
+use crate::stats::Stats;
+use crate::text_renderer::TextRenderer;
+use crate::{aiger, blif, equivalence, graph_export, logisim, minimizer, verilog, vhdl};
+
+/// Kept in the home directory, so history carries over between sessions.
+const HISTORY_FILE: &str = ".logic_diagram_history";
+
+const EXPORT_FORMATS: [&str; 12] = [
+    "svg",
+    "png",
+    "text",
+    "verilog",
+    "verilog-assign",
+    "vhdl",
+    "vhdl-dataflow",
+    "dot",
+    "mermaid",
+    "logisim",
+    "blif",
+    "aiger",
+];
+
+/// Truth tables over more inputs than this are too long to be worth printing.
+const MAX_TABLE_INPUTS: usize = 16;
+
+const HELP: &str = "Type an expression to draw it to output.png. Commands act on the last expression:
+  :simplify              minimizes it and draws the result
+  :table                 prints its truth table
+  :stats                 prints its gate counts and depth
+  :assign a=1,b=0        simulates it with these values, added to any given before (:assign alone clears them)
+  :equiv <expression>    checks it against another expression
+  :export <format> <path> writes it as one of {formats}
+  :help                  shows this
+  :quit                  leaves, as does Ctrl-D";
+
+/// How long `help` lets a line run, as long as the longest of `HELP`'s own,
+/// and how far in the lines it wraps onto start, under the descriptions.
+const HELP_WIDTH: usize = 110;
+const HELP_INDENT: usize = 25;
+
+/// `HELP` with the export formats filled in from `EXPORT_FORMATS`, wrapped to fit.
+fn help() -> String {
+    let mut help = Vec::new();
+    for line in HELP.replace("{formats}", &format_list()).lines() {
+        let mut line = String::from(line);
+        while line.len() > HELP_WIDTH {
+            let split = line[..HELP_WIDTH].rfind(' ').unwrap();
+            help.push(String::from(&line[..split]));
+            line = format!("{}{}", " ".repeat(HELP_INDENT), &line[split + 1..]);
+        }
+        help.push(line);
+    }
+    help.join("\n")
+}
+
+/// The export formats as a sentence lists them: `svg, png, ... blif or aiger`.
+fn format_list() -> String {
+    let (last, rest) = EXPORT_FORMATS.split_last().unwrap();
+    format!("{} or {}", rest.join(", "), last)
+}
+
+/// Reads expressions and commands until the user quits, drawing each expression as it's typed.
+/// The command line options that shape the diagram, like `--label` and `--preview`, apply to every drawing.
+pub fn run(args: &[String]) {
+    let mut editor = DefaultEditor::new().unwrap_or_else(|error| {
+        eprintln!("Couldn't start the line editor: {}", error);
+        std::process::exit(1);
+    });
+    let history = history_path();
+    if let Some(path) = &history {
+        // there's nothing to load the first time
+        let _ = editor.load_history(path);
+    }
+
+    let mut session = Session {
+        args,
+        tree: None,
+        assignment: crate::render_options(args, String::new(), &[])
+            .assignment
+            .unwrap_or_default(),
+    };
+    println!("Type an expression to draw it, or :help for the commands");
+    loop {
+        match editor.readline("> ") {
+            Ok(line) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let _ = editor.add_history_entry(line);
+                if line == ":quit" || line == ":q" {
+                    break;
+                }
+                // the parser and renderer panic on input they can't handle, which shouldn't end the session
+                if panic::catch_unwind(AssertUnwindSafe(|| session.run(line))).is_err() {
+                    eprintln!("Couldn't run {}", line);
+                }
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => {
+                eprintln!("Couldn't read a line: {}", error);
+                break;
+            }
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(error) = editor.save_history(path) {
+            eprintln!("Couldn't save the history to {}: {}", path.display(), error);
+        }
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+struct Session<'a> {
+    args: &'a [String],
+    /// The last expression typed, as simplified since.
+    tree: Option<Gate>,
+    /// Input values gathered from `--assign` and `:assign`, used whenever they cover every input.
+    assignment: HashMap<String, bool>,
+}
+
+impl<'a> Session<'a> {
+    fn run(&mut self, line: &str) {
+        if !line.starts_with(':') {
+            self.tree = Some(parse_boolean_expression(line));
+            self.render();
+            return;
+        }
+
+        let (command, rest) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(command, rest)| (command, rest.trim()));
+        match command {
+            ":help" => println!("{}", help()),
+            ":assign" => self.assign(rest),
+            _ => {
+                let tree = match &self.tree {
+                    Some(tree) => tree,
+                    None => {
+                        println!("Type an expression first");
+                        return;
+                    }
+                };
+                match command {
+                    ":simplify" => {
+                        let simplified = minimizer::minimize(tree);
+                        println!("Simplified to {}", simplified.get_name());
+                        self.tree = Some(simplified);
+                        self.render();
+                    }
+                    ":table" => self.print_table(tree),
+                    ":stats" => println!("{}", Stats::of(tree).describe()),
+                    ":equiv" if !rest.is_empty() => {
+                        match equivalence::find_expression_counterexample(tree, rest) {
+                            None => println!("{} and {} are equivalent", tree.get_name(), rest),
+                            Some(counterexample) => println!(
+                                "{} and {} are not equivalent: {}",
+                                tree.get_name(),
+                                rest,
+                                counterexample.describe()
+                            ),
+                        }
+                    }
+                    ":export" => match rest.split_once(char::is_whitespace) {
+                        Some((format, path)) => self.export(tree, format, path.trim()),
+                        None => println!(
+                            "Usage: :export <format> <path>, where the format is {}",
+                            format_list()
+                        ),
+                    },
+                    ":equiv" => println!("Usage: :equiv <expression>"),
+                    _ => println!("There's no command {}; :help lists them", command),
+                }
+            }
+        }
+    }
+
+    /// Adds `text`'s values to the assignment, or clears it when `text` is empty, and redraws.
+    fn assign(&mut self, text: &str) {
+        if text.is_empty() {
+            self.assignment.clear();
+            println!("Cleared the assignment");
+        } else {
+            match crate::expression_parser::parse_assignment(text) {
+                Ok(values) => self.assignment.extend(values),
+                Err(error) => {
+                    println!("Couldn't read the assignment: {}", error);
+                    return;
+                }
+            }
+        }
+        if self.tree.is_some() {
+            self.render();
+        }
+    }
+
+    /// Draws the expression, simulated when the assignment covers its inputs, to `output.png`.
+    fn render(&self) {
+        let tree = self.tree.as_ref().unwrap();
+        let image = self.draw(tree).into_image();
+        if let Err(error) = image.save("output.png") {
+            println!("Couldn't write output.png: {}", error);
+            return;
+        }
+        crate::preview(self.args, &image);
+        println!("Drew {} to output.png", tree.get_name());
+    }
+
+    fn draw(&self, tree: &Gate) -> Renderer {
+        let mut options = crate::render_options(self.args, tree.get_name(), &[]);
+        options.assignment = None;
+        if !self.assignment.is_empty() {
+            let missing: Vec<String> = tree
+                .input_names()
+                .into_iter()
+                .filter(|name| !self.assignment.contains_key(name))
+                .collect();
+            if missing.is_empty() {
+                println!(
+                    "{} = {}",
+                    options.output_label,
+                    tree.evaluate(&self.assignment) as u8
+                );
+                options.assignment = Some(self.assignment.clone());
+            } else {
+                println!("Not simulating, since {} have no value", missing.join(","));
+            }
+        }
+        let graph = GateGraph::new(tree);
+        let mut renderer = Renderer::new(&graph, options);
+        renderer.draw_tree(&graph);
+        renderer
+    }
+
+    fn print_table(&self, tree: &Gate) {
+        let variables = tree.input_names();
+        if variables.len() > MAX_TABLE_INPUTS {
+            println!(
+                "{} inputs make {} rows, too many to print",
+                variables.len(),
+                1_u64 << variables.len()
+            );
+            return;
+        }
+        let output = crate::output_port_name(self.args, &variables);
+        println!("{} | {}", variables.join(" "), output);
+        for minterm in 0..1_u64 << variables.len() {
+            let assignment = minterm_assignment(&variables, minterm);
+            // each value sits under the last character of its variable's name
+            let values: Vec<String> = variables
+                .iter()
+                .map(|name| format!("{:>width$}", assignment[name] as u8, width = name.len()))
+                .collect();
+            println!(
+                "{} | {:>width$}",
+                values.join(" "),
+                tree.evaluate(&assignment) as u8,
+                width = output.len()
+            );
+        }
+    }
+
+    fn export(&self, tree: &Gate, format: &str, path: &str) {
+        let module = crate::module_name(path);
+        let output = crate::output_port_name(self.args, &tree.input_names());
+        let text = match format {
+            "svg" => self.draw(tree).svg(),
+            "png" => {
+                match self.draw(tree).into_image().save(path) {
+                    Ok(()) => println!("Wrote png to {}", path),
+                    Err(error) => println!("Couldn't write {}: {}", path, error),
+                }
+                return;
+            }
+            "text" => {
+                let label = crate::render_options(self.args, tree.get_name(), &[]).output_label;
+                TextRenderer::new(&GateGraph::new(tree), &label).to_string()
+            }
+            "verilog" => verilog::structural(tree, &module, &output),
+            "verilog-assign" => verilog::behavioural(tree, &module, &output),
+            "vhdl" => vhdl::structural(tree, &module, &output),
+            "vhdl-dataflow" => vhdl::dataflow(tree, &module, &output),
+            "dot" => graph_export::dot(tree),
+            "mermaid" => graph_export::mermaid(tree),
+            "logisim" => logisim::circ(tree, &module, &output),
+            "blif" => blif::blif(tree, &module, &output),
+            "aiger" => aiger::aag(tree, &output),
+            _ => {
+                println!("Can't export {}; the formats are {}", format, format_list());
+                return;
+            }
+        };
+        match fs::write(path, text) {
+            Ok(()) => println!("Wrote {} to {}", format, path),
+            Err(error) => println!("Couldn't write {}: {}", path, error),
+        }
+    }
+}
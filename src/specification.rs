@@ -0,0 +1,301 @@
+use crate::expression_parser::Gate;
+use crate::minimizer::{espresso, Cover, Cube};
+
+/// A function given by its truth table rather than an expression:
+/// the rows where it is 1, or for a maxterm list the rows where it is 0, and the rows where it doesn't matter,
+/// numbered with the first variable as the most significant bit.
+#[derive(Debug, Clone)]
+pub struct Specification {
+    pub name: String,
+    pub variables: Vec<String>,
+    pub rows: Vec<u64>,
+    /// Whether `rows` are the maxterms rather than the minterms.
+    pub maxterms: bool,
+    pub dont_care: Vec<u64>,
+}
+
+/// The most variables a table can have, as many as a `Cube` holds.
+const MAX_VARIABLES: usize = 64;
+
+impl Specification {
+    /// Synthesizes a two-level circuit by minimizing the ON-set with the don't-cares free to go either way.
+    pub fn synthesize(&self) -> Gate {
+        let n = self.variables.len();
+        let to_cover = |rows: Vec<u64>| {
+            Cover::new(
+                self.variables.clone(),
+                rows.into_iter().map(|m| Cube::minterm(m, n)).collect(),
+            )
+        };
+
+        // the rows left unlisted are the complement of the listed ones and the don't-cares,
+        // which the cover finds without going through all 2^n of them
+        let listed = to_cover(self.rows.clone());
+        let dont_care = to_cover(self.dont_care.clone());
+        let unlisted =
+            to_cover(self.rows.iter().chain(&self.dont_care).copied().collect()).complement();
+        let (on, off) = if self.maxterms {
+            (unlisted, listed)
+        } else {
+            (listed, unlisted)
+        };
+
+        espresso(&on, &dont_care, &off).to_gate()
+    }
+}
+
+/// Minterm lists start with `Σ`/`Π`, or have one of the row list forms straight after their `=`,
+/// which tells them apart from statement lists assigning expressions.
+pub fn is_minterm_list(input: &str) -> bool {
+    let mut condensed = String::from(input);
+    condensed.retain(|c| !c.is_whitespace());
+    match condensed.find('=') {
+        Some(equals) => ["Σ", "Π", "summ(", "productM(", "m(", "M("]
+            .iter()
+            .any(|prefix| condensed[equals + 1..].starts_with(prefix)),
+        None => condensed.starts_with('Σ') || condensed.starts_with('Π'),
+    }
+}
+
+fn parse_number_list(list: &str) -> Result<Vec<u64>, String> {
+    if list.is_empty() {
+        return Ok(Vec::new());
+    }
+    list.split(',')
+        .map(|n| {
+            n.parse::<u64>()
+                .map_err(|_| format!("{} is not a row number", n))
+        })
+        .collect()
+}
+
+/// Splits `prefix(contents)rest` into `contents` and `rest`.
+fn take_group<'a>(input: &'a str, prefixes: &[&str]) -> Option<(&'a str, &'a str)> {
+    let prefix = prefixes.iter().find(|p| input.starts_with(*p))?;
+    let after = &input[prefix.len()..];
+    let close = after.find(')')?;
+    Some((&after[..close], &after[close + 1..]))
+}
+
+/// Parses `f(a,b,c) = Σm(1,3,5,7) + d(0)` or the maxterm form `f(a,b,c) = ΠM(0,2,4,6) + d(1)`.
+/// `sum m(...)`/`m(...)` and `product M(...)`/`M(...)` work where `Σ` and `Π` are hard to type.
+/// Without the `f(a,b,c) =` header the variables are `a`, `b`, `c`, ... as many as the row numbers need, up to `z`.
+pub fn parse_minterm_list(input: &str) -> Result<Specification, String> {
+    let mut condensed = String::from(input);
+    condensed.retain(|c| !c.is_whitespace());
+
+    let (name, mut variables, body) = match condensed.find('=') {
+        Some(equals) => {
+            let header = &condensed[..equals];
+            let (name, arguments) = match header.find('(') {
+                Some(open) if header.ends_with(')') => {
+                    (&header[..open], &header[open + 1..header.len() - 1])
+                }
+                _ => (header, ""),
+            };
+            let variables: Vec<String> = arguments
+                .split(',')
+                .filter(|v| !v.is_empty())
+                .map(String::from)
+                .collect();
+            (String::from(name), variables, &condensed[equals + 1..])
+        }
+        None => (String::from("f"), Vec::new(), &condensed[..]),
+    };
+
+    let (rows, rest, maxterms) =
+        if let Some((rows, rest)) = take_group(body, &["Σm(", "summ(", "m("]) {
+            (rows, rest, false)
+        } else if let Some((rows, rest)) = take_group(body, &["ΠM(", "productM(", "M("]) {
+            (rows, rest, true)
+        } else {
+            return Err(format!(
+                "expected Σm(...) or ΠM(...) after the =, found {}",
+                body
+            ));
+        };
+    let rows = parse_number_list(rows)?;
+
+    let dont_care = if rest.is_empty() {
+        Vec::new()
+    } else {
+        let separator = if maxterms { "*" } else { "+" };
+        let rest = rest.strip_prefix(separator).unwrap_or(rest);
+        match take_group(rest, &["d("]) {
+            Some((list, "")) => parse_number_list(list)?,
+            _ => return Err(format!("expected d(...) for the don't-cares, found {}", rest)),
+        }
+    };
+
+    let largest = rows.iter().chain(dont_care.iter()).copied().max().unwrap_or(0);
+    if variables.is_empty() {
+        let needed = (64 - largest.leading_zeros()).max(1) as u8;
+        if needed > 26 {
+            return Err(format!(
+                "row {} needs {} variables, more than a to z; name them in a header like f(x1,x2,...) =",
+                largest, needed
+            ));
+        }
+        variables = (0..needed).map(|i| ((b'a' + i) as char).to_string()).collect();
+    }
+    if variables.len() > MAX_VARIABLES {
+        return Err(too_many_variables(variables.len()));
+    }
+    if largest.checked_shr(variables.len() as u32).unwrap_or(0) != 0 {
+        return Err(format!(
+            "row {} doesn't fit in a table over {} variables",
+            largest,
+            variables.len()
+        ));
+    }
+
+    Ok(Specification {
+        name,
+        variables,
+        rows,
+        maxterms,
+        dont_care,
+    })
+}
+
+fn too_many_variables(count: usize) -> String {
+    format!(
+        "the table has {} variables, more than the {} it can have",
+        count, MAX_VARIABLES
+    )
+}
+
+/// Parses a CSV truth table: a header naming the inputs and then the output,
+/// and one row per line with `0`/`1` inputs and a `0`, `1`, `x` or `-` output.
+/// Rows the table leaves out are 0.
+pub fn parse_truth_table_csv(input: &str) -> Result<Specification, String> {
+    let mut lines = input
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'));
+
+    let header: Vec<String> = lines
+        .next()
+        .ok_or("the truth table is empty")?
+        .split(',')
+        .map(|c| String::from(c.trim()))
+        .collect();
+    if header.len() < 2 {
+        return Err(String::from(
+            "the truth table needs at least one input column and an output column",
+        ));
+    }
+    let (variables, name) = header.split_at(header.len() - 1);
+    if variables.len() > MAX_VARIABLES {
+        return Err(too_many_variables(variables.len()));
+    }
+
+    let mut on = Vec::new();
+    let mut dont_care = Vec::new();
+    for line in lines {
+        let cells: Vec<&str> = line.split(',').map(|c| c.trim()).collect();
+        if cells.len() != header.len() {
+            return Err(format!(
+                "row {} has {} columns, the header has {}",
+                line,
+                cells.len(),
+                header.len()
+            ));
+        }
+
+        let mut row = 0;
+        for cell in &cells[..variables.len()] {
+            row = match *cell {
+                "0" => row << 1,
+                "1" => (row << 1) | 1,
+                other => return Err(format!("{} is not an input value in row {}", other, line)),
+            };
+        }
+        match cells[variables.len()] {
+            "1" => on.push(row),
+            "x" | "X" | "-" => dont_care.push(row),
+            "0" => {}
+            other => return Err(format!("{} is not an output value in row {}", other, line)),
+        }
+    }
+
+    Ok(Specification {
+        name: name[0].clone(),
+        variables: variables.to_vec(),
+        rows: on,
+        maxterms: false,
+        dont_care,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::minterm_assignment;
+
+    #[test]
+    fn maxterms_give_the_rows_left_over() {
+        let specification = parse_minterm_list("f(a,b,c) = ΠM(0,2,4,6) * d(1)").unwrap();
+        assert_eq!(specification.rows, vec![0, 2, 4, 6]);
+        assert!(specification.maxterms);
+        assert_eq!(specification.dont_care, vec![1]);
+        let tree = specification.synthesize();
+        for row in [0, 2, 3, 4, 5, 6, 7] {
+            let value = tree.evaluate(&minterm_assignment(&specification.variables, row));
+            assert_eq!(value, [3, 5, 7].contains(&row), "row {}", row);
+        }
+    }
+
+    #[test]
+    fn unnamed_variables_stop_at_z() {
+        let specification = parse_minterm_list("Σm(0, 33554432)").unwrap();
+        assert_eq!(specification.variables.len(), 26);
+        assert_eq!(specification.variables[25], "z");
+
+        let error = parse_minterm_list("Σm(67108864)").unwrap_err();
+        assert!(error.contains("needs 27 variables"), "{}", error);
+        let named: Vec<String> = (1..=27).map(|i| format!("x{}", i)).collect();
+        let header = format!("f({}) = Σm(67108864)", named.join(","));
+        assert_eq!(parse_minterm_list(&header).unwrap().variables, named);
+    }
+
+    #[test]
+    fn synthesis_covers_the_on_set_and_misses_the_off_set() {
+        let specification = parse_minterm_list("f(a,b,c,d) = Σm(1,3,5,7,9,11,13) + d(15)").unwrap();
+        let tree = specification.synthesize();
+        for row in 0..16 {
+            let value = tree.evaluate(&minterm_assignment(&specification.variables, row));
+            if specification.rows.contains(&row) {
+                assert!(value, "row {}", row);
+            } else if !specification.dont_care.contains(&row) {
+                assert!(!value, "row {}", row);
+            }
+        }
+    }
+
+    #[test]
+    fn wide_tables_synthesize_without_listing_every_row() {
+        let specification = parse_minterm_list("Σm(0, 33554432)").unwrap();
+        let tree = specification.synthesize();
+        for row in [0, 1, 33554432, 33554433, 67108863] {
+            let value = tree.evaluate(&minterm_assignment(&specification.variables, row));
+            assert_eq!(value, row == 0 || row == 33554432, "row {}", row);
+        }
+
+        let named = |count: usize| {
+            (1..=count)
+                .map(|i| format!("x{}", i))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let header = format!("f({}) = Σm(1)", named(64));
+        let widest = parse_minterm_list(&header).unwrap();
+        let tree = widest.synthesize();
+        assert!(tree.evaluate(&minterm_assignment(&widest.variables, 1)));
+        assert!(!tree.evaluate(&minterm_assignment(&widest.variables, 3)));
+        let error = parse_minterm_list(&format!("f({}) = Σm(1)", named(65))).unwrap_err();
+        assert!(error.contains("65 variables"), "{}", error);
+        let error = parse_truth_table_csv(&format!("{},f\n", named(65))).unwrap_err();
+        assert!(error.contains("65 variables"), "{}", error);
+    }
+}
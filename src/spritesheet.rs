@@ -1,9 +1,7 @@
-use core::num;
-use image::{
-    imageops, math, open, DynamicImage, GenericImageView, ImageBuffer, Pixel, Rgb, RgbImage, Rgba,
-    RgbaImage, SubImage,
-};
-use std::{collections::HashMap, vec};
+use image::DynamicImage;
+use std::collections::HashMap;
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Hash, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Sprite {
     AND,
@@ -13,7 +11,8 @@ pub enum Sprite {
     XOR,
     XNOR,
     NOT,
-    INPUT
+    INPUT,
+    OUTPUT,
 }
 
 #[derive(Hash, PartialEq, Eq, Debug)]
@@ -24,27 +23,14 @@ pub struct SpriteInfo {
     pub outputs: Vec<u32>,
 }
 
-impl SpriteInfo {
-    pub fn new(width: u32, height: u32, inputs: Vec<u32>, outputs: Vec<u32>) -> SpriteInfo {
-        SpriteInfo {
-            width,
-            height,
-            inputs,
-            outputs,
-        }
-    }
-}
-
 pub struct SpriteCreator {
-    spritesheet: DynamicImage,
     already_generated: HashMap<Sprite, DynamicImage>,
 }
 
 impl SpriteCreator {
     pub fn new() -> SpriteCreator {
-
         let mut sprite_map = HashMap::new();
-        for sprite in vec![
+        for sprite in [
             Sprite::AND,
             Sprite::NAND,
             Sprite::OR,
@@ -53,6 +39,7 @@ impl SpriteCreator {
             Sprite::XNOR,
             Sprite::NOT,
             Sprite::INPUT,
+            Sprite::OUTPUT,
         ] {
             let filename = match sprite {
                 Sprite::AND => "assets/AND.png",
@@ -62,73 +49,84 @@ impl SpriteCreator {
                 Sprite::XOR => "assets/XOR.png",
                 Sprite::XNOR => "assets/XNOR.png",
                 Sprite::NOT => "assets/NOT.png",
-                Sprite::INPUT => "assets/INPUT.png"
+                Sprite::INPUT => "assets/INPUT.png",
+                Sprite::OUTPUT => "assets/OUTPUT.png",
             };
 
             let img = image::open(filename).unwrap();
-            sprite_map.insert(sprite.clone(), img);
-        };
-        
+            sprite_map.insert(sprite, img);
+        }
+
         SpriteCreator {
-            spritesheet: image::open("spritesheet.png").unwrap(),
             already_generated: sprite_map,
         }
     }
 
     pub fn get_sprite_info(&self, sprite_name: Sprite) -> SpriteInfo {
-        match sprite_name {
-            Sprite::AND => SpriteInfo {
-                width: 90,
-                height: 40,
-                inputs: vec![9, 29],
-                outputs: vec![19],
-            },
-            Sprite::NAND => SpriteInfo {
-                width: 90,
-                height: 40,
-                inputs: vec![9, 29],
-                outputs: vec![19],
-            },
-            Sprite::OR => SpriteInfo {
-                width: 90,
-                height: 40,
-                inputs: vec![9, 29],
-                outputs: vec![19],
-            },
-            Sprite::NOR => SpriteInfo {
-                width: 90,
-                height: 40,
-                inputs: vec![9, 29],
-                outputs: vec![19],
-            },
-            Sprite::XOR => SpriteInfo {
-                width: 90,
-                height: 40,
-                inputs: vec![9, 29],
-                outputs: vec![19],
-            },
-            Sprite::XNOR => SpriteInfo {
-                width: 90,
-                height: 40,
-                inputs: vec![9, 29],
-                outputs: vec![19],
-            },
-            Sprite::NOT => SpriteInfo {
-                width: 90,
-                height: 40,
-                inputs: vec![21],
-                outputs: vec![21],
-            },
-            Sprite::INPUT => SpriteInfo {
-                width: 90,
-                height: 40,
-                inputs: vec![],
-                outputs: vec![21],
-            },
-        }
+        sprite_info(sprite_name)
     }
 
     pub fn get_sprite(&self, sprite_name: Sprite) -> &DynamicImage {
-        return self.already_generated.get(&sprite_name).unwrap();
+        self.already_generated.get(&sprite_name).unwrap()
+    }
+}
+
+/// Pin positions don't depend on the loaded images, so layout code can ask without a `SpriteCreator`.
+pub fn sprite_info(sprite_name: Sprite) -> SpriteInfo {
+    match sprite_name {
+        Sprite::AND => SpriteInfo {
+            width: 90,
+            height: 40,
+            inputs: vec![9, 29],
+            outputs: vec![19],
+        },
+        Sprite::NAND => SpriteInfo {
+            width: 90,
+            height: 40,
+            inputs: vec![9, 29],
+            outputs: vec![19],
+        },
+        Sprite::OR => SpriteInfo {
+            width: 90,
+            height: 40,
+            inputs: vec![9, 29],
+            outputs: vec![19],
+        },
+        Sprite::NOR => SpriteInfo {
+            width: 90,
+            height: 40,
+            inputs: vec![9, 29],
+            outputs: vec![19],
+        },
+        Sprite::XOR => SpriteInfo {
+            width: 90,
+            height: 40,
+            inputs: vec![9, 29],
+            outputs: vec![19],
+        },
+        Sprite::XNOR => SpriteInfo {
+            width: 90,
+            height: 40,
+            inputs: vec![9, 29],
+            outputs: vec![19],
+        },
+        Sprite::NOT => SpriteInfo {
+            width: 90,
+            height: 40,
+            inputs: vec![21],
+            outputs: vec![21],
+        },
+        Sprite::INPUT => SpriteInfo {
+            width: 90,
+            height: 40,
+            inputs: vec![],
+            outputs: vec![21],
+        },
+        Sprite::OUTPUT => SpriteInfo {
+            width: 90,
+            height: 40,
+            inputs: vec![19],
+            outputs: vec![],
+        },
     }
 }
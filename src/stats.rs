@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use crate::expression_parser::{Gate, GateType};
+use crate::gate_graph::{GateGraph, GateNode};
+
+/// The gate types in the order they're reported.
+const REPORTED_TYPES: [GateType; 7] = [
+    GateType::AND,
+    GateType::OR,
+    GateType::NOT,
+    GateType::NAND,
+    GateType::NOR,
+    GateType::XOR,
+    GateType::XNOR,
+];
+
+/// Size and shape figures for comparing implementations of the same function.
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub gate_counts: Vec<(GateType, usize)>,
+    pub gates: usize,
+    pub transistors: usize,
+    /// Gates on the longest path from an input to the output; `GateGraph::depth` counts the inputs too.
+    pub depth: u32,
+    pub max_fan_in: usize,
+    /// The most gate inputs any one variable or gate drives.
+    pub max_fan_out: usize,
+    /// Variable occurrences, leaving out the constants `0` and `1`.
+    pub literals: usize,
+    pub columns: Vec<u32>,
+}
+
+impl Stats {
+    pub fn of(tree: &Gate) -> Stats {
+        let graph = GateGraph::new(tree);
+        let mut uses: HashMap<&str, usize> = HashMap::new();
+        for node in &graph.nodes {
+            if node.gate_type == GateType::INPUT {
+                *uses.entry(&node.name).or_insert(0) += 1;
+            }
+        }
+        let logic: Vec<&GateNode> = graph
+            .nodes
+            .iter()
+            .filter(|node| node.gate_type != GateType::INPUT)
+            .collect();
+
+        Stats {
+            gate_counts: REPORTED_TYPES
+                .iter()
+                .map(|&t| (t, logic.iter().filter(|node| node.gate_type == t).count()))
+                .filter(|&(_, count)| count > 0)
+                .collect(),
+            gates: logic.len(),
+            transistors: logic
+                .iter()
+                .map(|node| transistors(node.gate_type, node.inputs.len()))
+                .sum(),
+            depth: graph.depth() - 1,
+            max_fan_in: logic
+                .iter()
+                .map(|node| node.inputs.len())
+                .max()
+                .unwrap_or(0),
+            // every gate in a tree feeds exactly one other, so only variables fan out further
+            max_fan_out: uses.values().copied().max().unwrap_or(0),
+            literals: graph
+                .nodes
+                .iter()
+                .filter(|node| {
+                    node.gate_type == GateType::INPUT && node.name != "0" && node.name != "1"
+                })
+                .count(),
+            columns: graph.column_sizes(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let counts: Vec<String> = self
+            .gate_counts
+            .iter()
+            .map(|(gate_type, count)| format!("{} {}", count, gate_type))
+            .collect();
+        let counts = if counts.is_empty() {
+            String::from("none")
+        } else {
+            counts.join(", ")
+        };
+        format!(
+            "Gates: {} ({})\n\
+             Transistors (CMOS estimate): {}\n\
+             Logic depth: {}\n\
+             Max fan-in: {}\n\
+             Max fan-out: {}\n\
+             Literals: {}\n\
+             Column sizes: {:?}",
+            self.gates,
+            counts,
+            self.transistors,
+            self.depth,
+            self.max_fan_in,
+            self.max_fan_out,
+            self.literals,
+            self.columns
+        )
+    }
+
+    pub fn to_json(&self) -> String {
+        let counts: Vec<String> = self
+            .gate_counts
+            .iter()
+            .map(|(gate_type, count)| format!("\"{}\": {}", gate_type, count))
+            .collect();
+        let columns: Vec<String> = self.columns.iter().map(u32::to_string).collect();
+        format!(
+            "{{\n  \"gates\": {},\n  \"gate_counts\": {{{}}},\n  \"transistors\": {},\n  \"depth\": {},\n  \
+             \"max_fan_in\": {},\n  \"max_fan_out\": {},\n  \"literals\": {},\n  \"columns\": [{}]\n}}\n",
+            self.gates,
+            counts.join(", "),
+            self.transistors,
+            self.depth,
+            self.max_fan_in,
+            self.max_fan_out,
+            self.literals,
+            columns.join(", ")
+        )
+    }
+}
+
+/// Static CMOS transistor counts: an inverter is 2, a NAND or NOR takes a pair per input,
+/// AND and OR are those followed by an inverter, and a two-input XOR or XNOR is 12.
+fn transistors(gate_type: GateType, fan_in: usize) -> usize {
+    match gate_type {
+        GateType::NOT => 2,
+        GateType::NAND | GateType::NOR => 2 * fan_in,
+        GateType::AND | GateType::OR => 2 * fan_in + 2,
+        GateType::XOR | GateType::XNOR => 12 * (fan_in - 1),
+        GateType::INPUT => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::parse_boolean_expression;
+
+    #[test]
+    fn counts_gates_and_measures_depth() {
+        let stats = Stats::of(&parse_boolean_expression("ab + c'(a ^ b)"));
+        assert_eq!(
+            stats.gate_counts,
+            vec![
+                (GateType::AND, 2),
+                (GateType::OR, 1),
+                (GateType::NOT, 1),
+                (GateType::XOR, 1)
+            ]
+        );
+        assert_eq!(stats.gates, 5);
+        // two 2-input ANDs at 6, an OR at 6, the inverter and a 12 transistor XOR
+        assert_eq!(stats.transistors, 32);
+        // OR, AND and XOR between a and the output
+        assert_eq!(stats.depth, 3);
+        assert_eq!(stats.max_fan_in, 2);
+        assert_eq!(stats.max_fan_out, 2);
+        assert_eq!(stats.literals, 5);
+        assert_eq!(stats.columns, vec![3, 4, 2, 1]);
+        assert!(stats
+            .to_json()
+            .contains("\"gate_counts\": {\"AND\": 2, \"OR\": 1"));
+    }
+
+    #[test]
+    fn constants_and_lone_variables_have_no_gates() {
+        let stats = Stats::of(&parse_boolean_expression("a"));
+        assert_eq!((stats.gates, stats.depth, stats.literals), (0, 0, 1));
+        assert!(stats.describe().starts_with("Gates: 0 (none)"));
+
+        let stats = Stats::of(&parse_boolean_expression("a1 + 0"));
+        assert_eq!((stats.gates, stats.depth, stats.literals), (2, 2, 1));
+    }
+}
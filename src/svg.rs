@@ -0,0 +1,240 @@
+use image::Rgba;
+use rusttype::{Font, Scale};
+
+use crate::spritesheet::Sprite;
+use crate::typesetting::{label_marks, Mark};
+
+/// Gate outlines are drawn this thick, like the sprites' strokes; wires and pin stubs are 2 pixels.
+const OUTLINE_WIDTH: u32 = 3;
+const WIRE_WIDTH: u32 = 2;
+const FONT_FAMILY: &str = "CMU Serif, serif";
+
+/// Something a `Renderer` has drawn, in the image's pixel coordinates,
+/// kept so the same diagram can be written out as vectors.
+#[derive(Debug, Clone)]
+pub enum Element {
+    Sprite {
+        sprite: Sprite,
+        x: u32,
+        y: u32,
+    },
+    /// A wire as `Renderer::wire` draws it: from `from` up or down to the height of `to`, then along to it.
+    Wire {
+        from: [u32; 2],
+        to: [u32; 2],
+        color: Rgba<u8>,
+    },
+    Junction {
+        x: u32,
+        y: u32,
+        color: Rgba<u8>,
+    },
+    /// Text with its top-left corner at (`x`, `y`), set with overbars and subscripts when `typeset`.
+    Text {
+        text: String,
+        x: f32,
+        y: f32,
+        size: f32,
+        typeset: bool,
+    },
+}
+
+/// An SVG of `elements` over a white page of the rendered image's size.
+pub fn document(width: u32, height: u32, elements: &[Element], font: &Font) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n  \
+         <rect width=\"{0}\" height=\"{1}\" fill=\"white\"/>\n",
+        width, height
+    );
+    for element in elements {
+        match element {
+            Element::Sprite { sprite, x, y } => {
+                svg.push_str(&format!(
+                    "  <g transform=\"translate({},{})\" fill=\"none\" stroke=\"black\">\n",
+                    x, y
+                ));
+                for (path, width) in sprite_paths(*sprite) {
+                    svg.push_str(&format!(
+                        "    <path d=\"{}\" stroke-width=\"{}\"/>\n",
+                        path, width
+                    ));
+                }
+                svg.push_str("  </g>\n");
+            }
+            Element::Wire { from, to, color } => {
+                // a drawn wire covers the two pixel columns from its x and the two pixel rows from its y
+                let (x, from_y, to_y) = (from[0] + 1, from[1] + 1, to[1] + 1);
+                svg.push_str(&format!(
+                    "  <polyline points=\"{},{} {},{} {},{}\" fill=\"none\" stroke=\"{}\" \
+                     stroke-width=\"{}\" stroke-linecap=\"square\"/>\n",
+                    x,
+                    from_y,
+                    x,
+                    to_y,
+                    to[0],
+                    to_y,
+                    rgb(*color),
+                    WIRE_WIDTH
+                ));
+            }
+            Element::Junction { x, y, color } => svg.push_str(&format!(
+                "  <rect x=\"{}\" y=\"{}\" width=\"6\" height=\"6\" fill=\"{}\"/>\n",
+                *x as i32 - 2,
+                *y as i32 - 2,
+                rgb(*color)
+            )),
+            Element::Text {
+                text,
+                x,
+                y,
+                size,
+                typeset,
+            } => {
+                let marks = if *typeset {
+                    label_marks(font, text, *x as u32, *y as u32, *size)
+                } else {
+                    vec![Mark::Text {
+                        text: text.clone(),
+                        x: *x,
+                        y: *y,
+                        size: *size,
+                    }]
+                };
+                for mark in marks {
+                    svg.push_str(&text_mark(font, &mark));
+                }
+            }
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn text_mark(font: &Font, mark: &Mark) -> String {
+    match mark {
+        Mark::Text { text, x, y, size } => {
+            let baseline = y + font.v_metrics(Scale::uniform(*size)).ascent;
+            format!(
+                "  <text x=\"{:.1}\" y=\"{:.1}\" font-family=\"{}\" font-size=\"{}\">{}</text>\n",
+                x,
+                baseline,
+                FONT_FAMILY,
+                size,
+                escape(text)
+            )
+        }
+        Mark::Bar {
+            x,
+            end,
+            y,
+            thickness,
+        } => format!(
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>\n",
+            x,
+            y - thickness + 1,
+            (end - x).max(0),
+            thickness
+        ),
+    }
+}
+
+/// The outline and pin stubs of each sprite as paths, traced from the sprite images
+/// so that pins meet wires at the heights `sprite_info` gives.
+fn sprite_paths(sprite: Sprite) -> Vec<(&'static str, u32)> {
+    const AND_BODY: &str = "M26.5 1 H45 A18.5 18.5 0 0 1 45 38 H26.5 Z";
+    const OR_BODY: &str = "M21 1 H43 Q58 5 65.5 20 Q58 35 43 39 H21 Q35 20 21 1 Z";
+    const XOR_BACK: &str = "M15 1 Q29 20 15 39";
+    const TWO_INPUTS: &str = "M0 10 H27 M0 30 H27";
+    const BUBBLE_AT_20: &str = "M74 20 A4 4 0 0 1 66 20 A4 4 0 0 1 74 20";
+    match sprite {
+        Sprite::AND => vec![
+            (AND_BODY, OUTLINE_WIDTH),
+            (TWO_INPUTS, WIRE_WIDTH),
+            ("M64.5 20 H90", WIRE_WIDTH),
+        ],
+        Sprite::NAND => vec![
+            (AND_BODY, OUTLINE_WIDTH),
+            (BUBBLE_AT_20, WIRE_WIDTH),
+            (TWO_INPUTS, WIRE_WIDTH),
+            ("M75 20 H90", WIRE_WIDTH),
+        ],
+        Sprite::OR => vec![
+            (OR_BODY, OUTLINE_WIDTH),
+            (TWO_INPUTS, WIRE_WIDTH),
+            ("M65.5 20 H90", WIRE_WIDTH),
+        ],
+        Sprite::NOR => vec![
+            (OR_BODY, OUTLINE_WIDTH),
+            (BUBBLE_AT_20, WIRE_WIDTH),
+            (TWO_INPUTS, WIRE_WIDTH),
+            ("M75 20 H90", WIRE_WIDTH),
+        ],
+        Sprite::XOR => vec![
+            (OR_BODY, OUTLINE_WIDTH),
+            (XOR_BACK, OUTLINE_WIDTH),
+            (TWO_INPUTS, WIRE_WIDTH),
+            ("M65.5 20 H90", WIRE_WIDTH),
+        ],
+        Sprite::XNOR => vec![
+            (OR_BODY, OUTLINE_WIDTH),
+            (XOR_BACK, OUTLINE_WIDTH),
+            (BUBBLE_AT_20, WIRE_WIDTH),
+            (TWO_INPUTS, WIRE_WIDTH),
+            ("M75 20 H90", WIRE_WIDTH),
+        ],
+        Sprite::NOT => vec![
+            ("M25.5 1 V43 L62.5 22 Z", OUTLINE_WIDTH),
+            ("M74.5 22 A4 4 0 0 1 66.5 22 A4 4 0 0 1 74.5 22", WIRE_WIDTH),
+            ("M0 22 H25.5 M75 22 H90", WIRE_WIDTH),
+        ],
+        Sprite::INPUT => vec![("M45 0 V22 H90", WIRE_WIDTH)],
+        Sprite::OUTPUT => vec![
+            ("M0 20 H45", WIRE_WIDTH),
+            ("M45 11 H64.5 L73.5 20 L64.5 29 H45 Z", WIRE_WIDTH),
+        ],
+    }
+}
+
+fn rgb(color: Rgba<u8>) -> String {
+    format!("rgb({},{},{})", color[0], color[1], color[2])
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expression_parser::parse_boolean_expression;
+    use crate::gate_graph::GateGraph;
+    use crate::renderer::{RenderOptions, Renderer};
+
+    #[test]
+    fn diagrams_are_drawn_as_shapes() {
+        let tree = parse_boolean_expression("a'b + c");
+        let graph = GateGraph::new(&tree);
+        let options = RenderOptions {
+            output_label: String::from("f & g"),
+            title: None,
+            wire_labels: false,
+            overbars: false,
+            assignment: None,
+            critical_path: None,
+        };
+        let mut renderer = Renderer::new(&graph, options);
+        renderer.draw_tree(&graph);
+        let svg = renderer.svg();
+
+        assert!(svg.starts_with("<svg"), "{}", svg);
+        assert!(!svg.contains("<image"), "{}", svg);
+        // OR, AND, NOT, three inputs and the output terminal
+        assert_eq!(svg.matches("<g transform").count(), 7, "{}", svg);
+        for label in [">a</text>", ">b</text>", ">c</text>", ">f &amp; g</text>"] {
+            assert!(svg.contains(label), "no {} in {}", label, svg);
+        }
+        // a wire into each gate input but the ones the tree has straight from an input sprite
+        assert!(svg.matches("<polyline").count() >= 5, "{}", svg);
+    }
+}
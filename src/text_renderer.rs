@@ -0,0 +1,215 @@
+use std::fmt::{self, Display};
+
+use crate::expression_parser::GateType;
+use crate::gate_graph::{GateGraph, GateId, ROOT};
+use crate::spritesheet::sprite_info;
+
+/// Pixels of the image layout per row of text, which puts a gate's two inputs two rows apart with its output between.
+const PIXELS_PER_ROW: u32 = 10;
+/// Room after each column for the wires to turn towards the next.
+const CHANNEL_WIDTH: usize = 4;
+
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+/// Draws a tree in Unicode box-drawing characters, in the layout `Renderer::draw_tree` uses,
+/// with a compact IEC-style glyph for each gate and the variables written where their inputs are.
+pub struct TextRenderer {
+    /// Which directions the wires through each cell run in, joined up into one character when printed.
+    wires: Vec<Vec<u8>>,
+    /// Gate glyphs and labels, printed over any wire.
+    text: Vec<Vec<Option<char>>>,
+}
+
+impl TextRenderer {
+    pub fn new(graph: &GateGraph, output_label: &str) -> TextRenderer {
+        let positions = graph.positions(graph.adjusted_origin(graph.drawn_image_height() / 2));
+        let top = *positions.iter().min().unwrap();
+        let height = positions.iter().map(|y| y - top).max().unwrap() / PIXELS_PER_ROW + 5;
+
+        let columns = column_starts(graph);
+        let width = columns[columns.len() - 1] + output_label.chars().count() + 1;
+        let mut renderer = TextRenderer {
+            wires: vec![vec![0; width]; height as usize],
+            text: vec![vec![None; width]; height as usize],
+        };
+        renderer.draw_tree(graph, &positions, top, &columns, output_label);
+        renderer
+    }
+
+    fn draw_tree(
+        &mut self,
+        graph: &GateGraph,
+        positions: &[u32],
+        top: u32,
+        columns: &[usize],
+        output_label: &str,
+    ) {
+        let row = |id: GateId, pin: u32| {
+            ((positions[id] - top + pin + PIXELS_PER_ROW / 2) / PIXELS_PER_ROW) as usize
+        };
+        let output_row =
+            |id: GateId| row(id, sprite_info(graph.nodes[id].sprite_type()).outputs[0]);
+
+        for (id, node) in graph.nodes.iter().enumerate() {
+            let x = columns[node.column as usize - 1];
+            let glyph = glyph(node.gate_type, &node.name);
+            let y = output_row(id);
+            self.write(x + 1, y, &glyph);
+
+            // the output runs to the channel, then up or down to the pin it feeds
+            let turn = columns[node.column as usize] - CHANNEL_WIDTH + 1;
+            self.horizontal(y, x + 1 + glyph.chars().count(), turn);
+            if let Some((parent, pin)) = node.parent {
+                let pin_y = row(
+                    parent,
+                    sprite_info(graph.nodes[parent].sprite_type()).inputs[pin],
+                );
+                let parent_x = columns[graph.nodes[parent].column as usize - 1];
+                self.vertical(turn, y, pin_y);
+                self.horizontal(pin_y, turn, parent_x);
+                // a gate's inputs are joined to its glyph at the start of its column
+                self.vertical(parent_x, pin_y, output_row(parent));
+                self.horizontal(output_row(parent), parent_x, parent_x + 1);
+            }
+        }
+
+        let root_x = columns[graph.nodes[ROOT].column as usize];
+        let y = output_row(ROOT);
+        self.horizontal(y, root_x - CHANNEL_WIDTH + 1, root_x);
+        self.write(root_x + 1, y, output_label);
+    }
+
+    fn write(&mut self, x: usize, y: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            self.text[y][x + i] = Some(c);
+        }
+    }
+
+    fn horizontal(&mut self, y: usize, from: usize, to: usize) {
+        let (left, right) = (from.min(to), from.max(to));
+        for x in left..=right {
+            if x > left {
+                self.wires[y][x] |= LEFT;
+            }
+            if x < right {
+                self.wires[y][x] |= RIGHT;
+            }
+        }
+    }
+
+    fn vertical(&mut self, x: usize, from: usize, to: usize) {
+        let (top, bottom) = (from.min(to), from.max(to));
+        for y in top..=bottom {
+            if y > top {
+                self.wires[y][x] |= UP;
+            }
+            if y < bottom {
+                self.wires[y][x] |= DOWN;
+            }
+        }
+    }
+}
+
+impl Display for TextRenderer {
+    /// Rows holding nothing but vertical wires are left out, since they only stretch the drawing.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (wires, text) in self.wires.iter().zip(&self.text) {
+            let line: String = wires
+                .iter()
+                .zip(text)
+                .map(|(&wire, &text)| text.unwrap_or_else(|| box_character(wire)))
+                .collect();
+            if line.chars().all(|c| c == ' ' || c == '│') {
+                continue;
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Where each column starts, leftmost first, with one more entry for where the output goes.
+/// Each column is as wide as the widest glyph or label in it, plus the channel after it.
+fn column_starts(graph: &GateGraph) -> Vec<usize> {
+    let mut widths = vec![0; graph.depth() as usize];
+    for node in &graph.nodes {
+        let width = glyph(node.gate_type, &node.name).chars().count();
+        let column = &mut widths[node.column as usize - 1];
+        *column = (*column).max(width);
+    }
+    let mut starts = vec![0];
+    for width in widths {
+        starts.push(starts[starts.len() - 1] + 1 + width + CHANNEL_WIDTH);
+    }
+    starts
+}
+
+fn glyph(gate_type: GateType, name: &str) -> String {
+    String::from(match gate_type {
+        GateType::INPUT => name,
+        GateType::AND => "[&]",
+        GateType::OR => "[≥1]",
+        GateType::NOT => "▷o",
+        GateType::NAND => "[&]o",
+        GateType::NOR => "[≥1]o",
+        GateType::XOR => "[=1]",
+        GateType::XNOR => "[=1]o",
+    })
+}
+
+fn box_character(wires: u8) -> char {
+    match wires {
+        0 => ' ',
+        UP | DOWN | 3 => '│',
+        LEFT | RIGHT | 12 => '─',
+        9 => '└',
+        5 => '┘',
+        10 => '┌',
+        6 => '┐',
+        11 => '├',
+        7 => '┤',
+        14 => '┬',
+        13 => '┴',
+        _ => '┼',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::parse_boolean_expression;
+
+    fn drawn(expression: &str) -> String {
+        let tree = parse_boolean_expression(expression);
+        TextRenderer::new(&GateGraph::new(&tree), &tree.get_name()).to_string()
+    }
+
+    #[test]
+    fn trees_are_drawn_in_the_image_layout() {
+        assert_eq!(
+            drawn("(a ^ b)'"),
+            " b─┐
+   └──┐
+      ├[=1]─────▷o─────(a^b)'
+   ┌──┘
+ a─┘
+"
+        );
+        assert_eq!(
+            drawn("ab + c'"),
+            " c─────▷o──┐
+           └──┐
+              ├[≥1]─────ab+c'
+           ┌──┘
+ b─┐       │
+   └──┐    │
+      ├[&]─┘
+   ┌──┘
+ a─┘
+"
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+
+use crate::circuit::{Circuit, CircuitLayout, NodeKind};
+use crate::expression_parser::{Gate, GateType};
+use crate::gate_graph::{GateGraph, GateId, ROOT};
+
+/// How long each type of gate takes to switch, in arbitrary units.
+#[derive(Debug, Clone)]
+pub struct DelayTable {
+    delays: HashMap<GateType, f32>,
+}
+
+impl Default for DelayTable {
+    /// One unit per inverting stage: NAND, NOR and NOT are a single stage, AND and OR
+    /// are one followed by an inverter, and XOR and XNOR take about three.
+    fn default() -> DelayTable {
+        let delays = vec![
+            (GateType::NOT, 1.0),
+            (GateType::NAND, 1.0),
+            (GateType::NOR, 1.0),
+            (GateType::AND, 2.0),
+            (GateType::OR, 2.0),
+            (GateType::XOR, 3.0),
+            (GateType::XNOR, 3.0),
+            (GateType::INPUT, 0.0),
+        ];
+        DelayTable {
+            delays: delays.into_iter().collect(),
+        }
+    }
+}
+
+impl DelayTable {
+    /// The default table with the delays written `and=2,or=2.5,not=1` replacing its own.
+    pub fn parse(text: &str) -> Result<DelayTable, String> {
+        let mut table = DelayTable::default();
+        for pair in text.split(',') {
+            let (name, delay) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected gate=delay, found {}", pair))?;
+            let gate_type = match name.trim().to_lowercase().as_str() {
+                "and" => GateType::AND,
+                "or" => GateType::OR,
+                "not" => GateType::NOT,
+                "nand" => GateType::NAND,
+                "nor" => GateType::NOR,
+                "xor" => GateType::XOR,
+                "xnor" => GateType::XNOR,
+                other => return Err(format!("{} is not a gate type", other)),
+            };
+            let delay: f32 = delay
+                .trim()
+                .parse()
+                .map_err(|_| format!("{} is not a delay", delay))?;
+            if !delay.is_finite() || delay < 0.0 {
+                return Err(format!("{} is not a delay", delay));
+            }
+            table.delays.insert(gate_type, delay);
+        }
+        Ok(table)
+    }
+
+    pub fn delay(&self, gate_type: GateType) -> f32 {
+        self.delays[&gate_type]
+    }
+}
+
+/// When the output of `gate` settles, with every input arriving at time 0.
+/// With a delay of 1 for every gate this is one less than `GateGraph::depth`.
+pub fn arrival_time(gate: &Gate, delays: &DelayTable) -> f32 {
+    arrival_times(&GateGraph::new(gate), delays, &|_| 0.0)[ROOT]
+}
+
+/// When every gate in `graph` settles, each worked out once from its inputs' times, which come after it.
+/// An input named `name` arrives at `start(name)`.
+fn arrival_times(graph: &GateGraph, delays: &DelayTable, start: &dyn Fn(&str) -> f32) -> Vec<f32> {
+    let mut times = vec![0.0; graph.nodes.len()];
+    for id in (0..graph.nodes.len()).rev() {
+        let node = &graph.nodes[id];
+        let latest_input = match node.gate_type {
+            GateType::INPUT => start(&node.name),
+            _ => node
+                .inputs
+                .iter()
+                .map(|&input| times[input])
+                .fold(0.0, f32::max),
+        };
+        times[id] = latest_input + delays.delay(node.gate_type);
+    }
+    times
+}
+
+/// The slowest route from an input to the output of `tree`, as the index of the input
+/// each gate along it waits on, starting from the root.
+/// Ties go to the input written first, which `Gate` stores last.
+pub fn critical_path(tree: &Gate, delays: &DelayTable) -> Vec<usize> {
+    let graph = GateGraph::new(tree);
+    let times = arrival_times(&graph, delays, &|_| 0.0);
+    path_through(&graph, &times)
+        .windows(2)
+        .map(|pair| graph.nodes[pair[1]].parent.unwrap().1)
+        .collect()
+}
+
+/// The nodes along the slowest route through `graph`, from the root to the input it starts at.
+fn path_through(graph: &GateGraph, times: &[f32]) -> Vec<GateId> {
+    let mut path = vec![ROOT];
+    let mut id = ROOT;
+    while !graph.nodes[id].inputs.is_empty() {
+        let mut latest = id;
+        let mut latest_time = f32::MIN;
+        for &input in &graph.nodes[id].inputs {
+            if times[input] >= latest_time {
+                latest = input;
+                latest_time = times[input];
+            }
+        }
+        path.push(latest);
+        id = latest;
+    }
+    path
+}
+
+/// The gates along `path` from the input it starts at to the output, each with its arrival time,
+/// as in `a @ 0 -> a*b @ 2 -> a*b+c @ 4`.
+pub fn describe_path(tree: &Gate, path: &[usize], delays: &DelayTable) -> String {
+    let graph = GateGraph::new(tree);
+    let times = arrival_times(&graph, delays, &|_| 0.0);
+    let mut ids = vec![ROOT];
+    for &i in path {
+        ids.push(graph.nodes[ids[ids.len() - 1]].inputs[i]);
+    }
+    let steps: Vec<String> = ids
+        .iter()
+        .rev()
+        .map(|&id| format!("{} @ {}", graph.nodes[id].name, times[id]))
+        .collect();
+    steps.join(" -> ")
+}
+
+/// When each signal of `circuit` settles, with the primary inputs arriving at time 0.
+/// Each signal is timed once, after the signals it reads, so shared signals aren't timed again per reader.
+pub fn signal_arrival_times(
+    circuit: &Circuit,
+    delays: &DelayTable,
+) -> Result<HashMap<String, f32>, String> {
+    let mut signal_times = HashMap::new();
+    for index in circuit.topological_order()? {
+        let (name, gate) = &circuit.signals[index];
+        let start = |input: &str| signal_times.get(input).copied().unwrap_or(0.0);
+        let time = arrival_times(&GateGraph::new(gate), delays, &start)[ROOT];
+        signal_times.insert(name.clone(), time);
+    }
+    Ok(signal_times)
+}
+
+/// The slowest route to the signal `output` of `circuit`, followed back through the signals it reads,
+/// written as `describe_path` writes it with each signal named where it settles, as in `a @ 0 -> t @ 3 -> sum @ 6`.
+pub fn describe_signal_path(
+    circuit: &Circuit,
+    output: &str,
+    signal_times: &HashMap<String, f32>,
+    delays: &DelayTable,
+) -> String {
+    let start = |input: &str| signal_times.get(input).copied().unwrap_or(0.0);
+    let mut steps = Vec::new();
+    let mut name = output.to_string();
+    while let Some(gate) = circuit.signal(&name) {
+        let graph = GateGraph::new(gate);
+        let times = arrival_times(&graph, delays, &start);
+        let path = path_through(&graph, &times);
+        steps.push(format!("{} @ {}", name, times[ROOT]));
+        // the route ends at a primary input, or at a signal whose own route is followed next
+        let end = path[path.len() - 1];
+        name = graph.nodes[end].name.clone();
+        let next_is_signal = circuit.signal(&name).is_some();
+        for &id in &path[1..] {
+            if id != end || !next_is_signal {
+                steps.push(format!("{} @ {}", graph.nodes[id].name, times[id]));
+            }
+        }
+        if end == ROOT && !next_is_signal {
+            steps.push(format!("{} @ {}", name, start(&name)));
+        }
+    }
+    steps.reverse();
+    steps.join(" -> ")
+}
+
+/// The wires along the slowest route to each output of `layout`, as the node each one feeds and the pin it arrives at.
+/// Pass-through wires and output terminals take no time, and as with `critical_path`, ties go to the input written first.
+/// Routes that meet one already traced stop there, so a shared wire is only listed once.
+pub fn layout_critical_wires(layout: &CircuitLayout, delays: &DelayTable) -> Vec<(usize, usize)> {
+    let mut times: Vec<Option<f32>> = vec![None; layout.nodes.len()];
+    for id in 0..layout.nodes.len() {
+        layout_time(layout, id, delays, &mut times);
+    }
+    let mut wires = Vec::new();
+    for (output, node) in layout.nodes.iter().enumerate() {
+        if !matches!(node.kind, NodeKind::Output(_)) {
+            continue;
+        }
+        let mut id = output;
+        while !layout.nodes[id].inputs.is_empty() {
+            let mut latest = 0;
+            let mut latest_time = f32::MIN;
+            for (pin, &input) in layout.nodes[id].inputs.iter().enumerate() {
+                if times[input].unwrap() >= latest_time {
+                    latest = pin;
+                    latest_time = times[input].unwrap();
+                }
+            }
+            if wires.contains(&(id, latest)) {
+                break;
+            }
+            wires.push((id, latest));
+            id = layout.nodes[id].inputs[latest];
+        }
+    }
+    wires
+}
+
+/// When node `id` of `layout` settles, timing what it reads first unless `times` already has it.
+fn layout_time(
+    layout: &CircuitLayout,
+    id: usize,
+    delays: &DelayTable,
+    times: &mut Vec<Option<f32>>,
+) -> f32 {
+    if let Some(time) = times[id] {
+        return time;
+    }
+    let node = &layout.nodes[id];
+    let latest_input = node
+        .inputs
+        .iter()
+        .map(|&input| layout_time(layout, input, delays, times))
+        .fold(0.0, f32::max);
+    let time = match &node.kind {
+        NodeKind::Gate(GateType::INPUT, _) => 0.0,
+        NodeKind::Gate(gate_type, _) => latest_input + delays.delay(*gate_type),
+        NodeKind::Output(_) | NodeKind::Wire => latest_input,
+    };
+    times[id] = Some(time);
+    time
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::parse_statements;
+    use crate::expression_parser::parse_boolean_expression;
+
+    #[test]
+    fn critical_path_follows_the_slowest_input() {
+        let tree = parse_boolean_expression("c + a^b");
+        let delays = DelayTable::default();
+        let path = critical_path(&tree, &delays);
+        assert_eq!(arrival_time(&tree, &delays), 5.0);
+        assert_eq!(
+            describe_path(&tree, &path, &delays),
+            "a @ 0 -> a^b @ 3 -> c+a^b @ 5"
+        );
+    }
+
+    #[test]
+    fn long_chains_are_timed_quickly() {
+        // each gate reads the one before twice, so walking every route would take 2^200 steps
+        let mut statements = String::from("input a; w0 = a;");
+        for i in 1..=200 {
+            statements.push_str(&format!(" w{} = w{} w{};", i, i - 1, i - 1));
+        }
+        let circuit = parse_statements(&statements).unwrap();
+        let delays = DelayTable::default();
+        let times = signal_arrival_times(&circuit, &delays).unwrap();
+        assert_eq!(times["w200"], 400.0);
+        let path = describe_signal_path(&circuit, "w200", &times, &delays);
+        assert!(path.starts_with("a @ 0 -> w0 @ 0 -> w1 @ 2 -> "));
+        assert!(path.ends_with(" -> w200 @ 400"));
+    }
+
+    #[test]
+    fn circuit_drawings_trace_the_slowest_route_to_each_output() {
+        let circuit = parse_statements("t = a ^ b; f = t + c; g = t'").unwrap();
+        let layout = CircuitLayout::new(&circuit);
+        let wires = layout_critical_wires(&layout, &DelayTable::default());
+        let name = |id: usize| match &layout.nodes[id].kind {
+            NodeKind::Gate(_, name) | NodeKind::Output(name) => name.clone(),
+            NodeKind::Wire => String::from("wire"),
+        };
+        let mut routes: Vec<(String, String)> = wires
+            .iter()
+            .map(|&(id, pin)| (name(layout.nodes[id].inputs[pin]), name(id)))
+            .filter(|(from, _)| from != "wire")
+            .collect();
+        routes.sort();
+        // both outputs wait on t, which waits on a, written before b; c arrives long before t does
+        assert!(
+            routes.contains(&(String::from("a"), String::from("a^b"))),
+            "{:?}",
+            routes
+        );
+        assert!(
+            !routes.iter().any(|(from, _)| from == "b" || from == "c"),
+            "{:?}",
+            routes
+        );
+        let t_reads = wires.iter().filter(|&&(id, _)| name(id) == "a^b").count();
+        assert_eq!(t_reads, 1);
+    }
+}
@@ -0,0 +1,318 @@
+use image::{Rgba, RgbaImage};
+use rusttype::{Font, Scale};
+
+use crate::renderer::draw_text;
+
+/// Subscripts are set this much smaller than the text they hang off.
+const SUBSCRIPT_SCALE: f32 = 0.7;
+/// Gap between the tallest letter and the lowest bar, and between stacked bars, as fractions of the size.
+const BAR_GAP: f32 = 0.12;
+const BAR_STEP: f32 = 0.18;
+
+/// A label broken into what gets set differently: plain text, a variable with its
+/// subscript, and negations drawn as a bar over what they negate.
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Variable(String, String),
+    Bar(Vec<Piece>),
+}
+
+/// Reads a gate name, turning `x'` and `(...)'` into bars (dropping the parentheses a bar makes redundant)
+/// and the digits after a letter into its subscript, so `(x1 + x2')'` becomes a bar over `x₁ + x̄₂`.
+fn parse_label(text: &str) -> Vec<Piece> {
+    let mut groups: Vec<Vec<Piece>> = vec![Vec::new()];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => groups.push(Vec::new()),
+            ')' if groups.len() > 1 => {
+                let group = groups.pop().unwrap();
+                let outer = groups.last_mut().unwrap();
+                if chars.peek() == Some(&'\'') {
+                    chars.next();
+                    outer.push(Piece::Bar(group));
+                } else {
+                    outer.push(Piece::Text(String::from("(")));
+                    outer.extend(group);
+                    outer.push(Piece::Text(String::from(")")));
+                }
+            }
+            '\'' => {
+                let current = groups.last_mut().unwrap();
+                match current.pop() {
+                    Some(last) => current.push(Piece::Bar(vec![last])),
+                    None => current.push(Piece::Text(String::from("'"))),
+                }
+            }
+            c if c.is_alphabetic() => {
+                let mut subscript = String::new();
+                while let Some(&digit) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    subscript.push(digit);
+                    chars.next();
+                }
+                groups
+                    .last_mut()
+                    .unwrap()
+                    .push(Piece::Variable(c.to_string(), subscript));
+            }
+            c => groups.last_mut().unwrap().push(Piece::Text(c.to_string())),
+        }
+    }
+
+    // anything left open is written out as it was
+    while groups.len() > 1 {
+        let group = groups.pop().unwrap();
+        let outer = groups.last_mut().unwrap();
+        outer.push(Piece::Text(String::from("(")));
+        outer.extend(group);
+    }
+    groups.pop().unwrap()
+}
+
+/// How many bars are stacked over the tallest part of `pieces`.
+fn bar_depth(pieces: &[Piece]) -> u32 {
+    pieces
+        .iter()
+        .map(|piece| match piece {
+            Piece::Bar(inner) => 1 + bar_depth(inner),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Horizontal advance rather than inked width, so neighbouring pieces don't touch.
+fn advance(font: &Font, text: &str, size: f32) -> f32 {
+    font.layout(text, Scale::uniform(size), rusttype::point(0.0, 0.0))
+        .last()
+        .map(|g| g.position().x + g.unpositioned().h_metrics().advance_width)
+        .unwrap_or(0.0)
+}
+
+fn pieces_width(font: &Font, pieces: &[Piece], size: f32) -> f32 {
+    pieces
+        .iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => advance(font, text, size),
+            Piece::Variable(name, subscript) => {
+                advance(font, name, size) + advance(font, subscript, size * SUBSCRIPT_SCALE)
+            }
+            Piece::Bar(inner) => pieces_width(font, inner, size),
+        })
+        .sum()
+}
+
+pub fn label_width(font: &Font, text: &str, size: f32) -> u32 {
+    pieces_width(font, &parse_label(text), size).ceil() as u32
+}
+
+/// One stroke of a typeset label: text with its top-left corner at (`x`, `y`) as `draw_text` takes it,
+/// or a bar covering the pixel columns from `x` up to `end` and the rows from `y` up `thickness` of them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mark {
+    Text {
+        text: String,
+        x: f32,
+        y: f32,
+        size: f32,
+    },
+    Bar {
+        x: i32,
+        end: i32,
+        y: i32,
+        thickness: i32,
+    },
+}
+
+/// Draws `text` like `draw_text`, with its letters' top-left corner at (`x`, `y`),
+/// but with negations as overbars and subscripted variable numbers.
+pub fn draw_label(
+    image: &mut RgbaImage,
+    font: &Font,
+    text: &str,
+    x: u32,
+    y: u32,
+    size: f32,
+    color: Rgba<u8>,
+) {
+    for mark in label_marks(font, text, x, y, size) {
+        match mark {
+            Mark::Text { text, x, y, size } => {
+                draw_text(image, font, &text, x as u32, y as u32, size, color)
+            }
+            Mark::Bar {
+                x,
+                end,
+                y,
+                thickness,
+            } => {
+                for pixel_x in x..end {
+                    for t in 0..thickness {
+                        let pixel_y = y - t;
+                        if pixel_x >= 0
+                            && pixel_y >= 0
+                            && (pixel_x as u32) < image.width()
+                            && (pixel_y as u32) < image.height()
+                        {
+                            image.put_pixel(pixel_x as u32, pixel_y as u32, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The marks `draw_label` makes, for drawing the label somewhere other than an image.
+pub fn label_marks(font: &Font, text: &str, x: u32, y: u32, size: f32) -> Vec<Mark> {
+    // the bars sit over the ascenders, so find where those are relative to the line's top
+    let scale = Scale::uniform(size);
+    let ascent = font.v_metrics(scale).ascent;
+    let ascender_top = font
+        .layout("bdfhklt", scale, rusttype::point(0.0, ascent))
+        .filter_map(|g| g.pixel_bounding_box())
+        .map(|bb| bb.min.y)
+        .min()
+        .unwrap_or(0)
+        .max(0) as f32;
+
+    let mut marks = Vec::new();
+    set_pieces(
+        &mut marks,
+        font,
+        &parse_label(text),
+        x as f32,
+        y as f32 + ascender_top,
+        y as f32,
+        size,
+    );
+    marks
+}
+
+/// Adds the marks for `pieces` from `x`, returning where they end. `letter_top` is where bars measure up from.
+fn set_pieces(
+    marks: &mut Vec<Mark>,
+    font: &Font,
+    pieces: &[Piece],
+    x: f32,
+    letter_top: f32,
+    line_top: f32,
+    size: f32,
+) -> f32 {
+    let mut x = x;
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => {
+                marks.push(Mark::Text {
+                    text: text.clone(),
+                    x,
+                    y: line_top,
+                    size,
+                });
+                x += advance(font, text, size);
+            }
+            Piece::Variable(name, subscript) => {
+                marks.push(Mark::Text {
+                    text: name.clone(),
+                    x,
+                    y: line_top,
+                    size,
+                });
+                x += advance(font, name, size);
+                if !subscript.is_empty() {
+                    let subscript_size = size * SUBSCRIPT_SCALE;
+                    marks.push(Mark::Text {
+                        text: subscript.clone(),
+                        x,
+                        y: line_top + size * 0.45,
+                        size: subscript_size,
+                    });
+                    x += advance(font, subscript, subscript_size);
+                }
+            }
+            Piece::Bar(inner) => {
+                let start = x;
+                x = set_pieces(marks, font, inner, x, letter_top, line_top, size);
+                let bar_y = letter_top - size * (BAR_GAP + BAR_STEP * bar_depth(inner) as f32);
+                marks.push(Mark::Bar {
+                    x: (start + 1.0) as i32,
+                    end: (x - 1.0) as i32,
+                    y: bar_y as i32,
+                    thickness: (size / 14.0).round().max(1.0) as i32,
+                });
+            }
+        }
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::load_font;
+
+    fn text(text: &str) -> Piece {
+        Piece::Text(String::from(text))
+    }
+
+    fn variable(name: &str, subscript: &str) -> Piece {
+        Piece::Variable(String::from(name), String::from(subscript))
+    }
+
+    #[test]
+    fn negations_become_bars_and_digits_subscripts() {
+        assert_eq!(
+            parse_label("(x1 + x2')'"),
+            vec![Piece::Bar(vec![
+                variable("x", "1"),
+                text(" "),
+                text("+"),
+                text(" "),
+                Piece::Bar(vec![variable("x", "2")]),
+            ])]
+        );
+        // parentheses only go when a bar takes their place, and unclosed ones are kept
+        assert_eq!(
+            parse_label("(a)b"),
+            vec![text("("), variable("a", ""), text(")"), variable("b", "")]
+        );
+        assert_eq!(
+            parse_label("a(b"),
+            vec![variable("a", ""), text("("), variable("b", "")]
+        );
+        assert_eq!(parse_label("'"), vec![text("'")]);
+    }
+
+    #[test]
+    fn stacked_bars_sit_above_each_other() {
+        let font = load_font();
+        let marks = label_marks(&font, "(ab')'c12", 10, 20, 24.0);
+        let bars: Vec<(i32, i32, i32)> = marks
+            .iter()
+            .filter_map(|mark| match *mark {
+                Mark::Bar { x, end, y, .. } => Some((x, end, y)),
+                _ => None,
+            })
+            .collect();
+        // b's bar comes first and sits lower, inside the span of the bar over everything
+        assert_eq!(bars.len(), 2);
+        let ((inner_x, inner_end, inner_y), (outer_x, outer_end, outer_y)) = (bars[0], bars[1]);
+        assert!(outer_y < inner_y && inner_y < 20 + 24);
+        assert!(outer_x <= inner_x && inner_end <= outer_end);
+
+        let subscript = marks
+            .iter()
+            .find(|mark| matches!(mark, Mark::Text { text, .. } if text == "12"))
+            .unwrap();
+        match subscript {
+            Mark::Text { y, size, .. } => assert!(*y > 20.0 && *size < 24.0),
+            _ => unreachable!(),
+        }
+        // primes take no room once they're bars
+        assert_eq!(
+            label_width(&font, "(ab')'c12", 24.0),
+            label_width(&font, "abc12", 24.0)
+        );
+    }
+}
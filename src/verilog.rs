@@ -0,0 +1,297 @@
+use crate::circuit::{self, Circuit, NetlistGate};
+use crate::expression_parser::{Gate, GateType};
+use crate::netlist::{Cell, LogicGate, Netlist};
+
+/// A structural module instancing a Verilog gate primitive for every gate in `tree`,
+/// joined by wires `n1`, `n2`, ... in the order they're computed.
+pub fn structural(tree: &Gate, module: &str, output: &str) -> String {
+    let netlist = Netlist::new(tree, output);
+    let mut body = String::new();
+    if !netlist.wires.is_empty() {
+        body.push_str(&format!("    wire {};\n\n", netlist.wires.join(", ")));
+    }
+    for cell in &netlist.cells {
+        let line = match cell {
+            Cell::Gate {
+                gate,
+                output,
+                operands,
+                expression,
+            } => {
+                let operands: Vec<String> = operands.iter().map(|name| literal(name)).collect();
+                format!(
+                    "{} ({}, {});  // {}",
+                    primitive(*gate),
+                    output,
+                    operands.join(", "),
+                    expression
+                )
+            }
+            Cell::Buffer { output, source } => format!("buf ({}, {});", output, literal(source)),
+        };
+        body.push_str(&format!("    {}\n", line));
+    }
+    module_text(tree, module, output, &body)
+}
+
+/// The same module as a single continuous assignment.
+pub fn behavioural(tree: &Gate, module: &str, output: &str) -> String {
+    let body = format!("    assign {} = {};\n", output, expression(tree));
+    module_text(tree, module, output, &body)
+}
+
+/// Turns `text` into a legal identifier, replacing anything else with underscores.
+pub fn identifier(text: &str) -> String {
+    let mut name: String = text
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert(0, 'm');
+    }
+    name
+}
+
+fn module_text(tree: &Gate, module: &str, output: &str, body: &str) -> String {
+    let mut ports: Vec<String> = tree
+        .input_names()
+        .iter()
+        .map(|name| format!("    input {}", name))
+        .collect();
+    ports.push(format!("    output {}", output));
+    format!(
+        "module {} (\n{}\n);\n{}endmodule\n",
+        module,
+        ports.join(",\n"),
+        body
+    )
+}
+
+fn expression(gate: &Gate) -> String {
+    let operands: Vec<String> = gate
+        .get_inputs()
+        .iter()
+        .rev()
+        .map(|input| match input.get_type() {
+            GateType::INPUT | GateType::NOT => expression(input),
+            _ => format!("({})", expression(input)),
+        })
+        .collect();
+    match gate.get_type() {
+        GateType::INPUT => literal(&gate.get_name()),
+        GateType::NOT => format!("~{}", operands[0]),
+        GateType::AND => operands.join(" & "),
+        GateType::OR => operands.join(" | "),
+        GateType::XOR => operands.join(" ^ "),
+        GateType::NAND => format!("~({})", operands.join(" & ")),
+        GateType::NOR => format!("~({})", operands.join(" | ")),
+        GateType::XNOR => format!("~({})", operands.join(" ^ ")),
+    }
+}
+
+fn primitive(gate: LogicGate) -> &'static str {
+    match gate {
+        LogicGate::AND => "and",
+        LogicGate::OR => "or",
+        LogicGate::NOT => "not",
+        LogicGate::NAND => "nand",
+        LogicGate::NOR => "nor",
+        LogicGate::XOR => "xor",
+        LogicGate::XNOR => "xnor",
+    }
+}
+
+/// The constants `0` and `1` become Verilog's one-bit literals; variables keep their names.
+fn literal(name: &str) -> String {
+    match name {
+        "0" => String::from("1'b0"),
+        "1" => String::from("1'b1"),
+        name => String::from(name),
+    }
+}
+
+/// Reads a structural netlist: one module of scalar `input`, `output` and `wire` declarations,
+/// gate primitives like `and g1 (y, a, b);` and plain `assign y = a;` connections.
+pub fn parse_netlist(text: &str) -> Result<Circuit, String> {
+    let text = strip_comments(text);
+    let mut inputs: Vec<String> = Vec::new();
+    let mut outputs: Vec<String> = Vec::new();
+    let mut gates: Vec<NetlistGate> = Vec::new();
+    let mut in_module = false;
+
+    for statement in text.split(';') {
+        let mut statement = statement.trim();
+        if let Some(rest) = statement.strip_prefix("endmodule") {
+            in_module = false;
+            statement = rest.trim();
+        }
+        if statement.is_empty() {
+            continue;
+        }
+        if statement.contains('[') {
+            return Err(format!(
+                "only single-bit nets can be read, found {}",
+                statement
+            ));
+        }
+
+        let (keyword, rest) = split_keyword(statement);
+        match keyword {
+            "module" => {
+                if in_module || !gates.is_empty() {
+                    return Err(String::from("only one module can be read"));
+                }
+                in_module = true;
+                // ANSI headers declare their ports' directions in the port list
+                if let (Some(open), Some(close)) = (rest.find('('), rest.rfind(')')) {
+                    let mut direction = None;
+                    for port in rest[open + 1..close].split(',') {
+                        let words: Vec<&str> = port.split_whitespace().collect();
+                        match words.first() {
+                            Some(&"input") => direction = Some(&mut inputs),
+                            Some(&"output") => direction = Some(&mut outputs),
+                            _ => {}
+                        }
+                        if let (Some(list), Some(name)) = (direction.as_mut(), words.last()) {
+                            list.push(String::from(*name));
+                        }
+                    }
+                }
+            }
+            "input" => inputs.extend(declared_names(rest)),
+            "output" => outputs.extend(declared_names(rest)),
+            "wire" => {}
+            "assign" => {
+                let (target, source) = rest
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected assign name = name, found {}", statement))?;
+                gates.push(NetlistGate {
+                    output: String::from(target.trim()),
+                    gate_type: None,
+                    operands: vec![operand(source.trim())?],
+                });
+            }
+            primitive => {
+                let gate_type = match primitive {
+                    "and" => Some(GateType::AND),
+                    "or" => Some(GateType::OR),
+                    "nand" => Some(GateType::NAND),
+                    "nor" => Some(GateType::NOR),
+                    "xor" => Some(GateType::XOR),
+                    "xnor" => Some(GateType::XNOR),
+                    "not" => Some(GateType::NOT),
+                    "buf" => None,
+                    _ => return Err(format!("can't read {}", statement)),
+                };
+                // the port list is the last parenthesised group, after any `#(delay)`
+                let (open, close) = match (rest.rfind('('), rest.rfind(')')) {
+                    (Some(open), Some(close)) if open < close => (open, close),
+                    _ => return Err(format!("expected a port list in {}", statement)),
+                };
+                let terminals: Vec<&str> =
+                    rest[open + 1..close].split(',').map(str::trim).collect();
+                if terminals.len() < 2 {
+                    return Err(format!("{} needs an output and an input", statement));
+                }
+                // not and buf can drive several outputs from their last terminal; the others have one output first
+                let single_input = matches!(primitive, "not" | "buf");
+                let (targets, sources) = if single_input {
+                    terminals.split_at(terminals.len() - 1)
+                } else {
+                    terminals.split_at(1)
+                };
+                let operands = sources
+                    .iter()
+                    .map(|source| operand(source))
+                    .collect::<Result<Vec<Gate>, String>>()?;
+                for target in targets {
+                    gates.push(NetlistGate {
+                        output: String::from(*target),
+                        gate_type,
+                        operands: operands.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    circuit::from_netlist(&inputs, outputs, gates)
+}
+
+fn strip_comments(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('/') {
+        stripped.push_str(&rest[..start]);
+        let after = &rest[start..];
+        if after.starts_with("//") {
+            rest = after.find('\n').map_or("", |end| &after[end..]);
+        } else if after.starts_with("/*") {
+            rest = after.find("*/").map_or("", |end| &after[end + 2..]);
+        } else {
+            stripped.push('/');
+            rest = &after[1..];
+        }
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// The statement's first word and what follows it, with anything between a primitive's name
+/// and its port list (a delay or an instance name) dropped.
+fn split_keyword(statement: &str) -> (&str, &str) {
+    let end = statement
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(statement.len());
+    (&statement[..end], statement[end..].trim())
+}
+
+/// The names in a declaration like `input wire a, b`.
+fn declared_names(list: &str) -> Vec<String> {
+    list.split(',')
+        .filter_map(|name| name.split_whitespace().last())
+        .map(String::from)
+        .collect()
+}
+
+/// A net or one of the constants `1'b0` and `1'b1`.
+fn operand(text: &str) -> Result<Gate, String> {
+    let name = match text {
+        "1'b0" | "0" => "0",
+        "1'b1" | "1" => "1",
+        name if !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$') =>
+        {
+            name
+        }
+        other => return Err(format!("{} isn't a net name", other)),
+    };
+    Ok(Gate::input(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equivalence::assert_round_trips;
+
+    #[test]
+    fn structural_modules_read_back_as_the_same_function() {
+        // inputs with the names the wires would take, n1 n2' + n1' n2
+        let (n1, n2) = (Gate::input("n1"), Gate::input("n2"));
+        let not = |gate: &Gate| Gate::new(GateType::NOT, vec![gate.clone()]);
+        let collision = Gate::new(
+            GateType::XOR,
+            vec![
+                Gate::new(GateType::AND, vec![n1.clone(), not(&n2)]),
+                Gate::new(GateType::NOR, vec![not(&n1), n2, Gate::input("1")]),
+            ],
+        );
+        assert_round_trips(
+            &|tree| structural(tree, "m", "f"),
+            parse_netlist,
+            &[collision],
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::expression_parser::{Gate, GateType};
+use crate::netlist::{Cell, LogicGate, Netlist};
+
+const LIBRARIES: &str = "library ieee;\nuse ieee.std_logic_1164.all;\n";
+
+/// VHDL-2008's reserved words, and `std_logic`, which a port can't take while its type is written so.
+const RESERVED: &str = "\
+    abs access after alias all and architecture array assert assume assume_guarantee attribute \
+    begin block body buffer bus case component configuration constant context cover default \
+    disconnect downto else elsif end entity exit fairness file for force function generate generic \
+    group guarded if impure in inertial inout is label library linkage literal loop map mod nand \
+    new next nor not null of on open or others out package parameter port postponed procedure \
+    process property protected pure range record register reject release rem report restrict \
+    restrict_guarantee return rol ror select sequence severity shared signal sla sll sra srl \
+    strong subtype then to transport type unaffected units until use variable vmode vprop vunit \
+    wait when while with xnor xor std_logic";
+
+/// An entity whose architecture computes `tree` in one signal assignment, as in `f <= (a and b) or not c;`.
+pub fn dataflow(tree: &Gate, entity: &str, output: &str) -> String {
+    let entity = identifier(entity);
+    let names = Names::of_ports(tree, output);
+    format!(
+        "{}\n{}\narchitecture dataflow of {} is\nbegin\n    {} <= {};\nend architecture dataflow;\n",
+        LIBRARIES,
+        entity_text(tree, &entity, output, &names),
+        entity,
+        names.literal(output),
+        expression(tree, &names)
+    )
+}
+
+/// An entity whose architecture instantiates a gate entity for every gate in `tree`,
+/// joined by signals `n1`, `n2`, ... in the order they're computed.
+/// The gate entities it uses are written first, so the file stands on its own.
+pub fn structural(tree: &Gate, entity: &str, output: &str) -> String {
+    let entity = identifier(entity);
+    let netlist = Netlist::new(tree, output);
+    let mut names = Names::of_ports(tree, output);
+    let wires: Vec<String> = netlist.wires.iter().map(|wire| names.add(wire)).collect();
+    // instance labels share the architecture's names with the signals
+    let labels: Vec<String> = (1..=netlist.cells.len())
+        .map(|i| names.add(&format!("g{}", i)))
+        .collect();
+    let mut text = String::new();
+    for (gate, fan_in) in gate_kinds(&netlist) {
+        text.push_str(&gate_entity(gate, fan_in));
+        text.push('\n');
+    }
+
+    text.push_str(LIBRARIES);
+    text.push('\n');
+    text.push_str(&entity_text(tree, &entity, output, &names));
+    text.push_str(&format!("\narchitecture structural of {} is\n", entity));
+    if !wires.is_empty() {
+        text.push_str(&format!("    signal {} : std_logic;\n", wires.join(", ")));
+    }
+    text.push_str("begin\n");
+    for (i, cell) in netlist.cells.iter().enumerate() {
+        let line = match cell {
+            Cell::Gate {
+                gate,
+                output,
+                operands,
+                expression,
+            } => {
+                let mut actuals: Vec<String> =
+                    operands.iter().map(|name| names.literal(name)).collect();
+                actuals.push(names.literal(output));
+                format!(
+                    "{} : entity work.{} port map ({});  -- {}",
+                    labels[i],
+                    gate_entity_name(*gate, operands.len()),
+                    actuals.join(", "),
+                    expression
+                )
+            }
+            Cell::Buffer { output, source } => {
+                format!("{} <= {};", names.literal(output), names.literal(source))
+            }
+        };
+        text.push_str(&format!("    {}\n", line));
+    }
+    text.push_str("end architecture structural;\n");
+    text
+}
+
+/// Turns `text` into a legal VHDL identifier: letters, digits and single underscores, starting with a letter
+/// and not ending in an underscore, with `_v` after any that would be a reserved word.
+pub fn identifier(text: &str) -> String {
+    let mut name = String::new();
+    for c in text.chars() {
+        let c = if c.is_ascii_alphanumeric() { c } else { '_' };
+        if c != '_' || !(name.is_empty() || name.ends_with('_')) {
+            name.push(c);
+        }
+    }
+    while name.ends_with('_') {
+        name.pop();
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert(0, 'm');
+    }
+    let lower = name.to_ascii_lowercase();
+    if RESERVED.split_whitespace().any(|word| word == lower) {
+        name.push_str("_v");
+    }
+    name
+}
+
+/// The identifier each net of an entity is written with. VHDL ignores case,
+/// so a name that only differs from one already given by case is numbered, like `A_2` after `a`.
+struct Names {
+    names: HashMap<String, String>,
+    /// The identifiers given so far, in lower case.
+    taken: HashSet<String>,
+}
+
+impl Names {
+    /// The names of `tree`'s inputs, in `input_names` order, and of the output.
+    fn of_ports(tree: &Gate, output: &str) -> Names {
+        let mut names = Names {
+            names: HashMap::new(),
+            taken: HashSet::new(),
+        };
+        for input in tree.input_names() {
+            names.add(&input);
+        }
+        names.add(output);
+        names
+    }
+
+    /// Gives `name` an identifier no other net has and returns it.
+    fn add(&mut self, name: &str) -> String {
+        let base = identifier(name);
+        let mut candidate = base.clone();
+        let mut count = 1;
+        while self.taken.contains(&candidate.to_ascii_lowercase()) {
+            count += 1;
+            candidate = format!("{}_{}", base, count);
+        }
+        self.taken.insert(candidate.to_ascii_lowercase());
+        self.names.insert(String::from(name), candidate.clone());
+        candidate
+    }
+
+    /// The constants `0` and `1` become `std_logic` literals; nets are written with their identifiers.
+    fn literal(&self, name: &str) -> String {
+        match name {
+            "0" => String::from("'0'"),
+            "1" => String::from("'1'"),
+            name => self.names[name].clone(),
+        }
+    }
+}
+
+fn entity_text(tree: &Gate, entity: &str, output: &str, names: &Names) -> String {
+    let mut ports: Vec<String> = tree
+        .input_names()
+        .iter()
+        .map(|name| format!("        {} : in std_logic", names.literal(name)))
+        .collect();
+    ports.push(format!("        {} : out std_logic", names.literal(output)));
+    format!(
+        "entity {} is\n    port (\n{}\n    );\nend entity {};\n",
+        entity,
+        ports.join(";\n"),
+        entity
+    )
+}
+
+/// Every kind of gate in `netlist` with each number of inputs it's given, in the order first used.
+fn gate_kinds(netlist: &Netlist) -> Vec<(LogicGate, usize)> {
+    let mut used = Vec::new();
+    for cell in &netlist.cells {
+        if let Cell::Gate { gate, operands, .. } = cell {
+            if !used.contains(&(*gate, operands.len())) {
+                used.push((*gate, operands.len()));
+            }
+        }
+    }
+    used
+}
+
+fn gate_entity_name(gate: LogicGate, fan_in: usize) -> String {
+    match gate {
+        LogicGate::NOT => String::from("inv"),
+        gate => format!("{}{}", operator(gate), fan_in),
+    }
+}
+
+/// An entity for one kind of gate, with inputs `a1`, `a2`, ... and output `y`.
+fn gate_entity(gate: LogicGate, fan_in: usize) -> String {
+    let name = gate_entity_name(gate, fan_in);
+    let ports: Vec<String> = (1..=fan_in).map(|i| format!("a{}", i)).collect();
+    let body = match gate {
+        LogicGate::NOT => String::from("not a1"),
+        gate => apply(gate, &ports),
+    };
+    format!(
+        "{}\nentity {} is\n    port ({} : in std_logic; y : out std_logic);\nend entity {};\n\n\
+         architecture dataflow of {} is\nbegin\n    y <= {};\nend architecture dataflow;\n",
+        LIBRARIES,
+        name,
+        ports.join(", "),
+        name,
+        name,
+        body
+    )
+}
+
+/// `operands` combined by a binary gate. Only AND, OR and XOR chain without parentheses,
+/// so the inverted gates with more than two operands are written as NOT of those.
+fn apply(gate: LogicGate, operands: &[String]) -> String {
+    let inverted = match gate {
+        LogicGate::NAND => LogicGate::AND,
+        LogicGate::NOR => LogicGate::OR,
+        LogicGate::XNOR => LogicGate::XOR,
+        _ => return operands.join(&format!(" {} ", operator(gate))),
+    };
+    if operands.len() == 2 {
+        format!("{} {} {}", operands[0], operator(gate), operands[1])
+    } else {
+        format!("not ({})", apply(inverted, operands))
+    }
+}
+
+/// VHDL's logical operators can't be mixed without parentheses, and `not` only takes a primary,
+/// so every operand that isn't a single name or literal is bracketed, except under a binary operator
+/// where `not x` can stand on its own.
+fn expression(gate: &Gate, names: &Names) -> String {
+    let operand = |input: &Gate, under_not: bool| match input.get_type() {
+        GateType::INPUT => expression(input, names),
+        GateType::NOT if !under_not => expression(input, names),
+        _ => format!("({})", expression(input, names)),
+    };
+    match LogicGate::of(gate.get_type()) {
+        None => names.literal(&gate.get_name()),
+        Some(LogicGate::NOT) => format!("not {}", operand(&gate.get_inputs()[0], true)),
+        Some(logic_gate) => {
+            let operands: Vec<String> = gate
+                .get_inputs()
+                .iter()
+                .rev()
+                .map(|input| operand(input, false))
+                .collect();
+            apply(logic_gate, &operands)
+        }
+    }
+}
+
+fn operator(gate: LogicGate) -> &'static str {
+    match gate {
+        LogicGate::AND => "and",
+        LogicGate::OR => "or",
+        LogicGate::NOT => "not",
+        LogicGate::NAND => "nand",
+        LogicGate::NOR => "nor",
+        LogicGate::XOR => "xor",
+        LogicGate::XNOR => "xnor",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression_parser::parse_boolean_expression;
+
+    #[test]
+    fn structural_entities_define_every_gate_they_instance() {
+        let tree = parse_boolean_expression("a ^ b ^ c + (a + b + c)' + 0");
+        let text = structural(&tree, "m", "f");
+        let instanced: Vec<&str> = text
+            .split("entity work.")
+            .skip(1)
+            .map(|rest| rest.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(instanced.len(), 7);
+        for name in instanced {
+            assert!(text.contains(&format!("entity {} is", name)), "{}", name);
+        }
+        assert!(text.contains("'0'"));
+    }
+
+    #[test]
+    fn identifiers_avoid_reserved_words_and_stray_underscores() {
+        assert_eq!(identifier("out"), "out_v");
+        assert_eq!(identifier("AND"), "AND_v");
+        assert_eq!(identifier("a__b_"), "a_b");
+        assert_eq!(identifier("_2-bit adder"), "m2_bit_adder");
+    }
+
+    #[test]
+    fn ports_get_names_vhdl_can_tell_apart() {
+        let (upper, lower, word) = (Gate::input("A"), Gate::input("a"), Gate::input("in"));
+        let tree = Gate::new(
+            GateType::OR,
+            vec![Gate::new(GateType::AND, vec![upper, lower]), word],
+        );
+        let text = structural(&tree, "or", "out");
+        assert!(text.contains("entity or_v is"), "{}", text);
+        for port in [
+            "A : in std_logic",
+            "a_2 : in std_logic",
+            "in_v : in std_logic",
+            "out_v : out std_logic",
+        ] {
+            assert!(text.contains(port), "no {} in {}", port, text);
+        }
+        assert!(text.contains("port map (A, a_2, n1)"), "{}", text);
+
+        let text = dataflow(&tree, "entity", "OUT");
+        assert!(text.contains("entity entity_v is"), "{}", text);
+        assert!(text.contains("OUT_v <= (A and a_2) or in_v;"), "{}", text);
+    }
+}